@@ -0,0 +1,11 @@
+CREATE TABLE unsubscribe_tokens(
+    unsubscribe_token TEXT NOT NULL,
+    subscriber_id uuid NOT NULL UNIQUE
+        REFERENCES subscriptions (id),
+    PRIMARY KEY (unsubscribe_token)
+);
+
+-- Existing subscribers need a token too, otherwise the issues we send them have no working unsubscribe link.
+INSERT INTO unsubscribe_tokens (unsubscribe_token, subscriber_id)
+SELECT replace(gen_random_uuid()::text, '-', ''), id
+FROM subscriptions;
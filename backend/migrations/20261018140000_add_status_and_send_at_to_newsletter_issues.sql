@@ -0,0 +1,7 @@
+-- Scheduled issues are only published (and delivered) once `send_at` has passed.
+ALTER TABLE newsletter_issues ADD COLUMN status TEXT NOT NULL DEFAULT 'published';
+ALTER TABLE newsletter_issues ADD COLUMN send_at timestamptz NULL;
+ALTER TABLE newsletter_issues ALTER COLUMN published_at DROP NOT NULL;
+CREATE INDEX newsletter_issues_scheduled_send_at_idx
+    ON newsletter_issues (send_at)
+    WHERE status = 'scheduled';
@@ -0,0 +1,9 @@
+CREATE TABLE newsletter_drafts (
+    draft_id uuid NOT NULL,
+    title TEXT NOT NULL DEFAULT '',
+    content_text TEXT NOT NULL DEFAULT '',
+    content_html TEXT NOT NULL DEFAULT '',
+    created_at timestamptz NOT NULL DEFAULT now(),
+    updated_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY(draft_id)
+);
@@ -0,0 +1,14 @@
+-- One row per recipient of an issue, updated after every delivery attempt.
+CREATE TABLE issue_deliveries (
+    newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues(newsletter_issue_id),
+    subscriber_email TEXT NOT NULL,
+    n_attempts SMALLINT NOT NULL,
+    -- 'sent', 'retrying', 'failed', or 'requeued' once a failure has been requeued
+    outcome TEXT NOT NULL,
+    provider_message_id TEXT NULL,
+    last_error TEXT NULL,
+    first_attempted_at timestamptz NOT NULL,
+    last_attempted_at timestamptz NOT NULL,
+    sent_at timestamptz NULL,
+    PRIMARY KEY(newsletter_issue_id, subscriber_email)
+);
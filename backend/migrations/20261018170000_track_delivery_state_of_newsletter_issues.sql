@@ -0,0 +1,14 @@
+-- Issues are now 'scheduled', 'sending', 'paused', 'cancelled' or 'completed'.
+-- The worker only delivers issues that are 'sending'.
+UPDATE newsletter_issues
+SET status = CASE
+    WHEN EXISTS (
+        SELECT 1 FROM issue_delivery_queue
+        WHERE issue_delivery_queue.newsletter_issue_id = newsletter_issues.newsletter_issue_id
+    ) THEN 'sending'
+    ELSE 'completed'
+END
+WHERE status = 'published';
+ALTER TABLE newsletter_issues ALTER COLUMN status SET DEFAULT 'sending';
+CREATE INDEX newsletter_issues_sending_idx ON newsletter_issues (newsletter_issue_id)
+    WHERE status = 'sending';
@@ -0,0 +1,9 @@
+-- Addresses we must not email anymore, because they hard-bounced or reported us as spam.
+CREATE TABLE suppressions (
+    email TEXT NOT NULL PRIMARY KEY,
+    -- 'hard_bounce' or 'spam_complaint'
+    reason TEXT NOT NULL,
+    -- What the email service told us, e.g. the bounce description
+    details TEXT NULL,
+    suppressed_at timestamptz NOT NULL
+);
@@ -0,0 +1,4 @@
+-- When existing subscribers confirmed wasn't recorded, so it stays empty for them
+ALTER TABLE subscriptions ADD COLUMN confirmed_at timestamptz NULL;
+-- How the subscriber was added: 'signup_form' or 'import'
+ALTER TABLE subscriptions ADD COLUMN source TEXT NOT NULL DEFAULT 'signup_form';
@@ -0,0 +1,12 @@
+-- Proof that access and erasure requests were fulfilled. Only a hash of the address is kept, so
+-- that the record itself doesn't undo an erasure.
+CREATE TABLE data_subject_requests (
+    request_id uuid NOT NULL PRIMARY KEY,
+    -- 'access' or 'erasure'
+    kind TEXT NOT NULL,
+    -- Hex-encoded SHA-256 of the address
+    email_hash TEXT NOT NULL,
+    fulfilled_by uuid NOT NULL REFERENCES users(user_id),
+    fulfilled_at timestamptz NOT NULL
+);
+CREATE INDEX data_subject_requests_email_hash_idx ON data_subject_requests (email_hash);
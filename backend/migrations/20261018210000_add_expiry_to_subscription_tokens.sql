@@ -0,0 +1,19 @@
+ALTER TABLE subscription_tokens
+    ADD COLUMN created_at timestamptz NULL,
+    ADD COLUMN expires_at timestamptz NULL;
+
+-- Existing links were sent at signup. Give them a week, so that nobody who is about to confirm
+-- finds their link expired.
+UPDATE subscription_tokens
+SET
+    created_at = subscriptions.subscribed_at,
+    expires_at = now() + interval '7 days'
+FROM subscriptions
+WHERE subscriptions.id = subscription_tokens.subscriber_id;
+
+ALTER TABLE subscription_tokens
+    ALTER COLUMN created_at SET NOT NULL,
+    ALTER COLUMN expires_at SET NOT NULL;
+
+-- To find a subscriber's latest token when they sign up again
+CREATE INDEX subscription_tokens_subscriber_id_idx ON subscription_tokens (subscriber_id);
@@ -0,0 +1,50 @@
+CREATE TABLE lists (
+    list_id uuid PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT NOT NULL DEFAULT '',
+    -- Signups and issues that don't name a list use this one
+    is_default BOOLEAN NOT NULL DEFAULT false,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE UNIQUE INDEX lists_default_idx ON lists (is_default) WHERE is_default;
+
+-- Everyone so far subscribed to the one newsletter there was
+INSERT INTO lists (list_id, name, is_default)
+VALUES (gen_random_uuid(), 'Newsletter', true);
+
+-- Each list is confirmed on its own; `subscriptions.status` says whether the address itself was
+-- confirmed and whether it may be emailed at all.
+CREATE TABLE list_memberships (
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id),
+    list_id uuid NOT NULL REFERENCES lists (list_id),
+    -- 'pending_confirmation', 'confirmed' or 'unsubscribed'
+    status TEXT NOT NULL,
+    joined_at timestamptz NOT NULL DEFAULT now(),
+    confirmed_at timestamptz NULL,
+    PRIMARY KEY (subscriber_id, list_id)
+);
+CREATE INDEX list_memberships_list_id_idx ON list_memberships (list_id);
+
+-- Bounced and complained addresses keep their membership, the suppression stops their deliveries
+INSERT INTO list_memberships (subscriber_id, list_id, status, joined_at, confirmed_at)
+SELECT
+    id,
+    (SELECT list_id FROM lists WHERE is_default),
+    CASE status
+        WHEN 'pending_confirmation' THEN 'pending_confirmation'
+        WHEN 'unsubscribed' THEN 'unsubscribed'
+        ELSE 'confirmed'
+    END,
+    subscribed_at,
+    confirmed_at
+FROM subscriptions;
+
+-- The list that following the link confirms
+ALTER TABLE subscription_tokens ADD COLUMN list_id uuid NULL REFERENCES lists (list_id);
+UPDATE subscription_tokens SET list_id = (SELECT list_id FROM lists WHERE is_default);
+ALTER TABLE subscription_tokens ALTER COLUMN list_id SET NOT NULL;
+
+-- The list whose members receive the issue
+ALTER TABLE newsletter_issues ADD COLUMN list_id uuid NULL REFERENCES lists (list_id);
+UPDATE newsletter_issues SET list_id = (SELECT list_id FROM lists WHERE is_default);
+ALTER TABLE newsletter_issues ALTER COLUMN list_id SET NOT NULL;
@@ -0,0 +1,17 @@
+-- Free-form labels like 'beta', and custom attributes like {"plan": "pro"}, set by admins
+ALTER TABLE subscriptions
+    ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN attributes JSONB NOT NULL DEFAULT '{}';
+CREATE INDEX subscriptions_tags_idx ON subscriptions USING GIN (tags);
+
+CREATE TABLE segments (
+    segment_id uuid PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    -- A `SegmentFilter`, see `domain::segment`
+    filter JSONB NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    updated_at timestamptz NOT NULL DEFAULT now()
+);
+
+-- Narrows down the members of the issue's list who receive it
+ALTER TABLE newsletter_issues ADD COLUMN segment_id uuid NULL REFERENCES segments (segment_id);
@@ -0,0 +1,11 @@
+-- A subscriber's request to move their subscription to another address, which takes effect once
+-- they follow the link we send to the new address.
+CREATE TABLE email_change_tokens (
+    email_change_token TEXT NOT NULL PRIMARY KEY,
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id),
+    new_email TEXT NOT NULL,
+    created_at timestamptz NOT NULL,
+    expires_at timestamptz NOT NULL
+);
+
+CREATE INDEX email_change_tokens_subscriber_id_idx ON email_change_tokens (subscriber_id);
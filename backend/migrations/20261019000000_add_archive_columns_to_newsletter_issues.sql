@@ -0,0 +1,30 @@
+-- Published issues are listed in the public archive, ordered by when they were published.
+ALTER TABLE newsletter_issues
+    ALTER COLUMN published_at TYPE timestamptz USING published_at::timestamptz;
+
+-- The stable part of an issue's archive URL. It's derived from the title when the issue is
+-- stored and never changes afterwards, so that shared links keep working.
+-- Issues sent before there was an archive were written for their recipients only, some of
+-- them for a list or segment, so they stay out of it unless an admin publishes them.
+ALTER TABLE newsletter_issues
+    ADD COLUMN slug TEXT NULL,
+    ADD COLUMN exclude_from_archive BOOLEAN NOT NULL DEFAULT true;
+ALTER TABLE newsletter_issues ALTER COLUMN exclude_from_archive SET DEFAULT false;
+
+UPDATE newsletter_issues
+SET slug = concat_ws(
+    '-',
+    nullif(
+        trim(BOTH '-' FROM left(
+            trim(BOTH '-' FROM regexp_replace(lower(title), '[^a-z0-9]+', '-', 'g')),
+            60
+        )),
+        ''
+    ),
+    left(replace(newsletter_issue_id::text, '-', ''), 8)
+);
+
+ALTER TABLE newsletter_issues ALTER COLUMN slug SET NOT NULL;
+CREATE UNIQUE INDEX newsletter_issues_slug_idx ON newsletter_issues (slug);
+CREATE INDEX newsletter_issues_archive_idx ON newsletter_issues (published_at DESC)
+    WHERE published_at IS NOT NULL AND NOT exclude_from_archive;
@@ -0,0 +1,15 @@
+-- Email services report bounces with whatever casing the recipient's server used, so addresses
+-- are compared case-insensitively. Keep only the latest suppression of each address.
+DELETE FROM suppressions
+WHERE email IN (
+    SELECT email
+    FROM (
+        SELECT
+            email,
+            row_number() OVER (PARTITION BY lower(email) ORDER BY suppressed_at DESC) AS n
+        FROM suppressions
+    ) AS ranked
+    WHERE n > 1
+);
+CREATE UNIQUE INDEX suppressions_lower_email_idx ON suppressions (lower(email));
+CREATE INDEX subscriptions_lower_email_idx ON subscriptions (lower(email));
@@ -0,0 +1,6 @@
+-- Addresses are now identified by an HMAC with a server-side key. The plain SHA-256 hashes
+-- recorded so far can be reversed by hashing a list of known addresses, and can't be converted
+-- without knowing the address, so they are dropped: those requests stay on record, but can no
+-- longer be looked up by address.
+ALTER TABLE data_subject_requests ALTER COLUMN email_hash DROP NOT NULL;
+UPDATE data_subject_requests SET email_hash = NULL;
@@ -0,0 +1,12 @@
+-- The lists whose members receive the issue. Members of several of them receive it once.
+CREATE TABLE newsletter_issue_lists (
+    newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+    list_id uuid NOT NULL REFERENCES lists (list_id),
+    PRIMARY KEY (newsletter_issue_id, list_id)
+);
+
+INSERT INTO newsletter_issue_lists (newsletter_issue_id, list_id)
+SELECT newsletter_issue_id, list_id
+FROM newsletter_issues;
+
+ALTER TABLE newsletter_issues DROP COLUMN list_id;
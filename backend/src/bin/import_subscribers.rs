@@ -0,0 +1,115 @@
+//! Import subscribers from a CSV file, like `POST /api/admin/subscribers/import` does.
+//!
+//! Usage: `import_subscribers <file.csv> --mode <confirmed|send_confirmation> [--list <list_id>]`
+//!
+//! The subscribers join the default list unless another one is given. The report is printed to
+//! stdout as JSON, the logs go to stderr.
+
+use std::io::Read;
+use std::process::ExitCode;
+
+use anyhow::Context;
+use uuid::Uuid;
+use zero2prod::{
+    configuration::get_configuration,
+    lists::target_list,
+    startup::get_connection_pool,
+    subscriber_import::{ImportMode, SubscriberImport},
+    telemetry,
+};
+
+const USAGE: &str =
+    "Usage: import_subscribers <file.csv> --mode <confirmed|send_confirmation> [--list <list_id>]";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let subscriber =
+        telemetry::get_subscriber("import_subscribers".into(), "info".into(), std::io::stderr);
+    telemetry::init_subscriber(subscriber);
+
+    let arguments = match parse_arguments(std::env::args().skip(1)) {
+        Ok(arguments) => arguments,
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match import(&arguments).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Failed to import '{}': {e:?}", arguments.path);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+struct Arguments {
+    path: String,
+    mode: ImportMode,
+    list_id: Option<Uuid>,
+}
+
+fn parse_arguments(mut arguments: impl Iterator<Item = String>) -> Result<Arguments, String> {
+    let mut path = None;
+    let mut mode = None;
+    let mut list_id = None;
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--mode" => {
+                let value = arguments.next().ok_or("--mode needs a value")?;
+                mode = Some(value.parse()?);
+            }
+            "--list" => {
+                let value = arguments.next().ok_or("--list needs a value")?;
+                list_id = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("'{value}' isn't a list id"))?,
+                );
+            }
+            _ if path.is_none() => path = Some(argument),
+            _ => return Err(format!("Unexpected argument '{argument}'")),
+        }
+    }
+    match (path, mode) {
+        (Some(path), Some(mode)) => Ok(Arguments {
+            path,
+            mode,
+            list_id,
+        }),
+        _ => Err("Both a file and a mode are required".to_string()),
+    }
+}
+
+async fn import(arguments: &Arguments) -> Result<(), anyhow::Error> {
+    let configuration = get_configuration();
+    let pool = get_connection_pool(&configuration.database);
+    let email_client = configuration.email_client.client();
+    let base_url = configuration.application.base_url;
+    let list_id = target_list(&pool, arguments.list_id)
+        .await?
+        .context("No such list")?;
+
+    let mut file = std::fs::File::open(&arguments.path)?;
+    let mut import = SubscriberImport::new(
+        &pool,
+        email_client.as_ref(),
+        &base_url,
+        arguments.mode,
+        list_id,
+        configuration.subscriptions.confirmation_token_ttl(),
+        &configuration.data_requests.hash_key,
+    );
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        import.push(&buffer[..n]).await?;
+    }
+    let report = import.finish().await?;
+
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
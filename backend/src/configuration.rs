@@ -1,10 +1,18 @@
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
 use secrecy::{ExposeSecret, Secret};
-use serde_aux::field_attributes::deserialize_number_from_string;
+use serde_aux::field_attributes::{
+    deserialize_number_from_string, deserialize_option_number_from_string,
+};
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
 
-use crate::{domain::SubscriberEmail, email_client::EmailClient};
+use crate::{
+    domain::SubscriberEmail,
+    email_client::{EmailSender, FileDropClient, PostmarkClient, SmtpClient},
+};
 
 pub fn get_configuration() -> Settings {
     let base_path = std::env::current_dir().expect("Failed to determine the current directory");
@@ -75,6 +83,11 @@ pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
+    pub worker: WorkerSettings,
+    pub subscriptions: SubscriptionSettings,
+    pub feeds: FeedSettings,
+    pub data_requests: DataRequestSettings,
+    pub webhook: WebhookSettings,
     pub redis_uri: Secret<String>,
 }
 
@@ -117,14 +130,146 @@ pub struct ApplicationSettings {
     pub cookie_store_key: Secret<String>,
     pub secure_cookies: bool,
     pub frontend_files_directory: String,
+    /// Prepended to the subject of test issues, so reviewers can tell them apart from real ones.
+    #[serde(default = "default_test_subject_prefix")]
+    pub test_subject_prefix: String,
+}
+
+fn default_test_subject_prefix() -> String {
+    "[TEST]".to_string()
+}
+
+/// The credentials the email service sends with its webhook requests, using HTTP basic auth.
+#[derive(Clone, serde::Deserialize)]
+pub struct WebhookSettings {
+    pub username: String,
+    pub password: Secret<String>,
+}
+
+/// The audit log of data subject requests identifies addresses by a keyed hash, which can't be
+/// reversed by hashing a list of known addresses without the key.
+#[derive(Clone, serde::Deserialize)]
+pub struct DataRequestSettings {
+    pub hash_key: Secret<String>,
+}
+
+/// The background workers that deliver newsletter issues.
+#[derive(Clone, serde::Deserialize)]
+pub struct WorkerSettings {
+    /// How many workers deliver concurrently.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub concurrency: NonZeroUsize,
+    /// How many deliveries a worker dequeues and sends to the email service at once.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub batch_size: NonZeroUsize,
+    /// How often scheduled issues are checked for being due.
+    pub poll_interval_milliseconds: u64,
+    /// How long a worker waits before checking an empty queue again, unless it is woken up
+    /// earlier because tasks have been enqueued.
+    pub empty_queue_sleep_milliseconds: u64,
+    #[serde(default)]
+    pub rate_limit: RateLimitSettings,
+}
+
+impl WorkerSettings {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_milliseconds)
+    }
+
+    pub fn empty_queue_sleep(&self) -> Duration {
+        Duration::from_millis(self.empty_queue_sleep_milliseconds)
+    }
+}
+
+/// How long confirmation links stay valid and how long unconfirmed signups are kept.
+#[derive(Clone, serde::Deserialize)]
+pub struct SubscriptionSettings {
+    /// How long after it was sent a confirmation link can be followed.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub confirmation_token_ttl_hours: u64,
+    /// How long a pending subscriber has to wait before signing up again sends them another
+    /// confirmation email, so that the form can't be used to flood someone's inbox.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub resend_confirmation_interval_seconds: u64,
+    /// How long a pending subscription is kept after its last confirmation link expired.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub pending_retention_days: u64,
+}
+
+impl SubscriptionSettings {
+    pub fn confirmation_token_ttl(&self) -> Duration {
+        Duration::from_secs(self.confirmation_token_ttl_hours * 60 * 60)
+    }
+
+    pub fn resend_confirmation_interval(&self) -> Duration {
+        Duration::from_secs(self.resend_confirmation_interval_seconds)
+    }
+
+    pub fn pending_retention(&self) -> Duration {
+        Duration::from_secs(self.pending_retention_days * 24 * 60 * 60)
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct FeedSettings {
+    /// How many of the latest issues the RSS and Atom feeds list.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub item_limit: i64,
+}
+
+/// Limits on how fast issues are delivered, to stay within the email service's rate limit and
+/// to avoid being throttled by the receiving domains. Every limit is optional.
+#[derive(Clone, Default, serde::Deserialize)]
+pub struct RateLimitSettings {
+    /// How many emails are sent per second, to all domains together.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub messages_per_second: Option<NonZeroU32>,
+    /// How many emails to the same domain, e.g. `gmail.com`, are sent concurrently.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub domain_concurrency: Option<NonZeroUsize>,
+    /// How many emails are sent to the same domain per minute.
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub domain_messages_per_minute: Option<NonZeroU32>,
 }
 
 #[derive(Clone, serde::Deserialize)]
 pub struct EmailClientSettings {
+    #[serde(default)]
+    pub kind: EmailClientKind,
     pub base_url: String,
     pub sender_email: String,
     pub authorization_token: Option<Secret<String>>,
     pub timeout_milliseconds: u64,
+    pub smtp: Option<SmtpSettings>,
+    pub file_drop: Option<FileDropSettings>,
+}
+
+/// Which backend delivers our emails.
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailClientKind {
+    /// The Postmark email API, configured by `base_url` and `authorization_token`.
+    #[default]
+    Postmark,
+    /// An SMTP relay, configured by the `smtp` section.
+    Smtp,
+    /// `.eml` files in a local directory, configured by the `file_drop` section.
+    FileDrop,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct SmtpSettings {
+    pub host: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<Secret<String>>,
+    pub starttls: bool,
+}
+
+#[derive(Clone, serde::Deserialize)]
+pub struct FileDropSettings {
+    pub directory: PathBuf,
 }
 
 impl EmailClientSettings {
@@ -136,16 +281,49 @@ impl EmailClientSettings {
         Duration::from_millis(self.timeout_milliseconds)
     }
 
-    pub fn client(self) -> EmailClient {
+    pub fn client(self) -> Arc<dyn EmailSender> {
         let sender_email = self.sender().expect("Invalid sender email address");
         let timeout = self.timeout();
-        EmailClient::new(
-            self.base_url,
-            sender_email,
-            // TODO: Can we do better than this 'expect'?
-            self.authorization_token
-                .expect("email_client.authorization_token should have been set by now"),
-            timeout,
-        )
+        match self.kind {
+            EmailClientKind::Postmark => Arc::new(PostmarkClient::new(
+                self.base_url,
+                sender_email,
+                // TODO: Can we do better than this 'expect'?
+                self.authorization_token
+                    .expect("email_client.authorization_token should have been set by now"),
+                timeout,
+            )),
+            EmailClientKind::Smtp => {
+                let smtp = self
+                    .smtp
+                    .expect("email_client.smtp must be set when email_client.kind is 'smtp'");
+                let credentials = smtp.username.map(|username| {
+                    let password = smtp.password.expect(
+                        "email_client.smtp.password must be set when a username is configured",
+                    );
+                    (username, password)
+                });
+                Arc::new(
+                    SmtpClient::new(
+                        &smtp.host,
+                        smtp.port,
+                        credentials,
+                        smtp.starttls,
+                        sender_email,
+                        timeout,
+                    )
+                    .expect("Failed to configure the SMTP client"),
+                )
+            }
+            EmailClientKind::FileDrop => {
+                let file_drop = self.file_drop.expect(
+                    "email_client.file_drop must be set when email_client.kind is 'file_drop'",
+                );
+                Arc::new(
+                    FileDropClient::new(file_drop.directory, sender_email)
+                        .expect("Failed to create the email drop directory"),
+                )
+            }
+        }
     }
 }
@@ -0,0 +1,253 @@
+//! Reading and writing RFC 4180 CSV. The reader is push-based, so that uploads can be parsed as
+//! their chunks arrive.
+
+/// One line of the file, or several when a quoted field spans lines.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CsvRecord {
+    /// Where the record starts in the file, counting from 1.
+    pub line: usize,
+    pub fields: Result<Vec<String>, MalformedRecord>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MalformedRecord {
+    InvalidUtf8,
+    /// The file ended inside a quoted field.
+    UnterminatedQuote,
+}
+
+impl MalformedRecord {
+    pub fn description(&self) -> &'static str {
+        match self {
+            MalformedRecord::InvalidUtf8 => "The line is not valid UTF-8",
+            MalformedRecord::UnterminatedQuote => "The line has a quoted field that never ends",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    StartOfField,
+    Unquoted,
+    Quoted,
+    /// Just read a quote inside a quoted field: either the field ends or the quote was escaped.
+    QuoteInQuoted,
+}
+
+pub struct CsvReader {
+    state: State,
+    field: Vec<u8>,
+    fields: Vec<Vec<u8>>,
+    line: usize,
+    record_line: usize,
+}
+
+impl Default for CsvReader {
+    fn default() -> Self {
+        Self {
+            state: State::StartOfField,
+            field: Vec::new(),
+            fields: Vec::new(),
+            line: 1,
+            record_line: 1,
+        }
+    }
+}
+
+impl CsvReader {
+    /// Read the next chunk of the file, returning the records it completes. A record that is
+    /// still incomplete at the end of the chunk is kept until the next one.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<CsvRecord> {
+        let mut records = Vec::new();
+        for &byte in chunk {
+            match (self.state, byte) {
+                (State::Quoted, b'"') => self.state = State::QuoteInQuoted,
+                (State::Quoted, _) => {
+                    if byte == b'\n' {
+                        self.line += 1;
+                    }
+                    self.field.push(byte);
+                }
+                (State::StartOfField, b'"') => self.state = State::Quoted,
+                (State::QuoteInQuoted, b'"') => {
+                    self.field.push(b'"');
+                    self.state = State::Quoted;
+                }
+                (_, b',') => self.end_field(),
+                (_, b'\n') => {
+                    records.extend(self.end_record());
+                    self.line += 1;
+                    self.record_line = self.line;
+                }
+                // Line endings are either "\r\n" or "\n"
+                (_, b'\r') => {}
+                (_, _) => {
+                    self.field.push(byte);
+                    self.state = State::Unquoted;
+                }
+            }
+        }
+        records
+    }
+
+    /// Return the last record of the file, if it didn't end with a line break.
+    pub fn finish(mut self) -> Option<CsvRecord> {
+        if self.state == State::Quoted {
+            return Some(CsvRecord {
+                line: self.record_line,
+                fields: Err(MalformedRecord::UnterminatedQuote),
+            });
+        }
+        self.end_record()
+    }
+
+    fn end_field(&mut self) {
+        self.fields.push(std::mem::take(&mut self.field));
+        self.state = State::StartOfField;
+    }
+
+    fn end_record(&mut self) -> Option<CsvRecord> {
+        let is_blank_line = self.fields.is_empty() && self.field.is_empty();
+        let quoted_empty_field = self.state == State::QuoteInQuoted;
+        self.end_field();
+        let fields = std::mem::take(&mut self.fields);
+        if is_blank_line && !quoted_empty_field {
+            return None;
+        }
+
+        let fields = fields
+            .into_iter()
+            .map(|field| String::from_utf8(field).map_err(|_| MalformedRecord::InvalidUtf8))
+            .collect();
+        Some(CsvRecord {
+            line: self.record_line,
+            fields,
+        })
+    }
+}
+
+/// Append a record to `buffer`, ending it with a line break. Fields are quoted when they contain
+/// a separator, a quote or a line break.
+pub fn write_record(buffer: &mut String, fields: &[&str]) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            buffer.push(',');
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            buffer.push('"');
+            buffer.push_str(&field.replace('"', "\"\""));
+            buffer.push('"');
+        } else {
+            buffer.push_str(field);
+        }
+    }
+    buffer.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CsvReader, CsvRecord, MalformedRecord, write_record};
+
+    fn read_all(chunks: &[&[u8]]) -> Vec<CsvRecord> {
+        let mut reader = CsvReader::default();
+        let mut records = Vec::new();
+        for chunk in chunks {
+            records.extend(reader.feed(chunk));
+        }
+        records.extend(reader.finish());
+        records
+    }
+
+    fn record(line: usize, fields: &[&str]) -> CsvRecord {
+        CsvRecord {
+            line,
+            fields: Ok(fields.iter().map(|f| f.to_string()).collect()),
+        }
+    }
+
+    #[test]
+    fn plain_records_are_split_on_commas_and_line_breaks() {
+        let records = read_all(&[b"email,name\r\nursula@example.com,Ursula\n,\n"]);
+        assert_eq!(
+            records,
+            vec![
+                record(1, &["email", "name"]),
+                record(2, &["ursula@example.com", "Ursula"]),
+                record(3, &["", ""]),
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_fields_can_hold_commas_quotes_and_line_breaks() {
+        let records =
+            read_all(&[b"\"Le Guin, Ursula\",\"She said \"\"hi\"\"\"\n\"two\nlines\",x\nlast"]);
+        assert_eq!(
+            records,
+            vec![
+                record(1, &["Le Guin, Ursula", "She said \"hi\""]),
+                record(2, &["two\nlines", "x"]),
+                record(4, &["last"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn records_can_be_split_across_chunks() {
+        let records = read_all(&[b"ursula@exa", b"mple.com,\"Urs", b"ula\"\r", b"\nnext"]);
+        assert_eq!(
+            records,
+            vec![
+                record(1, &["ursula@example.com", "Ursula"]),
+                record(2, &["next"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn blank_lines_are_skipped() {
+        let records = read_all(&[b"\n\na,b\n\r\n\"\"\n"]);
+        assert_eq!(records, vec![record(3, &["a", "b"]), record(5, &[""])]);
+    }
+
+    #[test]
+    fn malformed_records_are_reported_with_their_line() {
+        let records = read_all(&[b"a,\xff\n\"never ends\nb"]);
+        assert_eq!(
+            records,
+            vec![
+                CsvRecord {
+                    line: 1,
+                    fields: Err(MalformedRecord::InvalidUtf8),
+                },
+                CsvRecord {
+                    line: 2,
+                    fields: Err(MalformedRecord::UnterminatedQuote),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn written_records_are_read_back_unchanged() {
+        let fields = [
+            "plain",
+            "",
+            "Le Guin, Ursula",
+            "She said \"hi\"",
+            "two\r\nlines",
+        ];
+        let mut buffer = String::new();
+        write_record(&mut buffer, &fields);
+        write_record(&mut buffer, &["last"]);
+
+        assert_eq!(
+            buffer,
+            "plain,,\"Le Guin, Ursula\",\"She said \"\"hi\"\"\",\"two\r\nlines\"\r\nlast\r\n"
+        );
+        assert_eq!(
+            read_all(&[buffer.as_bytes()]),
+            vec![record(1, &fields), record(3, &["last"])]
+        );
+    }
+}
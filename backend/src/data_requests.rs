@@ -0,0 +1,534 @@
+//! Requests from people to see or erase what we store about their email address.
+
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::lists::{Membership, memberships};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataRequestKind {
+    Access,
+    Erasure,
+}
+
+impl DataRequestKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataRequestKind::Access => "access",
+            DataRequestKind::Erasure => "erasure",
+        }
+    }
+}
+
+/// Everything stored about an email address.
+#[derive(serde::Serialize)]
+pub struct AccessReport {
+    pub email: String,
+    pub generated_at: DateTime<Utc>,
+    pub subscription: Option<StoredSubscription>,
+    pub lists: Vec<Membership>,
+    pub subscription_tokens: Vec<String>,
+    pub unsubscribe_token: Option<String>,
+    /// Addresses the subscriber asked to move to, and hasn't confirmed yet.
+    pub pending_email_changes: Vec<String>,
+    pub deliveries: Vec<StoredDelivery>,
+    pub queued_deliveries: Vec<QueuedDelivery>,
+    pub delivery_failures: Vec<StoredDeliveryFailure>,
+    pub suppression: Option<StoredSuppression>,
+}
+
+impl AccessReport {
+    /// Whether nothing at all is stored about the address.
+    pub fn is_empty(&self) -> bool {
+        self.subscription.is_none()
+            && self.subscription_tokens.is_empty()
+            && self.unsubscribe_token.is_none()
+            && self.pending_email_changes.is_empty()
+            && self.deliveries.is_empty()
+            && self.queued_deliveries.is_empty()
+            && self.delivery_failures.is_empty()
+            && self.suppression.is_none()
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct StoredSubscription {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    confirmed_at: Option<DateTime<Utc>>,
+    unsubscribed_at: Option<DateTime<Utc>>,
+    source: String,
+    tags: Vec<String>,
+    attributes: serde_json::Value,
+}
+
+#[derive(serde::Serialize)]
+pub struct StoredDelivery {
+    newsletter_issue_id: Uuid,
+    title: String,
+    outcome: String,
+    n_attempts: i16,
+    provider_message_id: Option<String>,
+    last_error: Option<String>,
+    first_attempted_at: DateTime<Utc>,
+    last_attempted_at: DateTime<Utc>,
+    sent_at: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize)]
+pub struct QueuedDelivery {
+    newsletter_issue_id: Uuid,
+    title: String,
+    n_retries: i16,
+    execute_after: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+pub struct StoredDeliveryFailure {
+    newsletter_issue_id: Uuid,
+    title: String,
+    n_retries: i16,
+    last_error: String,
+    failed_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+pub struct StoredSuppression {
+    reason: String,
+    details: Option<String>,
+    suppressed_at: DateTime<Utc>,
+}
+
+/// How many rows an erasure deleted or pseudonymized, per kind of data.
+#[derive(serde::Serialize, Debug, Default)]
+pub struct ErasureSummary {
+    pub subscriptions: u64,
+    /// Requests to move a subscription to or from the address.
+    pub pending_email_changes: u64,
+    pub queued_deliveries: u64,
+    pub delivery_failures: u64,
+    /// Delivery log entries, which are kept under a pseudonym so that issue statistics still add
+    /// up.
+    pub pseudonymized_deliveries: u64,
+    /// Suppressions, which are kept under the address's hash so that it still isn't emailed.
+    pub suppressions: u64,
+    /// Saved responses to admin requests that mentioned the address.
+    pub idempotency_records: u64,
+}
+
+impl ErasureSummary {
+    /// Whether the address matched nothing, so that nothing was erased.
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions == 0
+            && self.pending_email_changes == 0
+            && self.queued_deliveries == 0
+            && self.delivery_failures == 0
+            && self.pseudonymized_deliveries == 0
+            && self.suppressions == 0
+            && self.idempotency_records == 0
+    }
+}
+
+#[tracing::instrument(name = "Collect the data stored about an address", skip(pool, email))]
+pub async fn access_report(pool: &PgPool, email: &str) -> Result<AccessReport, sqlx::Error> {
+    let subscription = sqlx::query_as!(
+        StoredSubscription,
+        r#"
+        SELECT
+            id,
+            email,
+            name,
+            status,
+            subscribed_at,
+            confirmed_at,
+            unsubscribed_at,
+            source,
+            tags,
+            attributes
+        FROM subscriptions
+        WHERE lower(email) = lower($1)
+        "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let lists = match &subscription {
+        Some(subscription) => memberships(pool, subscription.id).await?,
+        None => Vec::new(),
+    };
+
+    let subscription_tokens = sqlx::query!(
+        r#"
+        SELECT subscription_token
+        FROM subscription_tokens
+        JOIN subscriptions ON subscriptions.id = subscription_tokens.subscriber_id
+        WHERE lower(subscriptions.email) = lower($1)
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| row.subscription_token)
+    .collect();
+
+    let unsubscribe_token = sqlx::query!(
+        r#"
+        SELECT unsubscribe_token
+        FROM unsubscribe_tokens
+        JOIN subscriptions ON subscriptions.id = unsubscribe_tokens.subscriber_id
+        WHERE lower(subscriptions.email) = lower($1)
+        "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await?
+    .map(|row| row.unsubscribe_token);
+
+    let pending_email_changes = sqlx::query!(
+        r#"
+        SELECT new_email
+        FROM email_change_tokens
+        JOIN subscriptions ON subscriptions.id = email_change_tokens.subscriber_id
+        WHERE
+            lower(subscriptions.email) = lower($1) OR
+            lower(email_change_tokens.new_email) = lower($1)
+        ORDER BY email_change_tokens.created_at
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| row.new_email)
+    .collect();
+
+    let deliveries = sqlx::query_as!(
+        StoredDelivery,
+        r#"
+        SELECT
+            issue_deliveries.newsletter_issue_id,
+            newsletter_issues.title,
+            issue_deliveries.outcome,
+            issue_deliveries.n_attempts,
+            issue_deliveries.provider_message_id,
+            issue_deliveries.last_error,
+            issue_deliveries.first_attempted_at,
+            issue_deliveries.last_attempted_at,
+            issue_deliveries.sent_at
+        FROM issue_deliveries
+        JOIN newsletter_issues
+            ON newsletter_issues.newsletter_issue_id = issue_deliveries.newsletter_issue_id
+        WHERE lower(issue_deliveries.subscriber_email) = lower($1)
+        ORDER BY issue_deliveries.first_attempted_at
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let queued_deliveries = sqlx::query_as!(
+        QueuedDelivery,
+        r#"
+        SELECT
+            issue_delivery_queue.newsletter_issue_id,
+            newsletter_issues.title,
+            issue_delivery_queue.n_retries,
+            issue_delivery_queue.execute_after
+        FROM issue_delivery_queue
+        JOIN newsletter_issues
+            ON newsletter_issues.newsletter_issue_id = issue_delivery_queue.newsletter_issue_id
+        WHERE lower(issue_delivery_queue.subscriber_email) = lower($1)
+        ORDER BY issue_delivery_queue.execute_after
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let delivery_failures = sqlx::query_as!(
+        StoredDeliveryFailure,
+        r#"
+        SELECT
+            issue_delivery_failures.newsletter_issue_id,
+            newsletter_issues.title,
+            issue_delivery_failures.n_retries,
+            issue_delivery_failures.last_error,
+            issue_delivery_failures.failed_at
+        FROM issue_delivery_failures
+        JOIN newsletter_issues
+            ON newsletter_issues.newsletter_issue_id = issue_delivery_failures.newsletter_issue_id
+        WHERE lower(issue_delivery_failures.subscriber_email) = lower($1)
+        ORDER BY issue_delivery_failures.failed_at
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let suppression = sqlx::query_as!(
+        StoredSuppression,
+        r#"
+        SELECT reason, details, suppressed_at
+        FROM suppressions
+        WHERE lower(email) = lower($1)
+        "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(AccessReport {
+        email: email.to_string(),
+        generated_at: Utc::now(),
+        subscription,
+        lists,
+        subscription_tokens,
+        unsubscribe_token,
+        pending_email_changes,
+        deliveries,
+        queued_deliveries,
+        delivery_failures,
+        suppression,
+    })
+}
+
+/// Delete everything stored about `email`, except for the delivery log, where the address is
+/// replaced by a pseudonym derived from `request_id`. Addresses are matched case-insensitively.
+///
+/// Suppressions are kept with the address replaced by its `email_hash`, so that an address that
+/// bounced or complained can't be subscribed or imported again and emailed.
+#[tracing::instrument(
+    name = "Erase the data stored about an address",
+    skip(transaction, email, email_hash)
+)]
+pub async fn erase(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+    email_hash: &str,
+    request_id: Uuid,
+) -> Result<ErasureSummary, sqlx::Error> {
+    let mut summary = ErasureSummary::default();
+
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM subscription_tokens
+        WHERE subscriber_id IN (SELECT id FROM subscriptions WHERE lower(email) = lower($1))
+        "#,
+        email
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM unsubscribe_tokens
+        WHERE subscriber_id IN (SELECT id FROM subscriptions WHERE lower(email) = lower($1))
+        "#,
+        email
+    );
+    transaction.execute(query).await?;
+    // Including requests to move another subscription to the erased address
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM email_change_tokens
+        WHERE
+            lower(new_email) = lower($1) OR
+            subscriber_id IN (SELECT id FROM subscriptions WHERE lower(email) = lower($1))
+        "#,
+        email
+    );
+    summary.pending_email_changes = transaction.execute(query).await?.rows_affected();
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM list_memberships
+        WHERE subscriber_id IN (SELECT id FROM subscriptions WHERE lower(email) = lower($1))
+        "#,
+        email
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        "DELETE FROM subscriptions WHERE lower(email) = lower($1)",
+        email
+    );
+    summary.subscriptions = transaction.execute(query).await?.rows_affected();
+
+    let query = sqlx::query!(
+        "DELETE FROM issue_delivery_queue WHERE lower(subscriber_email) = lower($1)",
+        email
+    );
+    summary.queued_deliveries = transaction.execute(query).await?.rows_affected();
+
+    // Requeuing a failure for an erased address would be pointless
+    let query = sqlx::query!(
+        "DELETE FROM issue_delivery_failures WHERE lower(subscriber_email) = lower($1)",
+        email
+    );
+    summary.delivery_failures = transaction.execute(query).await?.rows_affected();
+
+    // The message id leads to the address in the email service's logs, and errors may quote it.
+    // Each spelling of the address gets its own pseudonym, as an issue may have been delivered
+    // to several of them.
+    let query = sqlx::query!(
+        r#"
+        UPDATE issue_deliveries
+        SET
+            subscriber_email = concat('erased-', $2::text, '-', spellings.n),
+            provider_message_id = NULL,
+            last_error = NULL
+        FROM (
+            SELECT subscriber_email, dense_rank() OVER (ORDER BY subscriber_email) AS n
+            FROM issue_deliveries
+            WHERE lower(subscriber_email) = lower($1)
+        ) AS spellings
+        WHERE issue_deliveries.subscriber_email = spellings.subscriber_email
+        "#,
+        email,
+        request_id.to_string()
+    );
+    summary.pseudonymized_deliveries = transaction.execute(query).await?.rows_affected();
+
+    // Bounce and complaint details may quote the address. An earlier erasure may have left a
+    // hashed suppression already, which this one replaces.
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM suppressions
+        WHERE
+            email = $2 AND
+            EXISTS (SELECT 1 FROM suppressions WHERE lower(email) = lower($1))
+        "#,
+        email,
+        email_hash
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"
+        UPDATE suppressions
+        SET email = $2, details = NULL
+        WHERE lower(email) = lower($1)
+        "#,
+        email,
+        email_hash
+    );
+    summary.suppressions = transaction.execute(query).await?.rows_affected();
+
+    let records = idempotency_records_mentioning(transaction, email).await?;
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM idempotency
+        WHERE (user_id, idempotency_key) IN (
+            SELECT * FROM unnest($1::uuid[], $2::text[])
+        )
+        "#,
+        &records
+            .iter()
+            .map(|(user_id, _)| *user_id)
+            .collect::<Vec<_>>(),
+        &records.into_iter().map(|(_, key)| key).collect::<Vec<_>>()
+    );
+    summary.idempotency_records = transaction.execute(query).await?.rows_affected();
+
+    Ok(summary)
+}
+
+/// The saved responses that mention `email`, by user and idempotency key. Responses that aren't
+/// UTF-8 can't be JSON that mentions it, so they are skipped.
+async fn idempotency_records_mentioning(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+) -> Result<Vec<(Uuid, String)>, sqlx::Error> {
+    let email = email.to_lowercase();
+    let mut records = Vec::new();
+    let mut rows = sqlx::query!(
+        r#"
+        SELECT user_id, idempotency_key, response_body AS "response_body!"
+        FROM idempotency
+        WHERE response_body IS NOT NULL
+        "#
+    )
+    .fetch(&mut **transaction);
+    while let Some(row) = rows.try_next().await? {
+        let mentions_email = std::str::from_utf8(&row.response_body)
+            .is_ok_and(|body| body.to_lowercase().contains(&email));
+        if mentions_email {
+            records.push((row.user_id, row.idempotency_key));
+        }
+    }
+    Ok(records)
+}
+
+/// Keep proof that a request was fulfilled, identifying the address only by `email_hash`.
+#[tracing::instrument(
+    name = "Record a fulfilled data subject request",
+    skip(executor, email_hash)
+)]
+pub async fn record_fulfilled_request<'e>(
+    executor: impl Executor<'e, Database = Postgres>,
+    request_id: Uuid,
+    kind: DataRequestKind,
+    email_hash: &str,
+    fulfilled_by: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO data_subject_requests (
+            request_id,
+            kind,
+            email_hash,
+            fulfilled_by,
+            fulfilled_at
+        )
+        VALUES ($1, $2, $3, $4, now())
+        "#,
+        request_id,
+        kind.as_str(),
+        email_hash,
+        fulfilled_by
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// How the audit log identifies `email`: a hex-encoded HMAC-SHA256 of the lowercased address.
+pub fn email_hash(key: &Secret<String>, email: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.expose_secret().as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(email.to_lowercase().as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::email_hash;
+
+    #[test]
+    fn email_hashes_ignore_the_case_of_the_address() {
+        let key = Secret::new("key".to_string());
+        assert_eq!(
+            email_hash(&key, "Ursula@Example.com"),
+            email_hash(&key, "ursula@example.com")
+        );
+        assert_ne!(
+            email_hash(&key, "ursula@example.com"),
+            email_hash(&key, "octavia@example.com")
+        );
+    }
+
+    #[test]
+    fn email_hashes_depend_on_the_key() {
+        assert_ne!(
+            email_hash(&Secret::new("key".to_string()), "ursula@example.com"),
+            email_hash(&Secret::new("other key".to_string()), "ursula@example.com")
+        );
+    }
+}
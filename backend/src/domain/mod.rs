@@ -1,7 +1,15 @@
 mod new_subscriber;
+mod newsletter_issue;
+mod segment;
 mod subscriber_email;
 mod subscriber_name;
+mod subscriber_tags;
 
 pub use new_subscriber::NewSubscriber;
+pub use newsletter_issue::{
+    NewsletterIssue, Recipient, archive_slug, preferences_link, unsubscribe_link,
+};
+pub use segment::SegmentFilter;
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
+pub use subscriber_tags::SubscriberTags;
@@ -0,0 +1,279 @@
+use minijinja::{Environment, UndefinedBehavior, Value, context};
+use uuid::Uuid;
+
+/// How many characters of the title an archive slug keeps.
+const MAX_SLUG_TITLE_LENGTH: usize = 60;
+
+/// The contents of a newsletter issue. The bodies are templates that are rendered for every
+/// recipient, see `Recipient` for the available placeholders.
+#[derive(Clone, Debug)]
+pub struct NewsletterIssue {
+    pub title: String,
+    pub content_text: String,
+    pub content_html: String,
+}
+
+/// The subscriber an issue is rendered for. Each field is available as a placeholder,
+/// e.g. `{{ name }}`.
+pub struct Recipient<'a> {
+    pub name: &'a str,
+    pub email: &'a str,
+    pub unsubscribe_url: &'a str,
+    /// Where the subscriber changes their name, address or lists.
+    pub preferences_url: &'a str,
+}
+
+impl NewsletterIssue {
+    /// Render the bodies for one recipient and append a footer with their unsubscribe link.
+    /// The HTML body escapes the values it is given; the text body doesn't.
+    pub fn render_for(self, recipient: &Recipient) -> Result<Self, minijinja::Error> {
+        Ok(self
+            .render(recipient)?
+            .with_unsubscribe_link(recipient.unsubscribe_url))
+    }
+
+    /// Render the bodies for the public archive, where nobody in particular reads them: the
+    /// name and email are empty and the links lead to the home page, which has the signup form.
+    pub fn render_for_archive(self, base_url: &str) -> Result<Self, minijinja::Error> {
+        let home_page = format!("{base_url}/");
+        self.render(&Recipient {
+            name: "",
+            email: "",
+            unsubscribe_url: &home_page,
+            preferences_url: &home_page,
+        })
+    }
+
+    fn render(self, recipient: &Recipient) -> Result<Self, minijinja::Error> {
+        let ctx = context! {
+            name => recipient.name,
+            email => recipient.email,
+            // We build this URL ourselves, so it doesn't need escaping to be put in an `href`
+            unsubscribe_url => Value::from_safe_string(recipient.unsubscribe_url.to_string()),
+            preferences_url => Value::from_safe_string(recipient.preferences_url.to_string()),
+        };
+        let (content_text, content_html) = {
+            let mut env = Environment::new();
+            // Fail on typos like `{{ nmae }}` instead of silently rendering nothing
+            env.set_undefined_behavior(UndefinedBehavior::Strict);
+            env.add_template("content.txt", &self.content_text)?;
+            env.add_template("content.html", &self.content_html)?;
+
+            (
+                env.get_template("content.txt")?.render(&ctx)?,
+                env.get_template("content.html")?.render(&ctx)?,
+            )
+        };
+
+        Ok(Self {
+            content_text,
+            content_html,
+            ..self
+        })
+    }
+
+    /// The issue as an example subscriber would receive it, with an unsubscribe link that
+    /// belongs to nobody. Fails if the bodies aren't valid templates.
+    pub fn preview(self, base_url: &str, email: &str) -> Result<Self, minijinja::Error> {
+        let unsubscribe_url = unsubscribe_link(base_url, "preview");
+        let preferences_url = preferences_link(base_url, "preview");
+        self.render_for(&Recipient {
+            name: "Subscriber",
+            email,
+            unsubscribe_url: &unsubscribe_url,
+            preferences_url: &preferences_url,
+        })
+    }
+
+    /// Check that the bodies are valid templates that only use known placeholders.
+    pub fn validate(&self) -> Result<(), minijinja::Error> {
+        self.clone()
+            .preview("", "subscriber@example.com")
+            .map(|_| ())
+    }
+
+    /// Append a footer with the subscriber's unsubscribe link to both bodies of the issue.
+    fn with_unsubscribe_link(self, unsubscribe_link: &str) -> Self {
+        Self {
+            content_html: format!(
+                "{}<p><a href=\"{unsubscribe_link}\">Unsubscribe</a></p>",
+                self.content_html
+            ),
+            content_text: format!("{}\n\nUnsubscribe: {unsubscribe_link}", self.content_text),
+            ..self
+        }
+    }
+}
+
+/// The link a subscriber follows (or their mail client POSTs to) to unsubscribe.
+pub fn unsubscribe_link(base_url: &str, unsubscribe_token: &str) -> String {
+    format!("{base_url}/api/subscriptions/unsubscribe?unsubscribe_token={unsubscribe_token}")
+}
+
+/// The stable part of an issue's archive URL: the title in lowercase ASCII words joined by
+/// dashes, followed by the start of the issue's id so that issues with the same title don't
+/// collide. The migration that added slugs computes them the same way.
+pub fn archive_slug(title: &str, newsletter_issue_id: Uuid) -> String {
+    let words: Vec<_> = title
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect();
+    let mut words = words.join("-");
+    words.truncate(MAX_SLUG_TITLE_LENGTH);
+    let words = words.trim_end_matches('-');
+
+    let id = newsletter_issue_id.simple().to_string();
+    if words.is_empty() {
+        id[..8].to_string()
+    } else {
+        format!("{words}-{}", &id[..8])
+    }
+}
+
+/// The preference center page, which authenticates the subscriber with their unsubscribe token.
+pub fn preferences_link(base_url: &str, unsubscribe_token: &str) -> String {
+    format!("{base_url}/preferences?unsubscribe_token={unsubscribe_token}")
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use uuid::Uuid;
+
+    use super::{NewsletterIssue, Recipient, archive_slug, unsubscribe_link};
+
+    fn issue(content_text: &str, content_html: &str) -> NewsletterIssue {
+        NewsletterIssue {
+            title: "Title".to_string(),
+            content_text: content_text.to_string(),
+            content_html: content_html.to_string(),
+        }
+    }
+
+    fn recipient<'a>(name: &'a str, unsubscribe_url: &'a str) -> Recipient<'a> {
+        Recipient {
+            name,
+            email: "ursula@example.com",
+            unsubscribe_url,
+            preferences_url: "https://example.com/preferences",
+        }
+    }
+
+    #[test]
+    fn unsubscribe_link_is_appended_to_both_bodies() {
+        let link = unsubscribe_link("https://example.com", "abc");
+        let issue = issue("Text", "<p>HTML</p>")
+            .render_for(&recipient("Ursula", &link))
+            .unwrap();
+
+        assert_eq!(issue.title, "Title");
+        assert_eq!(
+            issue.content_text,
+            "Text\n\nUnsubscribe: https://example.com/api/subscriptions/unsubscribe?unsubscribe_token=abc"
+        );
+        assert_eq!(
+            issue.content_html,
+            "<p>HTML</p><p><a href=\"https://example.com/api/subscriptions/unsubscribe?unsubscribe_token=abc\">Unsubscribe</a></p>"
+        );
+    }
+
+    #[test]
+    fn placeholders_are_rendered_for_the_recipient() {
+        let issue = issue(
+            "Hi {{ name }} <{{ email }}>! Leave: {{ unsubscribe_url }}",
+            "{% if name == 'Ursula' %}<p>Welcome back!</p>{% endif %}",
+        )
+        .render_for(&recipient("Ursula", "https://example.com/leave"))
+        .unwrap();
+
+        assert!(
+            issue
+                .content_text
+                .starts_with("Hi Ursula <ursula@example.com>! Leave: https://example.com/leave")
+        );
+        assert!(issue.content_html.starts_with("<p>Welcome back!</p>"));
+    }
+
+    #[test]
+    fn the_preferences_link_is_a_placeholder() {
+        let issue = issue("Manage: {{ preferences_url }}", "<p>Hi</p>")
+            .render_for(&recipient("Ursula", ""))
+            .unwrap();
+
+        assert!(
+            issue
+                .content_text
+                .starts_with("Manage: https://example.com/preferences")
+        );
+    }
+
+    #[test]
+    fn archived_issues_have_no_unsubscribe_footer() {
+        let issue = issue(
+            "{% if name %}Hi {{ name }}{% else %}Hi{% endif %}",
+            "<a href=\"{{ unsubscribe_url }}\">Leave</a>",
+        )
+        .render_for_archive("https://example.com")
+        .unwrap();
+
+        assert_eq!(issue.content_text, "Hi");
+        assert_eq!(
+            issue.content_html,
+            "<a href=\"https://example.com/\">Leave</a>"
+        );
+    }
+
+    #[test]
+    fn values_are_escaped_in_the_html_body_only() {
+        let issue = issue("{{ name }}", "{{ name }}")
+            .render_for(&recipient("<b>Ursula</b>", ""))
+            .unwrap();
+
+        assert!(issue.content_text.starts_with("<b>Ursula</b>"));
+        assert!(
+            issue
+                .content_html
+                .starts_with("&lt;b&gt;Ursula&lt;&#x2f;b&gt;")
+        );
+    }
+
+    #[test]
+    fn syntax_errors_are_rejected() {
+        assert_err!(issue("Hi {{ name", "<p>Hi</p>").validate());
+        assert_err!(issue("Hi", "{% if name %}<p>Hi</p>").validate());
+    }
+
+    #[test]
+    fn unknown_placeholders_are_rejected() {
+        assert_err!(issue("Hi {{ nmae }}", "<p>Hi</p>").validate());
+    }
+
+    #[test]
+    fn plain_bodies_are_valid_templates() {
+        assert_ok!(issue("Hi", "<p>Hi</p>").validate());
+    }
+
+    #[test]
+    fn archive_slugs_keep_the_words_of_the_title() {
+        let id = Uuid::parse_str("0f8fad5b-d9cb-469f-a165-70867728950e").unwrap();
+
+        assert_eq!(
+            archive_slug("Hello, World! Issue #12", id),
+            "hello-world-issue-12-0f8fad5b"
+        );
+        assert_eq!(archive_slug("Café ☕", id), "caf-0f8fad5b");
+        assert_eq!(archive_slug("¡¿?!", id), "0f8fad5b");
+    }
+
+    #[test]
+    fn long_titles_are_truncated_in_archive_slugs() {
+        let id = Uuid::parse_str("0f8fad5b-d9cb-469f-a165-70867728950e").unwrap();
+        let slug = archive_slug(&"word ".repeat(30), id);
+
+        assert!(slug.len() <= 60 + 9);
+        assert!(slug.ends_with("word-0f8fad5b"));
+    }
+}
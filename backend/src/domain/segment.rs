@@ -0,0 +1,272 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde_json::Value;
+use sqlx::{Postgres, QueryBuilder};
+
+/// The subscription statuses a filter can select.
+const STATUSES: [&str; 5] = [
+    "pending_confirmation",
+    "confirmed",
+    "unsubscribed",
+    "bounced",
+    "complained",
+];
+/// How deeply `all`, `any` and `not` can be nested.
+const MAX_DEPTH: usize = 8;
+/// The most days `subscribed_within_days` can look back, about a century.
+const MAX_DAYS: u32 = 36500;
+
+/// A condition that selects a segment of the subscribers, e.g. those who signed up in the last
+/// 30 days and are tagged `beta`:
+///
+/// ```json
+/// {"all": [{"subscribed_within_days": 30}, {"has_tag": "beta"}]}
+/// ```
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum SegmentFilter {
+    /// Every condition holds. Matches everyone when empty.
+    All(Vec<SegmentFilter>),
+    /// At least one condition holds. Matches nobody when empty.
+    Any(Vec<SegmentFilter>),
+    Not(Box<SegmentFilter>),
+    Status(String),
+    /// Signed up at or after this time.
+    SubscribedAfter(DateTime<Utc>),
+    /// Signed up before this time.
+    SubscribedBefore(DateTime<Utc>),
+    /// Signed up within this many days before the filter is applied.
+    SubscribedWithinDays(u32),
+    HasTag(String),
+    HasAttribute(String),
+    AttributeEquals {
+        key: String,
+        value: Value,
+    },
+}
+
+impl SegmentFilter {
+    /// Returns the filter `value` describes, if it is a valid one.
+    pub fn parse(value: Value) -> Result<SegmentFilter, String> {
+        let filter: SegmentFilter =
+            serde_json::from_value(value).map_err(|e| format!("Invalid segment filter: {e}"))?;
+        filter.validate(0)?;
+        Ok(filter)
+    }
+
+    /// Append the filter as a condition on the `subscriptions` table to `query`, with relative
+    /// dates counted back from `now`. The condition is never NULL, so `Not` selects exactly the
+    /// subscribers the inner filter doesn't.
+    pub fn push_sql(&self, query: &mut QueryBuilder<'_, Postgres>, now: DateTime<Utc>) {
+        match self {
+            SegmentFilter::All(filters) => push_combined(query, filters, " AND ", "TRUE", now),
+            SegmentFilter::Any(filters) => push_combined(query, filters, " OR ", "FALSE", now),
+            SegmentFilter::Not(filter) => {
+                query.push("NOT (");
+                filter.push_sql(query, now);
+                query.push(")");
+            }
+            SegmentFilter::Status(status) => {
+                query
+                    .push("subscriptions.status = ")
+                    .push_bind(status.clone());
+            }
+            SegmentFilter::SubscribedAfter(time) => {
+                query
+                    .push("subscriptions.subscribed_at >= ")
+                    .push_bind(*time);
+            }
+            SegmentFilter::SubscribedBefore(time) => {
+                query
+                    .push("subscriptions.subscribed_at < ")
+                    .push_bind(*time);
+            }
+            // Further back than dates go takes in everyone, which can only happen with filters
+            // saved before the number of days was bounded
+            SegmentFilter::SubscribedWithinDays(days) => {
+                match now.checked_sub_signed(TimeDelta::days(i64::from(*days))) {
+                    Some(since) => {
+                        query
+                            .push("subscriptions.subscribed_at >= ")
+                            .push_bind(since);
+                    }
+                    None => {
+                        query.push("TRUE");
+                    }
+                }
+            }
+            // Containment rather than `= ANY` so that the index on the tags is used
+            SegmentFilter::HasTag(tag) => {
+                query
+                    .push("subscriptions.tags @> ")
+                    .push_bind(vec![tag.clone()]);
+            }
+            SegmentFilter::HasAttribute(key) => {
+                query
+                    .push("subscriptions.attributes ? ")
+                    .push_bind(key.clone());
+            }
+            SegmentFilter::AttributeEquals { key, value } => {
+                query
+                    .push("(subscriptions.attributes -> ")
+                    .push_bind(key.clone())
+                    .push(" = ")
+                    .push_bind(value.clone())
+                    .push(") IS TRUE");
+            }
+        }
+    }
+
+    fn validate(&self, depth: usize) -> Result<(), String> {
+        if depth > MAX_DEPTH {
+            return Err(format!(
+                "Segment filters can be nested at most {MAX_DEPTH} levels deep"
+            ));
+        }
+        match self {
+            SegmentFilter::All(filters) | SegmentFilter::Any(filters) => filters
+                .iter()
+                .try_for_each(|filter| filter.validate(depth + 1)),
+            SegmentFilter::Not(filter) => filter.validate(depth + 1),
+            SegmentFilter::Status(status) if !STATUSES.contains(&status.as_str()) => Err(format!(
+                "'{status}' isn't a subscription status, use one of {}",
+                STATUSES.join(", ")
+            )),
+            SegmentFilter::SubscribedWithinDays(days) if *days > MAX_DAYS => Err(format!(
+                "Segment filters can look back at most {MAX_DAYS} days"
+            )),
+            SegmentFilter::HasTag(name)
+            | SegmentFilter::HasAttribute(name)
+            | SegmentFilter::AttributeEquals { key: name, .. }
+                if name.trim().is_empty() =>
+            {
+                Err("Tags and attribute names in segment filters can't be empty".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Append `filters` joined by `operator`, or `empty` if there are none.
+fn push_combined(
+    query: &mut QueryBuilder<'_, Postgres>,
+    filters: &[SegmentFilter],
+    operator: &str,
+    empty: &str,
+    now: DateTime<Utc>,
+) {
+    if filters.is_empty() {
+        query.push(empty);
+        return;
+    }
+    query.push("(");
+    for (i, filter) in filters.iter().enumerate() {
+        if i > 0 {
+            query.push(operator);
+        }
+        filter.push_sql(query, now);
+    }
+    query.push(")");
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use claims::{assert_err, assert_ok};
+    use serde_json::json;
+    use sqlx::{Postgres, QueryBuilder};
+
+    use super::SegmentFilter;
+
+    fn sql(filter: &SegmentFilter) -> String {
+        let mut query = QueryBuilder::<Postgres>::new("");
+        filter.push_sql(&mut query, Utc::now());
+        query.sql().to_string()
+    }
+
+    #[test]
+    fn filters_are_parsed_from_json() {
+        let filter = SegmentFilter::parse(json!({
+            "all": [
+                { "subscribed_within_days": 30 },
+                { "has_tag": "beta" },
+                { "attribute_equals": { "key": "plan", "value": "pro" } },
+            ]
+        }));
+
+        assert_eq!(
+            filter,
+            Ok(SegmentFilter::All(vec![
+                SegmentFilter::SubscribedWithinDays(30),
+                SegmentFilter::HasTag("beta".to_string()),
+                SegmentFilter::AttributeEquals {
+                    key: "plan".to_string(),
+                    value: json!("pro"),
+                },
+            ]))
+        );
+    }
+
+    #[test]
+    fn unknown_conditions_and_statuses_are_rejected() {
+        assert_err!(SegmentFilter::parse(json!({ "has_tags": "beta" })));
+        assert_err!(SegmentFilter::parse(json!({ "status": "active" })));
+        assert_err!(SegmentFilter::parse(json!({ "has_tag": " " })));
+        assert_ok!(SegmentFilter::parse(json!({ "status": "confirmed" })));
+    }
+
+    #[test]
+    fn looking_back_more_than_a_century_is_rejected() {
+        assert_ok!(SegmentFilter::parse(
+            json!({ "subscribed_within_days": 36500 })
+        ));
+        assert_err!(SegmentFilter::parse(
+            json!({ "subscribed_within_days": 36501 })
+        ));
+        assert_err!(SegmentFilter::parse(
+            json!({ "subscribed_within_days": u32::MAX })
+        ));
+    }
+
+    #[test]
+    fn looking_back_further_than_dates_go_matches_everyone() {
+        assert_eq!(sql(&SegmentFilter::SubscribedWithinDays(u32::MAX)), "TRUE");
+    }
+
+    #[test]
+    fn deeply_nested_filters_are_rejected() {
+        let mut filter = json!({ "has_tag": "beta" });
+        for _ in 0..10 {
+            filter = json!({ "not": filter });
+        }
+        assert_err!(SegmentFilter::parse(filter));
+    }
+
+    #[test]
+    fn conditions_are_combined_with_their_values_bound() {
+        let recent_beta_testers = SegmentFilter::All(vec![
+            SegmentFilter::SubscribedWithinDays(30),
+            SegmentFilter::HasTag("beta".to_string()),
+        ]);
+
+        assert_eq!(
+            sql(&SegmentFilter::Not(Box::new(recent_beta_testers))),
+            "NOT ((subscriptions.subscribed_at >= $1 AND subscriptions.tags @> $2))"
+        );
+        assert_eq!(sql(&SegmentFilter::Any(vec![])), "FALSE");
+        assert_eq!(sql(&SegmentFilter::All(vec![])), "TRUE");
+    }
+
+    #[test]
+    fn missing_attributes_are_never_equal() {
+        let seats = SegmentFilter::AttributeEquals {
+            key: "seats".to_string(),
+            value: json!(3),
+        };
+
+        // Without `IS TRUE` a missing attribute would be NULL, and so would its negation
+        assert_eq!(
+            sql(&SegmentFilter::Not(Box::new(seats))),
+            "NOT ((subscriptions.attributes -> $1 = $2) IS TRUE)"
+        );
+    }
+}
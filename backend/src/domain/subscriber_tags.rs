@@ -0,0 +1,51 @@
+/// The tags of a subscriber, trimmed, without duplicates and sorted.
+#[derive(Debug)]
+pub struct SubscriberTags(Vec<String>);
+
+const MAX_TAG_LENGTH: usize = 50;
+
+impl SubscriberTags {
+    pub fn parse(tags: Vec<String>) -> Result<SubscriberTags, String> {
+        let mut tags: Vec<String> = tags.into_iter().map(|t| t.trim().to_string()).collect();
+        if let Some(tag) = tags
+            .iter()
+            .find(|t| t.is_empty() || t.chars().count() > MAX_TAG_LENGTH)
+        {
+            return Err(format!(
+                "'{tag}' is not a valid tag, tags need 1 to {MAX_TAG_LENGTH} characters"
+            ));
+        }
+        tags.sort();
+        tags.dedup();
+        Ok(Self(tags))
+    }
+}
+
+impl AsRef<[String]> for SubscriberTags {
+    fn as_ref(&self) -> &[String] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubscriberTags;
+    use claims::assert_err;
+
+    #[test]
+    fn tags_are_trimmed_deduplicated_and_sorted() {
+        let tags = SubscriberTags::parse(vec![
+            " beta ".to_string(),
+            "alpha".to_string(),
+            "beta".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(tags.as_ref(), ["alpha", "beta"]);
+    }
+
+    #[test]
+    fn empty_and_long_tags_are_rejected() {
+        assert_err!(SubscriberTags::parse(vec!["  ".to_string()]));
+        assert_err!(SubscriberTags::parse(vec!["a".repeat(51)]));
+    }
+}
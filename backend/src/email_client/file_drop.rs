@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+use lettre::{AsyncFileTransport, AsyncTransport, Tokio1Executor};
+
+use crate::domain::SubscriberEmail;
+
+use super::{EmailError, EmailHeader, EmailSender, SentEmail, build_message};
+
+/// Writes every email as an `.eml` file into a directory instead of sending it.
+/// Meant for local development, where the files can be opened with any mail client.
+pub struct FileDropClient {
+    transport: AsyncFileTransport<Tokio1Executor>,
+    sender: SubscriberEmail,
+}
+
+impl FileDropClient {
+    pub fn new(directory: PathBuf, sender: SubscriberEmail) -> Result<Self, std::io::Error> {
+        std::fs::create_dir_all(&directory)?;
+
+        Ok(Self {
+            transport: AsyncFileTransport::new(directory),
+            sender,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for FileDropClient {
+    fn sender(&self) -> &SubscriberEmail {
+        &self.sender
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[EmailHeader],
+    ) -> Result<SentEmail, EmailError> {
+        let message = build_message(
+            &self.sender,
+            recipient,
+            subject,
+            html_content,
+            text_content,
+            headers,
+        )?;
+
+        let id = self
+            .transport
+            .send(message)
+            .await
+            .map_err(|e| EmailError::Transient(e.into()))?;
+
+        tracing::info!(email_id = id, "Wrote email to file");
+        Ok(SentEmail {
+            message_id: Some(id),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::assert_ok;
+    use fake::Fake;
+    use fake::faker::internet::en::SafeEmail;
+    use uuid::Uuid;
+
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{EmailSender, FileDropClient};
+
+    #[tokio::test]
+    async fn send_email_writes_an_eml_file_to_the_directory() {
+        // Arrange
+        let directory = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let client = FileDropClient::new(directory.clone(), email()).unwrap();
+
+        // Act
+        let outcome = client
+            .send_email(&email(), "Hello", "<p>Hello as HTML</p>", "Hello as text")
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+
+        let files: Vec<_> = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].extension().unwrap(), "eml");
+
+        let contents = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(contents.contains("Subject: Hello"));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    /// Generate a random subscriber email
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+}
@@ -0,0 +1,171 @@
+mod file_drop;
+mod postmark;
+mod smtp;
+
+pub use file_drop::FileDropClient;
+pub use postmark::PostmarkClient;
+pub use smtp::SmtpClient;
+
+use std::time::Duration;
+
+use lettre::Message;
+use lettre::message::MultiPart;
+use lettre::message::header::{HeaderName, HeaderValue};
+
+use crate::domain::SubscriberEmail;
+use crate::utils::error_chain_fmt;
+
+/// Something that can deliver an email on our behalf, e.g. an email API or an SMTP server.
+#[async_trait::async_trait]
+pub trait EmailSender: Send + Sync {
+    /// The address that emails are sent from.
+    fn sender(&self) -> &SubscriberEmail;
+
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[EmailHeader],
+    ) -> Result<SentEmail, EmailError>;
+
+    async fn send_email(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<SentEmail, EmailError> {
+        self.send_email_with_headers(recipient, subject, html_content, text_content, &[])
+            .await
+    }
+
+    /// Send several emails, returning one result per email in the same order. Services with a
+    /// batch API send them in as few requests as possible; the others send them one by one.
+    async fn send_emails(&self, emails: &[OutgoingEmail]) -> Vec<Result<SentEmail, EmailError>> {
+        let mut results = Vec::with_capacity(emails.len());
+        for email in emails {
+            let result = self
+                .send_email_with_headers(
+                    &email.recipient,
+                    &email.subject,
+                    &email.html_content,
+                    &email.text_content,
+                    &email.headers,
+                )
+                .await;
+            results.push(result);
+        }
+        results
+    }
+}
+
+/// An email to send with `EmailSender::send_emails`.
+#[derive(Debug)]
+pub struct OutgoingEmail {
+    pub recipient: SubscriberEmail,
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
+    pub headers: Vec<EmailHeader>,
+}
+
+/// What the email service told us about an email it accepted.
+#[derive(Debug, Default)]
+pub struct SentEmail {
+    /// The id the service assigned to the message, e.g. Postmark's `MessageID`.
+    pub message_id: Option<String>,
+}
+
+/// A custom header to add to an outgoing email, e.g. `List-Unsubscribe`.
+#[derive(Debug)]
+pub struct EmailHeader {
+    name: String,
+    value: String,
+}
+
+impl EmailHeader {
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum EmailError {
+    /// Sending may succeed if we try again later, e.g. the service timed out or was unavailable.
+    #[error("Failed to send an email. The failure may be temporary")]
+    Transient(#[source] anyhow::Error),
+
+    /// Trying again won't help, e.g. the recipient address was rejected.
+    #[error("Failed to send an email")]
+    Permanent(#[source] anyhow::Error),
+
+    /// The service asked us to slow down, and possibly said for how long.
+    #[error("The email service is rate limiting us")]
+    RateLimited { retry_after: Option<Duration> },
+}
+
+impl EmailError {
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            EmailError::Transient(_) | EmailError::RateLimited { .. }
+        )
+    }
+}
+
+impl std::fmt::Debug for EmailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Build a MIME message with both an HTML and a plain text part, for backends that speak SMTP.
+fn build_message(
+    sender: &SubscriberEmail,
+    recipient: &SubscriberEmail,
+    subject: &str,
+    html_content: &str,
+    text_content: &str,
+    headers: &[EmailHeader],
+) -> Result<Message, EmailError> {
+    let mut builder = Message::builder()
+        // Generate a Message-ID, so we can report it as the id of the sent message
+        .message_id(None)
+        .from(
+            sender
+                .as_ref()
+                .parse()
+                .map_err(|e| EmailError::Permanent(anyhow::Error::new(e)))?,
+        )
+        .to(recipient
+            .as_ref()
+            .parse()
+            .map_err(|e| EmailError::Permanent(anyhow::Error::new(e)))?)
+        .subject(subject);
+
+    for header in headers {
+        let name = HeaderName::new_from_ascii(header.name.clone())
+            .map_err(|e| EmailError::Permanent(anyhow::Error::new(e)))?;
+        builder = builder.raw_header(HeaderValue::new(name, header.value.clone()));
+    }
+
+    builder
+        .multipart(MultiPart::alternative_plain_html(
+            text_content.to_string(),
+            html_content.to_string(),
+        ))
+        .map_err(|e| EmailError::Permanent(anyhow::Error::new(e)))
+}
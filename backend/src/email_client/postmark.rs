@@ -0,0 +1,702 @@
+use std::time::Duration;
+
+use reqwest::{Client, StatusCode, header::RETRY_AFTER};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::domain::SubscriberEmail;
+
+use super::{EmailError, EmailHeader, EmailSender, OutgoingEmail, SentEmail};
+
+/// The most emails Postmark accepts in one batch request.
+const MAX_BATCH_SIZE: usize = 500;
+
+/// Postmark error codes for emails that will be rejected again if we retry them: an invalid
+/// request (300) and an inactive recipient (406). Other errors in a batch, e.g. about the sender
+/// signature, can be fixed on our side. See https://postmarkapp.com/developer/api/overview
+const PERMANENT_ERROR_CODES: [i64; 2] = [300, 406];
+
+/// Sends emails through the Postmark email API.
+pub struct PostmarkClient {
+    http_client: Client,
+    base_url: String,
+    sender: SubscriberEmail,
+    authorization_token: Secret<String>,
+}
+
+impl PostmarkClient {
+    pub fn new(
+        base_url: String,
+        sender: SubscriberEmail,
+        authorization_token: Secret<String>,
+        timeout: std::time::Duration,
+    ) -> Self {
+        let http_client = Client::builder().timeout(timeout).build().unwrap();
+
+        Self {
+            http_client,
+            base_url,
+            sender,
+            authorization_token,
+        }
+    }
+}
+
+impl PostmarkClient {
+    /// Send up to `MAX_BATCH_SIZE` emails with a single request to the batch endpoint.
+    #[tracing::instrument(skip_all, fields(n_emails = emails.len()))]
+    async fn send_batch(&self, emails: &[OutgoingEmail]) -> Vec<Result<SentEmail, EmailError>> {
+        let url = format!("{}/email/batch", self.base_url);
+
+        tracing::info!(url, "Sending batch request to email service");
+
+        let headers: Vec<_> = emails
+            .iter()
+            .map(|e| send_email_headers(&e.headers))
+            .collect();
+        let request_body: Vec<_> = emails
+            .iter()
+            .zip(&headers)
+            .map(|(email, headers)| SendEmailRequest {
+                from: self.sender.as_ref(),
+                to: email.recipient.as_ref(),
+                subject: &email.subject,
+                html_body: &email.html_content,
+                text_body: &email.text_content,
+                headers,
+            })
+            .collect();
+
+        let response = match self.post(&url, &request_body).await {
+            Ok(response) => response,
+            Err(e) => return fail_batch(e, emails.len()),
+        };
+
+        // The emails have been accepted at this point, so don't fail them if the response is
+        // unexpected: retrying would send them twice.
+        let mut results: Vec<_> = match response.json::<Vec<SendBatchResponseItem>>().await {
+            Ok(items) => items
+                .into_iter()
+                .map(SendBatchResponseItem::into_result)
+                .collect(),
+            Err(e) => {
+                tracing::warn!(error.message = %e, "Postmark batch response could not be read");
+                Vec::new()
+            }
+        };
+        if results.len() != emails.len() {
+            tracing::warn!(
+                n_results = results.len(),
+                "Postmark batch response did not have one result per email"
+            );
+        }
+        results.resize_with(emails.len(), || Ok(SentEmail::default()));
+        results
+    }
+
+    /// Post `body` to `url`, turning unsuccessful responses into errors.
+    async fn post(
+        &self,
+        url: &str,
+        body: &impl serde::Serialize,
+    ) -> Result<reqwest::Response, EmailError> {
+        let response = self
+            .http_client
+            .post(url)
+            .header(
+                "X-Postmark-Server-Token",
+                self.authorization_token.expose_secret(),
+            )
+            .json(body)
+            .send()
+            .await
+            .map_err(classify_error)?;
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            return Err(EmailError::RateLimited {
+                retry_after: retry_after(&response),
+            });
+        }
+        response.error_for_status().map_err(classify_error)
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for PostmarkClient {
+    fn sender(&self) -> &SubscriberEmail {
+        &self.sender
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[EmailHeader],
+    ) -> Result<SentEmail, EmailError> {
+        let url = format!("{}/email", self.base_url);
+
+        tracing::info!(url, "Sending email request to email service");
+
+        let headers = send_email_headers(headers);
+        let request_body = SendEmailRequest {
+            from: self.sender.as_ref(),
+            to: recipient.as_ref(),
+            subject,
+            html_body: html_content,
+            text_body: text_content,
+            headers: &headers,
+        };
+
+        let response = self.post(&url, &request_body).await?;
+
+        // The email has been accepted at this point, so don't fail if the response is unexpected
+        let message_id = match response.json::<SendEmailResponse>().await {
+            Ok(body) => Some(body.message_id),
+            Err(e) => {
+                tracing::warn!(error.message = %e, "Postmark response did not include a MessageID");
+                None
+            }
+        };
+        Ok(SentEmail { message_id })
+    }
+
+    async fn send_emails(&self, emails: &[OutgoingEmail]) -> Vec<Result<SentEmail, EmailError>> {
+        // A single email doesn't need the batch endpoint, whose failures are harder to read
+        if let [email] = emails {
+            let result = self
+                .send_email_with_headers(
+                    &email.recipient,
+                    &email.subject,
+                    &email.html_content,
+                    &email.text_content,
+                    &email.headers,
+                )
+                .await;
+            return vec![result];
+        }
+
+        let mut results = Vec::with_capacity(emails.len());
+        for batch in emails.chunks(MAX_BATCH_SIZE) {
+            results.extend(self.send_batch(batch).await);
+        }
+        results
+    }
+}
+
+fn send_email_headers(headers: &[EmailHeader]) -> Vec<SendEmailHeader<'_>> {
+    headers
+        .iter()
+        .map(|h| SendEmailHeader {
+            name: h.name(),
+            value: h.value(),
+        })
+        .collect()
+}
+
+/// When the whole batch request fails, every email in it fails the same way.
+fn fail_batch(error: EmailError, n: usize) -> Vec<Result<SentEmail, EmailError>> {
+    (0..n)
+        .map(|_| {
+            Err(match &error {
+                EmailError::Transient(e) => EmailError::Transient(anyhow::anyhow!("{e:#}")),
+                EmailError::Permanent(e) => EmailError::Permanent(anyhow::anyhow!("{e:#}")),
+                EmailError::RateLimited { retry_after } => EmailError::RateLimited {
+                    retry_after: *retry_after,
+                },
+            })
+        })
+        .collect()
+}
+
+/// Server errors and network failures are worth retrying; anything else Postmark rejected
+/// (e.g. an inactive recipient) will be rejected again.
+fn classify_error(e: reqwest::Error) -> EmailError {
+    let is_transient = match e.status() {
+        Some(status) => status.is_server_error(),
+        None => !e.is_builder(),
+    };
+
+    if is_transient {
+        EmailError::Transient(e.into())
+    } else {
+        EmailError::Permanent(e.into())
+    }
+}
+
+/// The delay from a `Retry-After` header. Only the number of seconds is supported, not dates.
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let seconds = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    seconds.trim().parse().ok().map(Duration::from_secs)
+}
+
+/// One entry of the batch endpoint's response, in the same order as the emails of the request.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendBatchResponseItem {
+    error_code: i64,
+    message: String,
+    #[serde(rename = "MessageID")]
+    message_id: Option<String>,
+}
+
+impl SendBatchResponseItem {
+    fn into_result(self) -> Result<SentEmail, EmailError> {
+        if self.error_code == 0 {
+            return Ok(SentEmail {
+                message_id: self.message_id,
+            });
+        }
+        let error = anyhow::anyhow!(
+            "Postmark rejected the email with error code {}: {}",
+            self.error_code,
+            self.message
+        );
+        if PERMANENT_ERROR_CODES.contains(&self.error_code) {
+            Err(EmailError::Permanent(error))
+        } else {
+            Err(EmailError::Transient(error))
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct SendEmailResponse {
+    #[serde(rename = "MessageID")]
+    message_id: String,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html_body: &'a str,
+    text_body: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    headers: &'a [SendEmailHeader<'a>],
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailHeader<'a> {
+    name: &'a str,
+    value: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+    use fake::faker::{
+        internet::en::SafeEmail,
+        lorem::en::{Paragraph, Sentence},
+    };
+    use fake::{Fake, Faker};
+    use secrecy::Secret;
+    use wiremock::{
+        Mock, MockServer, Request, ResponseTemplate,
+        matchers::{any, header, header_exists, method, path},
+    };
+
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{
+        EmailError, EmailHeader, EmailSender, OutgoingEmail, PostmarkClient,
+    };
+
+    #[tokio::test]
+    async fn send_email_sends_the_expected_request() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/email"))
+            .and(method("POST"))
+            .and(SendEmailBodyMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let _ = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+    }
+
+    #[tokio::test]
+    async fn send_email_with_headers_sends_the_headers() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .and(SendEmailBodyMatcher)
+            .and(HeadersMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let _ = email_client
+            .send_email_with_headers(
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+                &[EmailHeader::new(
+                    "List-Unsubscribe-Post",
+                    "List-Unsubscribe=One-Click",
+                )],
+            )
+            .await;
+
+        // Assert
+    }
+
+    #[tokio::test]
+    async fn send_email_succeeds_if_the_server_returns_200() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_returns_the_message_id_from_the_response() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "To": "receiver@example.com",
+                "SubmittedAt": "2026-10-18T12:00:00Z",
+                "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817",
+                "ErrorCode": 0,
+                "Message": "OK"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        let sent = assert_ok!(outcome);
+        assert_eq!(
+            sent.message_id.as_deref(),
+            Some("b7bc2f4a-e38e-4336-af7d-e6c392c2f817")
+        );
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_the_server_returns_500() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_the_server_takes_too_long() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        let response = ResponseTemplate::new(200)
+            // 3 minutes!
+            .set_delay(std::time::Duration::from_secs(180));
+
+        Mock::given(any())
+            .respond_with(response)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_failure_is_transient_if_the_server_returns_503() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        let error = assert_err!(outcome);
+        assert!(matches!(error, EmailError::Transient(_)));
+    }
+
+    #[tokio::test]
+    async fn send_email_failure_is_permanent_if_the_server_returns_422() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(422))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        let error = assert_err!(outcome);
+        assert!(matches!(error, EmailError::Permanent(_)));
+    }
+
+    #[tokio::test]
+    async fn send_email_is_rate_limited_if_the_server_returns_429() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "30"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        let error = assert_err!(outcome);
+        assert!(matches!(
+            error,
+            EmailError::RateLimited {
+                retry_after: Some(delay)
+            } if delay == std::time::Duration::from_secs(30)
+        ));
+    }
+
+    #[tokio::test]
+    async fn send_emails_sends_a_batch_request() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(path("/email/batch"))
+            .and(method("POST"))
+            .and(SendBatchBodyMatcher { n_emails: 3 })
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let results = email_client
+            .send_emails(&[outgoing_email(), outgoing_email(), outgoing_email()])
+            .await;
+
+        // Assert
+        assert_eq!(results.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn send_emails_returns_the_result_of_every_email_in_the_batch() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                { "ErrorCode": 0, "Message": "OK", "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817" },
+                { "ErrorCode": 406, "Message": "You tried to send to a recipient that has been marked as inactive." },
+                { "ErrorCode": 405, "Message": "Not allowed to send." }
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let mut results = email_client
+            .send_emails(&[outgoing_email(), outgoing_email(), outgoing_email()])
+            .await
+            .into_iter();
+
+        // Assert
+        let sent = assert_ok!(results.next().unwrap());
+        assert_eq!(
+            sent.message_id.as_deref(),
+            Some("b7bc2f4a-e38e-4336-af7d-e6c392c2f817")
+        );
+        let error = assert_err!(results.next().unwrap());
+        assert!(matches!(error, EmailError::Permanent(_)));
+        let error = assert_err!(results.next().unwrap());
+        assert!(matches!(error, EmailError::Transient(_)));
+    }
+
+    #[tokio::test]
+    async fn send_emails_fails_every_email_if_the_batch_request_fails() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let results = email_client
+            .send_emails(&[outgoing_email(), outgoing_email()])
+            .await;
+
+        // Assert
+        assert_eq!(results.len(), 2);
+        for result in results {
+            let error = assert_err!(result);
+            assert!(matches!(error, EmailError::Transient(_)));
+        }
+    }
+
+    /// Generate a random email subject
+    fn subject() -> String {
+        Sentence(1..2).fake()
+    }
+
+    /// Generate a random email content
+    fn content() -> String {
+        Paragraph(1..10).fake()
+    }
+
+    /// Generate a random subscriber email
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    /// Generate a random email to send in a batch
+    fn outgoing_email() -> OutgoingEmail {
+        OutgoingEmail {
+            recipient: email(),
+            subject: subject(),
+            html_content: content(),
+            text_content: content(),
+            headers: Vec::new(),
+        }
+    }
+
+    /// Get a test instance of `PostmarkClient`
+    fn email_client(base_url: String) -> PostmarkClient {
+        PostmarkClient::new(
+            base_url,
+            email(),
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+        )
+    }
+
+    struct SendEmailBodyMatcher;
+
+    impl wiremock::Match for SendEmailBodyMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+            if let Ok(body) = result {
+                // Check that all the mandatory fields are populated without inspecting the field values
+                body.get("From").is_some()
+                    && body.get("To").is_some()
+                    && body.get("Subject").is_some()
+                    && body.get("HtmlBody").is_some()
+                    && body.get("TextBody").is_some()
+            } else {
+                false
+            }
+        }
+    }
+
+    struct SendBatchBodyMatcher {
+        n_emails: usize,
+    }
+
+    impl wiremock::Match for SendBatchBodyMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            let result: Result<Vec<serde_json::Value>, _> = serde_json::from_slice(&request.body);
+            if let Ok(emails) = result {
+                emails.len() == self.n_emails
+                    && emails.iter().all(|email| {
+                        email.get("From").is_some()
+                            && email.get("To").is_some()
+                            && email.get("Subject").is_some()
+                            && email.get("HtmlBody").is_some()
+                            && email.get("TextBody").is_some()
+                    })
+            } else {
+                false
+            }
+        }
+    }
+
+    struct HeadersMatcher;
+
+    impl wiremock::Match for HeadersMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            body["Headers"]
+                == serde_json::json!([
+                    { "Name": "List-Unsubscribe-Post", "Value": "List-Unsubscribe=One-Click" }
+                ])
+        }
+    }
+}
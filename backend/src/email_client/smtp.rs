@@ -0,0 +1,253 @@
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::domain::SubscriberEmail;
+
+use super::{EmailError, EmailHeader, EmailSender, SentEmail, build_message};
+
+/// Sends emails through an SMTP relay.
+pub struct SmtpClient {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    sender: SubscriberEmail,
+}
+
+impl SmtpClient {
+    /// With `starttls` the connection must be upgraded to TLS before anything (including
+    /// credentials) is sent. Only turn it off for a local SMTP sink.
+    pub fn new(
+        host: &str,
+        port: u16,
+        credentials: Option<(String, Secret<String>)>,
+        starttls: bool,
+        sender: SubscriberEmail,
+        timeout: std::time::Duration,
+    ) -> Result<Self, anyhow::Error> {
+        let builder = if starttls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+        };
+
+        let mut builder = builder.port(port).timeout(Some(timeout));
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(
+                username,
+                password.expose_secret().to_owned(),
+            ));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            sender,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for SmtpClient {
+    fn sender(&self) -> &SubscriberEmail {
+        &self.sender
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[EmailHeader],
+    ) -> Result<SentEmail, EmailError> {
+        let message = build_message(
+            &self.sender,
+            recipient,
+            subject,
+            html_content,
+            text_content,
+            headers,
+        )?;
+
+        let message_id = message.headers().get_raw("Message-ID").map(str::to_owned);
+
+        tracing::info!("Sending email to SMTP server");
+
+        self.transport.send(message).await.map_err(|e| {
+            // 5xx replies are final; 4xx replies, timeouts and connection failures are not.
+            if e.is_permanent() {
+                EmailError::Permanent(e.into())
+            } else {
+                EmailError::Transient(e.into())
+            }
+        })?;
+        Ok(SentEmail { message_id })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use claims::{assert_err, assert_ok};
+    use fake::Fake;
+    use fake::faker::internet::en::SafeEmail;
+    use secrecy::Secret;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{EmailHeader, EmailSender, SmtpClient};
+
+    #[tokio::test]
+    async fn send_email_delivers_the_message_to_the_smtp_server() {
+        // Arrange
+        let sink = SmtpSink::start("250 OK").await;
+        let client = smtp_client(sink.port, None);
+
+        // Act
+        let outcome = client
+            .send_email_with_headers(
+                &email(),
+                "Hello",
+                "<p>Hello as HTML</p>",
+                "Hello as text",
+                &[EmailHeader::new(
+                    "List-Unsubscribe-Post",
+                    "List-Unsubscribe=One-Click",
+                )],
+            )
+            .await;
+
+        // Assert
+        let sent = assert_ok!(outcome);
+        let transcript = sink.transcript();
+        let message_id = sent
+            .message_id
+            .expect("The message should have a Message-ID");
+        assert!(transcript.contains(&format!("Message-ID: {message_id}")));
+        assert!(transcript.contains("Subject: Hello"));
+        assert!(transcript.contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click"));
+        assert!(transcript.contains("Hello as text"));
+        assert!(transcript.contains("<p>Hello as HTML</p>"));
+    }
+
+    #[tokio::test]
+    async fn send_email_authenticates_when_credentials_are_configured() {
+        // Arrange
+        let sink = SmtpSink::start("250 OK").await;
+        let client = smtp_client(
+            sink.port,
+            Some(("user".to_string(), Secret::new("password".to_string()))),
+        );
+
+        // Act
+        let outcome = client.send_email(&email(), "Hello", "html", "text").await;
+
+        // Assert
+        assert_ok!(outcome);
+        assert!(sink.transcript().contains("AUTH "));
+    }
+
+    #[tokio::test]
+    async fn send_email_failure_is_permanent_if_the_server_rejects_the_message() {
+        // Arrange
+        let sink = SmtpSink::start("550 Mailbox unavailable").await;
+        let client = smtp_client(sink.port, None);
+
+        // Act
+        let outcome = client.send_email(&email(), "Hello", "html", "text").await;
+
+        // Assert
+        let error = assert_err!(outcome);
+        assert!(!error.is_transient());
+    }
+
+    #[tokio::test]
+    async fn send_email_failure_is_transient_if_the_server_defers_the_message() {
+        // Arrange
+        let sink = SmtpSink::start("451 Try again later").await;
+        let client = smtp_client(sink.port, None);
+
+        // Act
+        let outcome = client.send_email(&email(), "Hello", "html", "text").await;
+
+        // Assert
+        let error = assert_err!(outcome);
+        assert!(error.is_transient());
+    }
+
+    /// Generate a random subscriber email
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    /// Get a test instance of `SmtpClient` talking plain text to a local sink
+    fn smtp_client(port: u16, credentials: Option<(String, Secret<String>)>) -> SmtpClient {
+        SmtpClient::new(
+            "127.0.0.1",
+            port,
+            credentials,
+            false,
+            email(),
+            Duration::from_secs(2),
+        )
+        .unwrap()
+    }
+
+    /// A minimal SMTP server that accepts any command, records everything it receives and
+    /// replies to the end of the message data with `data_reply`.
+    struct SmtpSink {
+        port: u16,
+        transcript: Arc<Mutex<String>>,
+    }
+
+    impl SmtpSink {
+        async fn start(data_reply: &'static str) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let transcript = Arc::new(Mutex::new(String::new()));
+
+            let recorded = transcript.clone();
+            tokio::spawn(async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (reader, mut writer) = stream.into_split();
+                let mut lines = BufReader::new(reader).lines();
+                let mut in_data = false;
+
+                writer.write_all(b"220 localhost\r\n").await.unwrap();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    recorded.lock().unwrap().push_str(&format!("{line}\n"));
+                    let reply = if in_data {
+                        if line != "." {
+                            continue;
+                        }
+                        in_data = false;
+                        data_reply
+                    } else if line.starts_with("EHLO") {
+                        "250-localhost\r\n250 AUTH PLAIN LOGIN"
+                    } else if line.starts_with("AUTH") {
+                        "235 Authenticated"
+                    } else if line == "DATA" {
+                        in_data = true;
+                        "354 Go ahead"
+                    } else if line == "QUIT" {
+                        "221 Bye"
+                    } else {
+                        "250 OK"
+                    };
+                    writer
+                        .write_all(format!("{reply}\r\n").as_bytes())
+                        .await
+                        .unwrap();
+                }
+            });
+
+            Self { port, transcript }
+        }
+
+        fn transcript(&self) -> String {
+            self.transcript.lock().unwrap().clone()
+        }
+    }
+}
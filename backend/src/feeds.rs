@@ -0,0 +1,185 @@
+//! Writing RSS 2.0 and Atom feeds of the archive, for readers who follow the newsletter with a
+//! feed reader instead of by email.
+
+use chrono::{DateTime, Utc};
+
+/// What a feed is about, and where it lives.
+pub struct FeedChannel<'a> {
+    pub title: &'a str,
+    /// The page the feed is a feed of, i.e. the archive.
+    pub link: &'a str,
+    /// Where the feed itself is served from.
+    pub self_link: &'a str,
+}
+
+pub struct FeedEntry {
+    pub title: String,
+    /// The issue's page in the archive, which also identifies the entry.
+    pub permalink: String,
+    pub published_at: DateTime<Utc>,
+    pub content_html: String,
+}
+
+pub fn rss(channel: &FeedChannel, entries: &[FeedEntry]) -> String {
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    xml.push('\n');
+    xml.push_str(r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">"#);
+    xml.push_str("\n<channel>\n");
+    element(&mut xml, "title", channel.title);
+    element(&mut xml, "link", channel.link);
+    element(&mut xml, "description", channel.title);
+    xml.push_str(&format!(
+        "<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+        escape(channel.self_link)
+    ));
+    if let Some(latest) = entries.first() {
+        element(&mut xml, "lastBuildDate", &latest.published_at.to_rfc2822());
+    }
+    for entry in entries {
+        xml.push_str("<item>\n");
+        element(&mut xml, "title", &entry.title);
+        element(&mut xml, "link", &entry.permalink);
+        xml.push_str(&format!(
+            "<guid isPermaLink=\"true\">{}</guid>\n",
+            escape(&entry.permalink)
+        ));
+        element(&mut xml, "pubDate", &entry.published_at.to_rfc2822());
+        element(&mut xml, "description", &entry.content_html);
+        xml.push_str("</item>\n");
+    }
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+pub fn atom(channel: &FeedChannel, entries: &[FeedEntry]) -> String {
+    // Atom requires `updated`, so an empty feed says it hasn't changed since the epoch rather
+    // than using the current time, which would give it a new ETag on every request.
+    let updated = entries
+        .first()
+        .map_or(DateTime::UNIX_EPOCH, |entry| entry.published_at);
+
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    xml.push('\n');
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    element(&mut xml, "id", channel.self_link);
+    element(&mut xml, "title", channel.title);
+    element(&mut xml, "updated", &updated.to_rfc3339());
+    xml.push_str(&format!(
+        "<link href=\"{}\" rel=\"alternate\" type=\"text/html\"/>\n",
+        escape(channel.link)
+    ));
+    xml.push_str(&format!(
+        "<link href=\"{}\" rel=\"self\" type=\"application/atom+xml\"/>\n",
+        escape(channel.self_link)
+    ));
+    xml.push_str("<author>\n");
+    element(&mut xml, "name", channel.title);
+    xml.push_str("</author>\n");
+    for entry in entries {
+        xml.push_str("<entry>\n");
+        element(&mut xml, "id", &entry.permalink);
+        element(&mut xml, "title", &entry.title);
+        xml.push_str(&format!(
+            "<link href=\"{}\" rel=\"alternate\" type=\"text/html\"/>\n",
+            escape(&entry.permalink)
+        ));
+        element(&mut xml, "published", &entry.published_at.to_rfc3339());
+        element(&mut xml, "updated", &entry.published_at.to_rfc3339());
+        xml.push_str(&format!(
+            "<content type=\"html\">{}</content>\n",
+            escape(&entry.content_html)
+        ));
+        xml.push_str("</entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn element(xml: &mut String, name: &str, text: &str) {
+    xml.push_str(&format!("<{name}>{}</{name}>\n", escape(text)));
+}
+
+/// Escape `text` for use in XML text and attribute values. Control characters that XML 1.0
+/// doesn't allow at all are dropped, since feed readers reject the whole feed over them.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' => {}
+            '\u{FFFE}' | '\u{FFFF}' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::{FeedChannel, FeedEntry, atom, escape, rss};
+
+    const CHANNEL: FeedChannel = FeedChannel {
+        title: "Newsletter",
+        link: "https://example.com/archive",
+        self_link: "https://example.com/feed.rss",
+    };
+
+    fn entry() -> FeedEntry {
+        FeedEntry {
+            title: "Tips & <tricks>".to_string(),
+            permalink: "https://example.com/archive/tips-tricks-0123abcd".to_string(),
+            published_at: Utc.with_ymd_and_hms(2026, 10, 18, 9, 30, 0).unwrap(),
+            content_html: "<p>Say \"hi\" & 'bye'</p>".to_string(),
+        }
+    }
+
+    #[test]
+    fn markup_characters_are_escaped() {
+        assert_eq!(
+            escape(r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn characters_xml_does_not_allow_are_dropped() {
+        assert_eq!(escape("a\u{0}b\u{1b}c\td\ne\u{FFFF}"), "abc\td\ne");
+        assert_eq!(escape("Grüße 👋"), "Grüße 👋");
+    }
+
+    #[test]
+    fn rss_items_hold_the_escaped_issue() {
+        let xml = rss(&CHANNEL, &[entry()]);
+        assert!(xml.contains("<title>Tips &amp; &lt;tricks&gt;</title>"));
+        assert!(xml.contains(
+            "<guid isPermaLink=\"true\">https://example.com/archive/tips-tricks-0123abcd</guid>"
+        ));
+        assert!(xml.contains("<pubDate>Sun, 18 Oct 2026 09:30:00 +0000</pubDate>"));
+        assert!(xml.contains(
+            "<description>&lt;p&gt;Say &quot;hi&quot; &amp; &apos;bye&apos;&lt;/p&gt;</description>"
+        ));
+    }
+
+    #[test]
+    fn atom_entries_hold_the_escaped_issue() {
+        let xml = atom(&CHANNEL, &[entry()]);
+        assert!(xml.contains("<updated>2026-10-18T09:30:00+00:00</updated>"));
+        assert!(xml.contains("<id>https://example.com/archive/tips-tricks-0123abcd</id>"));
+        assert!(xml.contains("<published>2026-10-18T09:30:00+00:00</published>"));
+        assert!(xml.contains(
+            "<content type=\"html\">&lt;p&gt;Say &quot;hi&quot; &amp; &apos;bye&apos;&lt;/p&gt;</content>"
+        ));
+    }
+
+    #[test]
+    fn an_empty_atom_feed_was_last_updated_at_the_epoch() {
+        assert!(atom(&CHANNEL, &[]).contains("<updated>1970-01-01T00:00:00+00:00</updated>"));
+    }
+}
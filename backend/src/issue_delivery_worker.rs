@@ -1,108 +1,456 @@
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 
-use sqlx::{Executor, PgPool, Postgres, Transaction};
-use tracing::{Span, field::display};
+use anyhow::Context;
+use chrono::Utc;
+use rand::Rng;
+use sqlx::{
+    Executor, PgPool, Postgres, QueryBuilder, Transaction,
+    postgres::{PgListener, PgPoolOptions},
+};
+use tokio::sync::Notify;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tracing::Span;
 use uuid::Uuid;
 
 use crate::{
-    configuration::Settings, domain::SubscriberEmail, email_client::EmailClient,
-    startup::get_connection_pool,
+    configuration::{Settings, WorkerSettings},
+    domain::{
+        NewsletterIssue, Recipient, SegmentFilter, SubscriberEmail, preferences_link,
+        unsubscribe_link,
+    },
+    email_client::{EmailError, EmailHeader, EmailSender, OutgoingEmail, SentEmail},
+    pending_subscriptions::cleanup_loop,
+    throttle::Throttle,
+    utils::error_chain_fmt,
 };
 
-pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
-    let connection_pool = get_connection_pool(&configuration.database);
+/// How many times a transient delivery failure is retried before the task is moved to
+/// `issue_delivery_failures`.
+const MAX_RETRIES: i16 = 8;
+const BASE_RETRY_DELAY: Duration = Duration::from_secs(10);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+/// How long to back off when the email service rate limits us without saying for how long.
+const RATE_LIMITED_DELAY: Duration = Duration::from_secs(60);
+
+/// Idle workers are woken up through this channel when tasks are enqueued, see `notify_workers`.
+const QUEUE_CHANNEL: &str = "issue_delivery_queue";
+
+/// Deliver newsletter issues, and clean up stale pending subscriptions, until `shutdown` is
+/// cancelled. Deliveries that are in flight by then are finished before this returns.
+pub async fn run_worker_until_stopped(
+    configuration: Settings,
+    shutdown: CancellationToken,
+) -> Result<(), anyhow::Error> {
+    // Every worker holds a connection for its batch's transaction and briefly needs a second one
+    // to look up the issues and subscribers, so size the pool to avoid workers waiting on each other. The
+    // scheduler and the cleanup need one more each and the listener keeps one for itself.
+    let max_connections = u32::try_from(configuration.worker.concurrency.get() * 2 + 3)
+        .context("The worker concurrency is too large")?;
+    let connection_pool = PgPoolOptions::new()
+        .max_connections(max_connections)
+        .connect_lazy_with(configuration.database.connect_options());
     let email_client = configuration.email_client.client();
-    worker_loop(connection_pool, email_client).await
+    // A panicking worker cancels `shutdown`, which stops the cleanup too
+    let (outcome, ()) = tokio::join!(
+        run_workers(
+            connection_pool.clone(),
+            email_client,
+            configuration.application.base_url,
+            configuration.worker,
+            shutdown.clone(),
+        ),
+        cleanup_loop(
+            connection_pool,
+            configuration.subscriptions.pending_retention(),
+            shutdown,
+        ),
+    );
+    outcome
+}
+
+/// Run `settings.concurrency` workers that share the delivery queue, plus one loop that starts
+/// sending due issues and one that wakes up idle workers, until `shutdown` is cancelled.
+pub async fn run_workers(
+    pool: PgPool,
+    email_client: Arc<dyn EmailSender>,
+    base_url: String,
+    settings: WorkerSettings,
+    shutdown: CancellationToken,
+) -> Result<(), anyhow::Error> {
+    let wakeup = Arc::new(Notify::new());
+    // Shared by all workers, so the limits apply to the process as a whole
+    let throttle = Arc::new(Throttle::new(settings.rate_limit.clone()));
+    let mut tasks = JoinSet::new();
+    tasks.spawn(scheduler_loop(
+        pool.clone(),
+        settings.poll_interval(),
+        shutdown.clone(),
+    ));
+    tasks.spawn(listener_loop(
+        pool.clone(),
+        wakeup.clone(),
+        shutdown.clone(),
+    ));
+    for _ in 0..settings.concurrency.get() {
+        tasks.spawn(worker_loop(
+            pool.clone(),
+            email_client.clone(),
+            base_url.clone(),
+            throttle.clone(),
+            wakeup.clone(),
+            settings.clone(),
+            shutdown.clone(),
+        ));
+    }
+    tracing::info!(
+        concurrency = settings.concurrency.get(),
+        "Started delivery workers"
+    );
+
+    while let Some(outcome) = tasks.join_next().await {
+        if let Err(e) = outcome {
+            // Don't leave the other workers running unsupervised
+            shutdown.cancel();
+            tasks.join_all().await;
+            return Err(e).context("A delivery worker panicked");
+        }
+    }
+    tracing::info!("All delivery workers have stopped");
+    Ok(())
 }
 
 pub enum ExecutionOutcome {
-    TaskCompleted,
+    /// A batch of tasks was dequeued. Each of them was delivered, rescheduled, deferred or moved
+    /// to the failures.
+    TasksExecuted,
     EmptyQueue,
+    /// Nothing may be sent for this long. No task was dequeued.
+    Throttled(Duration),
 }
 
-#[tracing::instrument(skip_all, fields(newsletter_issue_id=tracing::field::Empty, subscriber_email=tracing::field::Empty), err)]
+/// Deliver up to `batch_size` due tasks with as few requests to the email service as possible.
+/// Each task is settled on its own, so one failing recipient doesn't hold back the others.
+#[tracing::instrument(skip_all, fields(n_tasks=tracing::field::Empty), err)]
 pub async fn try_execute_task(
     pool: &PgPool,
-    email_client: &EmailClient,
+    email_client: &dyn EmailSender,
+    base_url: &str,
+    throttle: &Throttle,
+    batch_size: usize,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
-    let task = dequeue_task(pool).await?;
-    let Some((transaction, issue_id, email)) = task else {
+    // Wait for the global budget before dequeueing, so that no task stays locked meanwhile. The
+    // slots that aren't spent on an email are given back when `slots` is dropped, also when this
+    // returns early with an error.
+    let mut slots = match throttle.acquire(batch_size) {
+        Ok(slots) => slots,
+        Err(wait) => return Ok(ExecutionOutcome::Throttled(wait)),
+    };
+    let Some((mut transaction, tasks)) = dequeue_tasks(pool, slots.count()).await? else {
         return Ok(ExecutionOutcome::EmptyQueue);
     };
+    slots.truncate(tasks.len());
+    Span::current().record("n_tasks", tasks.len());
 
-    Span::current()
-        .record("newsletter_issue_id", display(issue_id))
-        .record("subscriber_email", display(&email));
-
-    match SubscriberEmail::parse(email.clone()) {
-        Ok(email) => {
-            let issue = get_issue(pool, issue_id).await?;
-            if let Err(e) = email_client
-                .send_email(
-                    &email,
-                    &issue.title,
-                    &issue.content_html,
-                    &issue.content_text,
-                )
-                .await
-            {
-                tracing::error!(
-                    error.cause_chain = ?e,
-                    error.message = %e,
-                    "Failed to deliver issue to a confirmed subscriber. Skipping.",
-                );
+    // On error, the tasks stay in the queue: the transaction is rolled back when dropped.
+    let (issues, subscribers) = load_batch(pool, &tasks).await?;
+
+    let mut permits = Vec::with_capacity(tasks.len());
+    let mut sending = Vec::with_capacity(tasks.len());
+    let mut emails = Vec::with_capacity(tasks.len());
+    for task in &tasks {
+        let Some(subscriber) = subscribers.get(&(task.issue_id, task.email.clone())) else {
+            // Unsubscribed, left the issue's lists or suppressed since the task was queued
+            tracing::info!(
+                newsletter_issue_id = %task.issue_id,
+                "Dropping a delivery to an address that may no longer be emailed"
+            );
+            delete_task(&mut transaction, task).await?;
+            continue;
+        };
+        match throttle.acquire_domain(recipient_domain(&task.email)) {
+            Ok(permit) => permits.push(permit),
+            Err(delay) => {
+                defer_task(&mut transaction, task, delay).await?;
+                continue;
+            }
+        }
+        match prepare_email(email_client, base_url, task, &issues, subscriber) {
+            Ok(email) => {
+                sending.push(task);
+                emails.push(email);
             }
+            Err(e) => settle_task(&mut transaction, throttle, task, Err(e)).await?,
+        }
+    }
+    slots.truncate(emails.len());
+
+    let results = email_client.send_emails(&emails).await;
+    slots.spend();
+    drop(permits);
+    for (task, result) in sending.into_iter().zip(results) {
+        let result = result.map_err(DeliveryError::SendError);
+        settle_task(&mut transaction, throttle, task, result).await?;
+    }
+    transaction.commit().await?;
+
+    Ok(ExecutionOutcome::TasksExecuted)
+}
+
+/// Record how delivering `task` went, and take it off the queue unless it will be tried again.
+#[tracing::instrument(
+    skip_all,
+    fields(newsletter_issue_id=%task.issue_id, subscriber_email=%task.email, n_retries=task.n_retries)
+)]
+async fn settle_task(
+    transaction: &mut PgTransaction,
+    throttle: &Throttle,
+    task: &Task,
+    result: Result<SentEmail, DeliveryError>,
+) -> Result<(), anyhow::Error> {
+    match result {
+        Ok(sent) => {
+            let outcome = AttemptOutcome::Sent {
+                message_id: sent.message_id.as_deref(),
+            };
+            record_attempt(transaction, task, outcome).await?;
+            delete_task(transaction, task).await?;
+        }
+        Err(DeliveryError::SendError(EmailError::RateLimited { retry_after })) => {
+            // Not the subscriber's fault, so this doesn't count as a failed attempt
+            let delay = retry_after.unwrap_or(RATE_LIMITED_DELAY);
+            tracing::warn!(
+                retry_in_seconds = delay.as_secs(),
+                "The email service is rate limiting us. Pausing deliveries.",
+            );
+            throttle.pause_for(delay);
+            defer_task(transaction, task, delay).await?;
+        }
+        Err(e) if e.is_transient() && task.n_retries < MAX_RETRIES => {
+            let delay = retry_delay(task.n_retries);
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                retry_in_seconds = delay.as_secs(),
+                "Failed to deliver issue to a confirmed subscriber. Retrying later.",
+            );
+            record_attempt(transaction, task, AttemptOutcome::Retrying(&e)).await?;
+            reschedule_task(transaction, task, delay).await?;
         }
         Err(e) => {
             tracing::error!(
                 error.cause_chain = ?e,
                 error.message = %e,
-                "Skipping a confirmed subscriber. Their stored contact details are invalid",
+                "Failed to deliver issue to a confirmed subscriber. Giving up.",
             );
+            record_attempt(transaction, task, AttemptOutcome::Failed(&e)).await?;
+            move_task_to_failures(transaction, task, &e).await?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(thiserror::Error)]
+enum DeliveryError {
+    #[error("Failed to send the issue to the email service")]
+    SendError(#[source] EmailError),
+
+    #[error("{0}")]
+    InvalidSubscriber(String),
+
+    #[error("Failed to render the issue for the subscriber")]
+    RenderError(#[source] minijinja::Error),
+}
+
+impl DeliveryError {
+    /// Whether trying again later has a reasonable chance of succeeding.
+    fn is_transient(&self) -> bool {
+        match self {
+            DeliveryError::SendError(e) => e.is_transient(),
+            DeliveryError::InvalidSubscriber(_) => false,
+            DeliveryError::RenderError(_) => false,
         }
     }
+}
+
+impl std::fmt::Debug for DeliveryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
 
-    delete_task(transaction, issue_id, &email).await?;
+/// Look up the issues and subscribers of a batch of tasks, with one query each.
+async fn load_batch(
+    pool: &PgPool,
+    tasks: &[Task],
+) -> Result<
+    (
+        HashMap<Uuid, NewsletterIssue>,
+        HashMap<(Uuid, String), Subscriber>,
+    ),
+    anyhow::Error,
+> {
+    let mut issue_ids: Vec<_> = tasks.iter().map(|t| t.issue_id).collect();
+    issue_ids.sort_unstable();
+    issue_ids.dedup();
+    Ok((
+        get_issues(pool, &issue_ids).await?,
+        get_subscribers(pool, tasks).await?,
+    ))
+}
 
-    Ok(ExecutionOutcome::TaskCompleted)
+/// Render the task's issue for its subscriber.
+fn prepare_email(
+    email_client: &dyn EmailSender,
+    base_url: &str,
+    task: &Task,
+    issues: &HashMap<Uuid, NewsletterIssue>,
+    subscriber: &Subscriber,
+) -> Result<OutgoingEmail, DeliveryError> {
+    let email = SubscriberEmail::parse(task.email.clone()).map_err(|_| {
+        DeliveryError::InvalidSubscriber(
+            "The subscriber's stored contact details are invalid".to_string(),
+        )
+    })?;
+
+    let unsubscribe_link = unsubscribe_link(base_url, &subscriber.unsubscribe_token);
+    let preferences_link = preferences_link(base_url, &subscriber.unsubscribe_token);
+    // The queue references the issue, so it can't have been deleted since the task was dequeued
+    let issue = issues[&task.issue_id]
+        .clone()
+        .render_for(&Recipient {
+            name: &subscriber.name,
+            email: email.as_ref(),
+            unsubscribe_url: &unsubscribe_link,
+            preferences_url: &preferences_link,
+        })
+        .map_err(DeliveryError::RenderError)?;
+    let headers = list_unsubscribe_headers(email_client.sender(), &unsubscribe_link);
+
+    Ok(OutgoingEmail {
+        recipient: email,
+        subject: issue.title,
+        html_content: issue.content_html,
+        text_content: issue.content_text,
+        headers: headers.into(),
+    })
+}
+
+/// The part of the address that receiving servers throttle us by, e.g. `gmail.com`.
+fn recipient_domain(email: &str) -> &str {
+    email.rsplit_once('@').map_or(email, |(_, domain)| domain)
+}
+
+/// Exponential backoff with "equal jitter": half of the delay is fixed, the other half is random,
+/// so that tasks which failed together during an outage don't all retry at the same moment.
+fn retry_delay(n_retries: i16) -> Duration {
+    let exponential = BASE_RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(n_retries.max(0) as u32))
+        .min(MAX_RETRY_DELAY);
+    let half = exponential / 2;
+    half + half.mul_f64(rand::thread_rng().r#gen::<f64>())
 }
 
 type PgTransaction = Transaction<'static, Postgres>;
 
+struct Task {
+    issue_id: Uuid,
+    email: String,
+    n_retries: i16,
+}
+
+/// Lock up to `limit` due tasks until the returned transaction ends.
 #[tracing::instrument(skip_all)]
-async fn dequeue_task(
+async fn dequeue_tasks(
     pool: &PgPool,
-) -> Result<Option<(PgTransaction, Uuid, String)>, anyhow::Error> {
+    limit: usize,
+) -> Result<Option<(PgTransaction, Vec<Task>)>, anyhow::Error> {
     let mut transaction = pool.begin().await?;
-    let row = sqlx::query!(
+    let tasks = sqlx::query_as!(
+        Task,
         r#"
-        SELECT newsletter_issue_id, subscriber_email
+        SELECT
+            issue_delivery_queue.newsletter_issue_id AS issue_id,
+            issue_delivery_queue.subscriber_email AS email,
+            issue_delivery_queue.n_retries
         FROM issue_delivery_queue
-        FOR UPDATE
+        JOIN newsletter_issues
+            ON newsletter_issues.newsletter_issue_id = issue_delivery_queue.newsletter_issue_id
+        WHERE
+            issue_delivery_queue.execute_after <= now() AND
+            -- Paused issues keep their tasks until they are resumed
+            newsletter_issues.status = 'sending'
+        FOR UPDATE OF issue_delivery_queue
         SKIP LOCKED
-        LIMIT 1
+        LIMIT $1
         "#,
+        i64::try_from(limit).unwrap_or(i64::MAX)
     )
-    .fetch_optional(&mut *transaction)
+    .fetch_all(&mut *transaction)
     .await?;
 
-    if let Some(row) = row {
-        Ok(Some((
-            transaction,
-            row.newsletter_issue_id,
-            row.subscriber_email,
-        )))
-    } else {
+    if tasks.is_empty() {
         Ok(None)
+    } else {
+        Ok(Some((transaction, tasks)))
     }
 }
 
+enum AttemptOutcome<'a> {
+    Sent { message_id: Option<&'a str> },
+    Retrying(&'a DeliveryError),
+    Failed(&'a DeliveryError),
+}
+
+/// Keep track of every attempt in `issue_deliveries`, which outlives the queued task.
 #[tracing::instrument(skip_all)]
-async fn delete_task(
-    mut transaction: PgTransaction,
-    issue_id: Uuid,
-    email: &str,
+async fn record_attempt(
+    transaction: &mut PgTransaction,
+    task: &Task,
+    outcome: AttemptOutcome<'_>,
 ) -> Result<(), anyhow::Error> {
+    let (outcome, message_id, error) = match outcome {
+        AttemptOutcome::Sent { message_id } => ("sent", message_id, None),
+        AttemptOutcome::Retrying(e) => ("retrying", None, Some(format!("{e:?}"))),
+        AttemptOutcome::Failed(e) => ("failed", None, Some(format!("{e:?}"))),
+    };
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO issue_deliveries (
+            newsletter_issue_id,
+            subscriber_email,
+            n_attempts,
+            outcome,
+            provider_message_id,
+            last_error,
+            first_attempted_at,
+            last_attempted_at,
+            sent_at
+        )
+        VALUES ($1, $2, 1, $3, $4, $5, now(), now(), CASE WHEN $3 = 'sent' THEN now() END)
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET
+            n_attempts = issue_deliveries.n_attempts + 1,
+            outcome = EXCLUDED.outcome,
+            provider_message_id = EXCLUDED.provider_message_id,
+            last_error = EXCLUDED.last_error,
+            last_attempted_at = EXCLUDED.last_attempted_at,
+            sent_at = EXCLUDED.sent_at
+        "#,
+        task.issue_id,
+        task.email,
+        outcome,
+        message_id,
+        error
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn delete_task(transaction: &mut PgTransaction, task: &Task) -> Result<(), anyhow::Error> {
     let query = sqlx::query!(
         r#"
         DELETE FROM issue_delivery_queue
@@ -110,48 +458,478 @@ async fn delete_task(
             newsletter_issue_id = $1 AND
             subscriber_email = $2
         "#,
-        issue_id,
-        email
+        task.issue_id,
+        task.email
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn reschedule_task(
+    transaction: &mut PgTransaction,
+    task: &Task,
+    delay: Duration,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET
+            n_retries = n_retries + 1,
+            execute_after = now() + make_interval(secs => $3)
+        WHERE
+            newsletter_issue_id = $1 AND
+            subscriber_email = $2
+        "#,
+        task.issue_id,
+        task.email,
+        delay.as_secs_f64()
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+/// Put the task back in the queue for later, without counting it as a retry.
+#[tracing::instrument(skip_all)]
+async fn defer_task(
+    transaction: &mut PgTransaction,
+    task: &Task,
+    delay: Duration,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET execute_after = now() + make_interval(secs => $3)
+        WHERE
+            newsletter_issue_id = $1 AND
+            subscriber_email = $2
+        "#,
+        task.issue_id,
+        task.email,
+        delay.as_secs_f64()
     );
     transaction.execute(query).await?;
-    transaction.commit().await?;
     Ok(())
 }
 
-struct NewsletterIssue {
-    title: String,
-    content_text: String,
-    content_html: String,
+#[tracing::instrument(skip_all)]
+async fn move_task_to_failures(
+    transaction: &mut PgTransaction,
+    task: &Task,
+    error: &DeliveryError,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_failures (
+            newsletter_issue_id,
+            subscriber_email,
+            n_retries,
+            last_error,
+            failed_at
+        )
+        VALUES ($1, $2, $3, $4, now())
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET
+            n_retries = EXCLUDED.n_retries,
+            last_error = EXCLUDED.last_error,
+            failed_at = EXCLUDED.failed_at
+        "#,
+        task.issue_id,
+        task.email,
+        task.n_retries,
+        format!("{error:?}")
+    );
+    transaction.execute(query).await?;
+    delete_task(transaction, task).await
 }
 
 #[tracing::instrument(skip_all)]
-async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, anyhow::Error> {
-    let issue = sqlx::query_as!(
-        NewsletterIssue,
+async fn get_issues(
+    pool: &PgPool,
+    issue_ids: &[Uuid],
+) -> Result<HashMap<Uuid, NewsletterIssue>, anyhow::Error> {
+    let rows = sqlx::query!(
         r#"
-        SELECT title, content_text, content_html
+        SELECT newsletter_issue_id, title, content_text, content_html
         FROM newsletter_issues
         WHERE
-            newsletter_issue_id = $1
+            newsletter_issue_id = ANY($1)
         "#,
-        issue_id
+        issue_ids
     )
-    .fetch_one(pool)
+    .fetch_all(pool)
     .await?;
-    Ok(issue)
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let issue = NewsletterIssue {
+                title: row.title,
+                content_text: row.content_text,
+                content_html: row.content_html,
+            };
+            (row.newsletter_issue_id, issue)
+        })
+        .collect())
+}
+
+/// Headers that let mail clients offer their own unsubscribe button (RFC 2369) which
+/// unsubscribes with a single POST to `unsubscribe_link`, without opening a page (RFC 8058).
+fn list_unsubscribe_headers(sender: &SubscriberEmail, unsubscribe_link: &str) -> [EmailHeader; 2] {
+    [
+        EmailHeader::new(
+            "List-Unsubscribe",
+            format!("<mailto:{sender}?subject=unsubscribe>, <{unsubscribe_link}>"),
+        ),
+        EmailHeader::new("List-Unsubscribe-Post", "List-Unsubscribe=One-Click"),
+    ]
+}
+
+struct Subscriber {
+    name: String,
+    unsubscribe_token: String,
+}
+
+/// The subscribers that may still be emailed the issue of their task, by issue and email: they
+/// are confirmed, not suppressed and still confirmed members of one of the issue's lists. Tasks
+/// for anyone else are dropped.
+#[tracing::instrument(skip_all)]
+async fn get_subscribers(
+    pool: &PgPool,
+    tasks: &[Task],
+) -> Result<HashMap<(Uuid, String), Subscriber>, anyhow::Error> {
+    let issue_ids: Vec<_> = tasks.iter().map(|t| t.issue_id).collect();
+    let emails: Vec<_> = tasks.iter().map(|t| t.email.clone()).collect();
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            tasks.newsletter_issue_id AS "newsletter_issue_id!",
+            subscriptions.email,
+            subscriptions.name,
+            unsubscribe_tokens.unsubscribe_token
+        FROM unnest($1::uuid[], $2::text[]) AS tasks (newsletter_issue_id, email)
+        JOIN subscriptions ON subscriptions.email = tasks.email
+        JOIN unsubscribe_tokens ON unsubscribe_tokens.subscriber_id = subscriptions.id
+        WHERE
+            subscriptions.status = 'confirmed' AND
+            NOT EXISTS (
+                SELECT 1
+                FROM suppressions
+                WHERE lower(suppressions.email) = lower(subscriptions.email)
+            ) AND
+            EXISTS (
+                SELECT 1
+                FROM list_memberships
+                JOIN newsletter_issue_lists
+                    ON newsletter_issue_lists.list_id = list_memberships.list_id
+                WHERE
+                    list_memberships.subscriber_id = subscriptions.id AND
+                    list_memberships.status = 'confirmed' AND
+                    newsletter_issue_lists.newsletter_issue_id = tasks.newsletter_issue_id
+            )
+        "#,
+        &issue_ids,
+        &emails
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let subscriber = Subscriber {
+                name: row.name,
+                unsubscribe_token: row.unsubscribe_token,
+            };
+            ((row.newsletter_issue_id, row.email), subscriber)
+        })
+        .collect())
+}
+
+/// Start sending the scheduled issues whose `send_at` has passed, see `enqueue_deliveries`.
+/// Returns how many issues were published.
+#[tracing::instrument(skip_all, err)]
+pub async fn enqueue_due_issues(pool: &PgPool) -> Result<u64, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let due_issues = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET
+            status = 'sending',
+            published_at = now()
+        WHERE status = 'scheduled' AND send_at <= now()
+        RETURNING newsletter_issue_id
+        "#,
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+
+    for issue in &due_issues {
+        enqueue_deliveries(&mut transaction, issue.newsletter_issue_id).await?;
+        tracing::info!(newsletter_issue_id = %issue.newsletter_issue_id, "Published scheduled issue");
+    }
+    if !due_issues.is_empty() {
+        notify_workers(&mut *transaction).await?;
+    }
+    transaction.commit().await?;
+
+    Ok(due_issues.len() as u64)
+}
+
+/// Mark the issues that have no deliveries left as `completed`. Returns how many were marked.
+#[tracing::instrument(skip_all, err)]
+pub async fn complete_finished_issues(pool: &PgPool) -> Result<u64, anyhow::Error> {
+    let completed = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'completed'
+        WHERE
+            status = 'sending' AND
+            NOT EXISTS (
+                SELECT 1
+                FROM issue_delivery_queue
+                WHERE issue_delivery_queue.newsletter_issue_id = newsletter_issues.newsletter_issue_id
+            )
+        "#,
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Ok(completed)
+}
+
+/// Enqueue a delivery task of the issue for every confirmed member of its lists, once each, or
+/// only for those in its segment if it has one. Returns how many tasks were enqueued.
+///
+/// The segment is applied now rather than when the issue was published, so that scheduled issues
+/// reach the subscribers who are in the segment when they are sent.
+#[tracing::instrument(skip(transaction))]
+pub(crate) async fn enqueue_deliveries(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<u64, anyhow::Error> {
+    let issue = sqlx::query!(
+        r#"
+        SELECT segments.filter AS "filter?"
+        FROM newsletter_issues
+        LEFT JOIN segments ON segments.segment_id = newsletter_issues.segment_id
+        WHERE newsletter_issues.newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    .context("Failed to look up the issue's recipients")?;
+    let segment = issue
+        .filter
+        .map(SegmentFilter::parse)
+        .transpose()
+        .map_err(anyhow::Error::msg)
+        .context("The issue's segment has an invalid filter")?;
+
+    // Everyone on the list if the issue isn't sent to a segment
+    let segment = segment.unwrap_or(SegmentFilter::All(Vec::new()));
+    let mut query = QueryBuilder::<Postgres>::new(
+        "INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email) SELECT ",
+    );
+    query
+        .push_bind(newsletter_issue_id)
+        .push(
+            r#", subscriptions.email
+            FROM subscriptions
+            WHERE
+                subscriptions.status = 'confirmed' AND
+                NOT EXISTS (
+                    SELECT 1
+                    FROM suppressions
+                    WHERE lower(suppressions.email) = lower(subscriptions.email)
+                ) AND
+                EXISTS (
+                    SELECT 1
+                    FROM list_memberships
+                    JOIN newsletter_issue_lists
+                        ON newsletter_issue_lists.list_id = list_memberships.list_id
+                    WHERE
+                        list_memberships.subscriber_id = subscriptions.id AND
+                        list_memberships.status = 'confirmed' AND
+                        newsletter_issue_lists.newsletter_issue_id = "#,
+        )
+        .push_bind(newsletter_issue_id)
+        .push(") AND ");
+    segment.push_sql(&mut query, Utc::now());
+    let enqueued = query
+        .build()
+        .execute(&mut **transaction)
+        .await
+        .context("Failed to enqueue delivery tasks")?
+        .rows_affected();
+    Ok(enqueued)
+}
+
+/// Wake up idle workers because there are new tasks in the queue. When called within a
+/// transaction, the workers are only woken up once it commits.
+pub(crate) async fn notify_workers<'e>(
+    executor: impl Executor<'e, Database = Postgres>,
+) -> Result<(), sqlx::Error> {
+    // `NOTIFY` doesn't take parameters, so this has to match `QUEUE_CHANNEL`
+    sqlx::query!("NOTIFY issue_delivery_queue")
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// Start sending due issues and mark finished ones as completed, every `poll_interval`.
+async fn scheduler_loop(pool: PgPool, poll_interval: Duration, shutdown: CancellationToken) {
+    while !shutdown.is_cancelled() {
+        // Errors are logged by the functions themselves; we'll try again on the next iteration.
+        let _ = enqueue_due_issues(&pool).await;
+        let _ = complete_finished_issues(&pool).await;
+        sleep_unless_shutdown(poll_interval, &shutdown).await;
+    }
 }
 
-async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
-    loop {
+/// Wake up the idle workers whenever tasks are enqueued, see `notify_workers`.
+///
+/// Notifications sent while the listener is disconnected are lost, which only delays deliveries
+/// until the workers check the queue on their own after `empty_queue_sleep`.
+async fn listener_loop(pool: PgPool, wakeup: Arc<Notify>, shutdown: CancellationToken) {
+    while !shutdown.is_cancelled() {
+        let mut listener = match listen(&pool).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to listen for enqueued deliveries",
+                );
+                sleep_unless_shutdown(Duration::from_secs(1), &shutdown).await;
+                continue;
+            }
+        };
+        // Tasks may have been enqueued while we weren't listening
+        wakeup.notify_waiters();
+
+        loop {
+            tokio::select! {
+                notification = listener.recv() => match notification {
+                    Ok(_) => wakeup.notify_waiters(),
+                    Err(e) => {
+                        tracing::warn!(
+                            error.cause_chain = ?e,
+                            error.message = %e,
+                            "Lost the connection for enqueued delivery notifications",
+                        );
+                        break;
+                    }
+                },
+                _ = shutdown.cancelled() => return,
+            }
+        }
+    }
+}
+
+async fn listen(pool: &PgPool) -> Result<PgListener, sqlx::Error> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(QUEUE_CHANNEL).await?;
+    Ok(listener)
+}
+
+/// Deliver tasks one batch at a time. Shutdown is only checked between batches, so deliveries
+/// that have been dequeued are always finished and recorded.
+async fn worker_loop(
+    pool: PgPool,
+    email_client: Arc<dyn EmailSender>,
+    base_url: String,
+    throttle: Arc<Throttle>,
+    wakeup: Arc<Notify>,
+    settings: WorkerSettings,
+    shutdown: CancellationToken,
+) {
+    let empty_queue_sleep = settings.empty_queue_sleep();
+    while !shutdown.is_cancelled() {
+        // Register for wakeups before looking at the queue, otherwise we could miss a
+        // notification for a task enqueued right after we found the queue empty.
+        let notified = wakeup.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+
         // TODO: p. 578: Don't sleep if 'try_execute_task' fails with a non-transient error
-        match try_execute_task(&pool, &email_client).await {
+        let outcome = try_execute_task(
+            &pool,
+            email_client.as_ref(),
+            &base_url,
+            &throttle,
+            settings.batch_size.get(),
+        )
+        .await;
+        match outcome {
             Ok(ExecutionOutcome::EmptyQueue) => {
-                tokio::time::sleep(Duration::from_secs(10)).await;
+                // Deferred and retried tasks shouldn't have to wait for the next fallback poll
+                let sleep = match next_task_due_in(&pool).await {
+                    Ok(Some(due_in)) => due_in.min(empty_queue_sleep),
+                    _ => empty_queue_sleep,
+                };
+                tokio::select! {
+                    _ = notified => {}
+                    _ = sleep_unless_shutdown(sleep, &shutdown) => {}
+                }
+            }
+            Ok(ExecutionOutcome::Throttled(wait)) => {
+                sleep_unless_shutdown(wait, &shutdown).await;
             }
             Err(_) => {
-                tokio::time::sleep(Duration::from_secs(1)).await;
+                sleep_unless_shutdown(Duration::from_secs(1), &shutdown).await;
             }
-            Ok(ExecutionOutcome::TaskCompleted) => {}
+            Ok(ExecutionOutcome::TasksExecuted) => {}
+        }
+    }
+}
+
+/// How long until the earliest task that is waiting for its `execute_after` is due, if any.
+/// Tasks that are already due are being delivered by other workers.
+async fn next_task_due_in(pool: &PgPool) -> Result<Option<Duration>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT EXTRACT(EPOCH FROM min(issue_delivery_queue.execute_after) - now())::float8 AS "seconds"
+        FROM issue_delivery_queue
+        JOIN newsletter_issues
+            ON newsletter_issues.newsletter_issue_id = issue_delivery_queue.newsletter_issue_id
+        WHERE
+            issue_delivery_queue.execute_after > now() AND
+            newsletter_issues.status = 'sending'
+        "#,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(row
+        .seconds
+        .map(|seconds| Duration::from_secs_f64(seconds.max(0.0))))
+}
+
+async fn sleep_unless_shutdown(duration: Duration, shutdown: &CancellationToken) {
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => {}
+        _ = shutdown.cancelled() => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{BASE_RETRY_DELAY, MAX_RETRY_DELAY, retry_delay};
+
+    #[test]
+    fn retry_delay_grows_exponentially_with_jitter() {
+        for n_retries in 0..5 {
+            let expected = BASE_RETRY_DELAY * 2u32.pow(n_retries as u32);
+            let delay = retry_delay(n_retries);
+            assert!(delay >= expected / 2 && delay <= expected, "{delay:?}");
         }
     }
+
+    #[test]
+    fn retry_delay_is_capped() {
+        let delay = retry_delay(i16::MAX);
+        assert!(delay >= MAX_RETRY_DELAY / 2 && delay <= MAX_RETRY_DELAY);
+        assert!(delay > Duration::ZERO);
+    }
 }
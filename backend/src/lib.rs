@@ -1,11 +1,19 @@
 pub mod authentication;
 pub mod configuration;
+pub mod csv;
+pub mod data_requests;
 pub mod domain;
 pub mod email_client;
+pub mod feeds;
 pub mod idempotency;
 pub mod issue_delivery_worker;
+pub mod lists;
+pub mod pending_subscriptions;
 pub mod routes;
 pub mod session_state;
 pub mod startup;
+pub mod subscriber_import;
+pub mod suppressions;
 pub mod telemetry;
+pub mod throttle;
 pub mod utils;
@@ -0,0 +1,84 @@
+//! The mailing lists that subscribers join and newsletter issues are sent to.
+
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+/// The list `list_id` refers to, or the default list when it is `None`. Returns `None` when
+/// there is no such list.
+#[tracing::instrument(name = "Find the target list", skip(executor))]
+pub async fn target_list<'e>(
+    executor: impl Executor<'e, Database = Postgres>,
+    list_id: Option<Uuid>,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let list = sqlx::query!(
+        r#"
+        SELECT list_id
+        FROM lists
+        WHERE CASE WHEN $1::uuid IS NULL THEN is_default ELSE list_id = $1 END
+        "#,
+        list_id
+    )
+    .fetch_optional(executor)
+    .await?;
+    Ok(list.map(|list| list.list_id))
+}
+
+/// Add the subscriber to the list, pending their confirmation. A membership they confirmed
+/// before is left alone; one they left is pending again.
+#[tracing::instrument(name = "Add subscriber to list", skip(executor))]
+pub async fn join_list<'e>(
+    executor: impl Executor<'e, Database = Postgres>,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (subscriber_id, list_id, status)
+        VALUES ($1, $2, 'pending_confirmation')
+        ON CONFLICT (subscriber_id, list_id) DO UPDATE
+        SET status = 'pending_confirmation', confirmed_at = NULL
+        WHERE list_memberships.status = 'unsubscribed'
+        "#,
+        subscriber_id,
+        list_id
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// A list the subscriber joined, and whether they confirmed or left it.
+#[derive(serde::Serialize)]
+pub struct Membership {
+    pub list_id: Uuid,
+    pub name: String,
+    pub status: String,
+    pub joined_at: DateTime<Utc>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+}
+
+#[tracing::instrument(name = "Get the subscriber's lists", skip(executor))]
+pub async fn memberships<'e>(
+    executor: impl Executor<'e, Database = Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Vec<Membership>, sqlx::Error> {
+    sqlx::query_as!(
+        Membership,
+        r#"
+        SELECT
+            lists.list_id,
+            lists.name,
+            list_memberships.status,
+            list_memberships.joined_at,
+            list_memberships.confirmed_at
+        FROM list_memberships
+        JOIN lists ON lists.list_id = list_memberships.list_id
+        WHERE list_memberships.subscriber_id = $1
+        ORDER BY lists.name
+        "#,
+        subscriber_id
+    )
+    .fetch_all(executor)
+    .await
+}
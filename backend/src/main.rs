@@ -1,5 +1,6 @@
 use std::fmt::{Debug, Display};
 use tokio::task::JoinError;
+use tokio_util::sync::CancellationToken;
 use zero2prod::{
     configuration::get_configuration, issue_delivery_worker::run_worker_until_stopped,
     startup::Application, telemetry,
@@ -30,14 +31,53 @@ async fn main() {
 
     let configuration = get_configuration();
 
+    // The HTTP server handles the shutdown signals on its own, the workers are told via this token
+    let shutdown = CancellationToken::new();
+    tokio::spawn(cancel_on_shutdown_signal(shutdown.clone()));
+
     let application = Application::build(configuration.clone()).await;
-    let application_task = tokio::spawn(application.run_until_stopped());
-    let worker_task = tokio::spawn(run_worker_until_stopped(configuration));
+    let mut application_task = tokio::spawn(application.run_until_stopped());
+    let mut worker_task = tokio::spawn(run_worker_until_stopped(configuration, shutdown.clone()));
 
     tokio::select! {
-        o = application_task => report_exit("API", o),
-        o = worker_task => report_exit("Background worker", o),
+        o = &mut application_task => {
+            report_exit("API", o);
+            // Let the workers finish the deliveries they have started
+            shutdown.cancel();
+            report_exit("Background worker", worker_task.await);
+        }
+        o = &mut worker_task => {
+            report_exit("Background worker", o);
+            if shutdown.is_cancelled() {
+                report_exit("API", application_task.await);
+            }
+        }
+    };
+}
+
+/// Cancel `shutdown` on SIGTERM, which is how deploys stop the process, or on Ctrl+C.
+async fn cancel_on_shutdown_signal(shutdown: CancellationToken) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl+C");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
     };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    tracing::info!("Received shutdown signal, finishing in-flight deliveries");
+    shutdown.cancel();
 }
 
 fn report_exit(task_name: &str, outcome: Result<Result<(), impl Debug + Display>, JoinError>) {
@@ -0,0 +1,88 @@
+//! Signups that were never confirmed.
+
+use std::time::Duration;
+
+use sqlx::{Executor, PgPool};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// How often stale pending subscriptions are looked for. They only need to be gone eventually.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Delete the pending subscriptions, with their tokens and lists, whose confirmation links all expired more
+/// than `retention` ago. Returns how many subscriptions were deleted.
+///
+/// The subscriptions are locked while they are deleted, so a signup that sends a fresh link at
+/// the same time either keeps the subscription or is refused as a duplicate afterwards.
+#[tracing::instrument(skip_all, err)]
+pub async fn delete_stale_pending_subscriptions(
+    pool: &PgPool,
+    retention: Duration,
+) -> Result<u64, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let stale: Vec<Uuid> = sqlx::query!(
+        r#"
+        SELECT id
+        FROM subscriptions
+        WHERE
+            status = 'pending_confirmation' AND
+            subscribed_at < now() - make_interval(secs => $1) AND
+            NOT EXISTS (
+                SELECT 1
+                FROM subscription_tokens
+                WHERE
+                    subscription_tokens.subscriber_id = subscriptions.id AND
+                    subscription_tokens.expires_at >= now() - make_interval(secs => $1)
+            )
+        FOR UPDATE
+        "#,
+        retention.as_secs_f64()
+    )
+    .fetch_all(&mut *transaction)
+    .await?
+    .into_iter()
+    .map(|row| row.id)
+    .collect();
+    if stale.is_empty() {
+        return Ok(0);
+    }
+
+    let query = sqlx::query!(
+        "DELETE FROM subscription_tokens WHERE subscriber_id = ANY($1)",
+        &stale
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        "DELETE FROM unsubscribe_tokens WHERE subscriber_id = ANY($1)",
+        &stale
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        "DELETE FROM email_change_tokens WHERE subscriber_id = ANY($1)",
+        &stale
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        "DELETE FROM list_memberships WHERE subscriber_id = ANY($1)",
+        &stale
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!("DELETE FROM subscriptions WHERE id = ANY($1)", &stale);
+    let deleted = transaction.execute(query).await?.rows_affected();
+    transaction.commit().await?;
+
+    tracing::info!(deleted, "Deleted stale pending subscriptions");
+    Ok(deleted)
+}
+
+/// Delete stale pending subscriptions every `CLEANUP_INTERVAL` until `shutdown` is cancelled.
+pub(crate) async fn cleanup_loop(pool: PgPool, retention: Duration, shutdown: CancellationToken) {
+    while !shutdown.is_cancelled() {
+        // Errors are logged by the function itself; we'll try again on the next iteration.
+        let _ = delete_stale_pending_subscriptions(&pool, retention).await;
+        tokio::select! {
+            _ = tokio::time::sleep(CLEANUP_INTERVAL) => {}
+            _ = shutdown.cancelled() => {}
+        }
+    }
+}
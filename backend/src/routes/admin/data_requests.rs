@@ -0,0 +1,177 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::UserId;
+use crate::configuration::DataRequestSettings;
+use crate::data_requests::{
+    AccessReport, DataRequestKind, ErasureSummary, access_report, email_hash, erase,
+    record_fulfilled_request,
+};
+use crate::utils::AppError;
+
+#[derive(serde::Deserialize)]
+pub struct DataRequestData {
+    email: String,
+}
+
+impl DataRequestData {
+    /// The address exactly as stored, without parsing it, so that invalid addresses that made it
+    /// into the database can be erased as well.
+    fn email(&self) -> Result<&str, AppError> {
+        let email = self.email.trim();
+        if email.is_empty() {
+            return Err(AppError::BadInputData("The email is missing".to_string()));
+        }
+        Ok(email)
+    }
+}
+
+#[derive(serde::Serialize)]
+struct AccessResponse {
+    request_id: Uuid,
+    #[serde(flatten)]
+    report: AccessReport,
+}
+
+#[derive(serde::Serialize)]
+struct ErasureResponse {
+    request_id: Uuid,
+    erased: ErasureSummary,
+}
+
+/// Answer a data subject access request with everything stored about the address. An address
+/// that nothing is stored about is a 404, and isn't recorded as a fulfilled request.
+#[tracing::instrument(
+    name = "Fulfil a data access request",
+    skip(form, pool, settings, user_id)
+)]
+pub async fn request_data_access(
+    form: web::Json<DataRequestData>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<DataRequestSettings>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, AppError> {
+    let email = form.email()?;
+    let report = access_report(&pool, email)
+        .await
+        .context("Failed to collect the stored data")?;
+    if report.is_empty() {
+        return Err(nothing_stored());
+    }
+
+    let request_id = Uuid::new_v4();
+    record_fulfilled_request(
+        pool.get_ref(),
+        request_id,
+        DataRequestKind::Access,
+        &email_hash(&settings.hash_key, email),
+        *user_id.into_inner(),
+    )
+    .await
+    .context("Failed to record the access request")?;
+
+    Ok(HttpResponse::Ok().json(AccessResponse { request_id, report }))
+}
+
+/// Erase everything stored about the address, and record that it was done. An address that
+/// matched nothing is a 404, and isn't recorded as a fulfilled request.
+#[tracing::instrument(
+    name = "Fulfil a data erasure request",
+    skip(form, pool, settings, user_id)
+)]
+pub async fn request_data_erasure(
+    form: web::Json<DataRequestData>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<DataRequestSettings>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, AppError> {
+    let email = form.email()?;
+    let request_id = Uuid::new_v4();
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to start a transaction")?;
+    let email_hash = email_hash(&settings.hash_key, email);
+    let erased = erase(&mut transaction, email, &email_hash, request_id)
+        .await
+        .context("Failed to erase the stored data")?;
+    if erased.is_empty() {
+        return Err(nothing_stored());
+    }
+    record_fulfilled_request(
+        &mut *transaction,
+        request_id,
+        DataRequestKind::Erasure,
+        &email_hash,
+        *user_id.into_inner(),
+    )
+    .await
+    .context("Failed to record the erasure request")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the erasure")?;
+
+    tracing::info!(%request_id, ?erased, "Erased the stored data");
+    Ok(HttpResponse::Ok().json(ErasureResponse { request_id, erased }))
+}
+
+fn nothing_stored() -> AppError {
+    AppError::NotFound("Nothing is stored about this address".to_string())
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct DataRequestFilters {
+    /// Only the requests about this address.
+    email: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct FulfilledDataRequest {
+    request_id: Uuid,
+    kind: String,
+    /// Missing for requests recorded before addresses were hashed with a key.
+    email_hash: Option<String>,
+    fulfilled_by: String,
+    fulfilled_at: DateTime<Utc>,
+}
+
+/// The audit log of fulfilled requests, newest first.
+#[tracing::instrument(name = "List data subject requests", skip(filters, pool, settings))]
+pub async fn list_data_requests(
+    filters: web::Query<DataRequestFilters>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<DataRequestSettings>,
+) -> Result<HttpResponse, AppError> {
+    let email_hash = filters
+        .email
+        .as_deref()
+        .map(|email| email_hash(&settings.hash_key, email.trim()));
+    let requests = sqlx::query_as!(
+        FulfilledDataRequest,
+        r#"
+        SELECT
+            data_subject_requests.request_id,
+            data_subject_requests.kind,
+            data_subject_requests.email_hash,
+            users.username AS fulfilled_by,
+            data_subject_requests.fulfilled_at
+        FROM data_subject_requests
+        JOIN users ON users.user_id = data_subject_requests.fulfilled_by
+        WHERE
+            $1::text IS NULL OR
+            data_subject_requests.email_hash = $1
+        ORDER BY data_subject_requests.fulfilled_at DESC
+        "#,
+        email_hash
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve data subject requests")?;
+
+    Ok(HttpResponse::Ok().json(requests))
+}
@@ -0,0 +1,117 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{issue_delivery_worker::notify_workers, utils::AppError};
+
+#[derive(serde::Serialize)]
+pub struct DeliveryFailure {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+    n_retries: i16,
+    last_error: String,
+    failed_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "List delivery failures", skip(pool))]
+pub async fn list_delivery_failures(pool: web::Data<PgPool>) -> Result<HttpResponse, AppError> {
+    let failures = sqlx::query_as!(
+        DeliveryFailure,
+        r#"
+        SELECT newsletter_issue_id, subscriber_email, n_retries, last_error, failed_at
+        FROM issue_delivery_failures
+        ORDER BY failed_at DESC
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve delivery failures")?;
+
+    Ok(HttpResponse::Ok().json(failures))
+}
+
+#[derive(serde::Deserialize)]
+pub struct RequeueDeliveryFailuresData {
+    /// Only requeue failures for this issue. All failures are requeued if omitted.
+    newsletter_issue_id: Option<Uuid>,
+}
+
+#[tracing::instrument(name = "Requeue delivery failures", skip(form, pool))]
+pub async fn requeue_delivery_failures(
+    form: web::Json<RequeueDeliveryFailuresData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let requeued = sqlx::query!(
+        r#"
+        WITH requeued AS (
+            DELETE FROM issue_delivery_failures
+            WHERE
+                ($1::uuid IS NULL OR newsletter_issue_id = $1) AND
+                -- Cancelled issues must stay cancelled
+                newsletter_issue_id IN (
+                    SELECT newsletter_issue_id
+                    FROM newsletter_issues
+                    WHERE status <> 'cancelled'
+                ) AND
+                -- Addresses that unsubscribed or were suppressed since must not be emailed again
+                EXISTS (
+                    SELECT 1
+                    FROM subscriptions
+                    JOIN list_memberships ON list_memberships.subscriber_id = subscriptions.id
+                    JOIN newsletter_issue_lists
+                        ON newsletter_issue_lists.list_id = list_memberships.list_id
+                    WHERE
+                        subscriptions.email = issue_delivery_failures.subscriber_email AND
+                        subscriptions.status = 'confirmed' AND
+                        list_memberships.status = 'confirmed' AND
+                        newsletter_issue_lists.newsletter_issue_id =
+                            issue_delivery_failures.newsletter_issue_id
+                ) AND
+                NOT EXISTS (
+                    SELECT 1
+                    FROM suppressions
+                    WHERE
+                        lower(suppressions.email) =
+                            lower(issue_delivery_failures.subscriber_email)
+                )
+            RETURNING newsletter_issue_id, subscriber_email
+        ),
+        resumed AS (
+            UPDATE newsletter_issues
+            SET status = 'sending'
+            WHERE
+                status = 'completed' AND
+                newsletter_issue_id IN (SELECT newsletter_issue_id FROM requeued)
+        ),
+        marked_as_requeued AS (
+            UPDATE issue_deliveries
+            SET outcome = 'requeued'
+            FROM requeued
+            WHERE
+                issue_deliveries.newsletter_issue_id = requeued.newsletter_issue_id AND
+                issue_deliveries.subscriber_email = requeued.subscriber_email
+        )
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        SELECT newsletter_issue_id, subscriber_email
+        FROM requeued
+        ON CONFLICT DO NOTHING
+        "#,
+        form.newsletter_issue_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to requeue delivery failures")?
+    .rows_affected();
+
+    if requeued > 0 {
+        notify_workers(pool.get_ref())
+            .await
+            .context("Failed to notify the delivery workers")?;
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "requeued": requeued
+    })))
+}
@@ -0,0 +1,288 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool};
+use uuid::Uuid;
+
+use super::{publish_issue, validate_audience, validate_send_at};
+use crate::{
+    authentication::UserId,
+    domain::NewsletterIssue,
+    idempotency::{IdempotencyKey, NextAction, save_response, try_processing},
+    startup::ApplicationBaseUrl,
+    utils::AppError,
+};
+
+#[derive(serde::Serialize)]
+pub struct Draft {
+    draft_id: Uuid,
+    title: String,
+    content_text: String,
+    content_html: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+pub struct DraftSummary {
+    draft_id: Uuid,
+    title: String,
+    updated_at: DateTime<Utc>,
+}
+
+/// All fields are optional, so that drafts can be created empty and autosaved one field at a time.
+#[derive(serde::Deserialize)]
+pub struct DraftData {
+    title: Option<String>,
+    content_text: Option<String>,
+    content_html: Option<String>,
+}
+
+#[tracing::instrument(name = "Create draft", skip(form, pool))]
+pub async fn create_draft(
+    form: web::Json<DraftData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let draft = sqlx::query_as!(
+        Draft,
+        r#"
+        INSERT INTO newsletter_drafts (draft_id, title, content_text, content_html)
+        VALUES ($1, COALESCE($2, ''), COALESCE($3, ''), COALESCE($4, ''))
+        RETURNING draft_id, title, content_text, content_html, created_at, updated_at
+        "#,
+        Uuid::new_v4(),
+        form.title,
+        form.content_text,
+        form.content_html
+    )
+    .fetch_one(pool.get_ref())
+    .await
+    .context("Failed to store draft")?;
+
+    Ok(HttpResponse::Created().json(draft))
+}
+
+#[tracing::instrument(name = "List drafts", skip(pool))]
+pub async fn list_drafts(pool: web::Data<PgPool>) -> Result<HttpResponse, AppError> {
+    let drafts = sqlx::query_as!(
+        DraftSummary,
+        r#"
+        SELECT draft_id, title, updated_at
+        FROM newsletter_drafts
+        ORDER BY updated_at DESC
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve drafts")?;
+
+    Ok(HttpResponse::Ok().json(drafts))
+}
+
+#[tracing::instrument(name = "Get draft", skip(pool))]
+pub async fn get_draft(
+    draft_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let draft = fetch_draft(&pool, *draft_id).await?;
+    Ok(HttpResponse::Ok().json(draft))
+}
+
+/// Only the fields present in the body are changed, so an editor can autosave whichever field
+/// they are typing in without overwriting the others.
+#[tracing::instrument(name = "Update draft", skip(form, pool))]
+pub async fn update_draft(
+    draft_id: web::Path<Uuid>,
+    form: web::Json<DraftData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let draft = sqlx::query_as!(
+        Draft,
+        r#"
+        UPDATE newsletter_drafts
+        SET
+            title = COALESCE($2, title),
+            content_text = COALESCE($3, content_text),
+            content_html = COALESCE($4, content_html),
+            updated_at = now()
+        WHERE draft_id = $1
+        RETURNING draft_id, title, content_text, content_html, created_at, updated_at
+        "#,
+        *draft_id,
+        form.title,
+        form.content_text,
+        form.content_html
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to update draft")?
+    .ok_or_else(draft_not_found)?;
+
+    Ok(HttpResponse::Ok().json(draft))
+}
+
+#[tracing::instrument(name = "Delete draft", skip(pool))]
+pub async fn delete_draft(
+    draft_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let deleted = sqlx::query!(
+        "DELETE FROM newsletter_drafts WHERE draft_id = $1",
+        *draft_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to delete draft")?
+    .rows_affected();
+
+    if deleted == 0 {
+        return Err(draft_not_found());
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// The subject and bodies exactly as they will be sent, with a placeholder unsubscribe link.
+#[tracing::instrument(name = "Preview draft", skip(pool, base_url))]
+pub async fn preview_draft(
+    draft_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, AppError> {
+    let draft = fetch_draft(&pool, *draft_id).await?;
+    let issue = NewsletterIssue::from(draft).preview(&base_url.0, "subscriber@example.com")?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "subject": issue.title,
+        "content_html": issue.content_html,
+        "content_text": issue.content_text,
+    })))
+}
+
+#[derive(serde::Deserialize)]
+pub struct PublishDraftData {
+    idempotency_key: String,
+    send_at: Option<DateTime<Utc>>,
+    /// The list whose members receive the issue, like `list_ids` with only this list.
+    list_id: Option<Uuid>,
+    /// The lists whose members receive the issue. The default list if neither this nor
+    /// `list_id` is given.
+    #[serde(default)]
+    list_ids: Vec<Uuid>,
+    /// Only send the issue to the lists' members in this segment.
+    segment_id: Option<Uuid>,
+    /// Keep the issue out of the public archive.
+    #[serde(default)]
+    exclude_from_archive: bool,
+}
+
+/// Publish the draft as a newsletter issue and delete it, so it can't be published twice.
+#[tracing::instrument(name = "Publish draft", skip(form, pool, user_id))]
+pub async fn publish_draft(
+    draft_id: web::Path<Uuid>,
+    form: web::Json<PublishDraftData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, AppError> {
+    let PublishDraftData {
+        idempotency_key,
+        send_at,
+        list_id,
+        list_ids,
+        segment_id,
+        exclude_from_archive,
+    } = form.0;
+    let idempotency_key: IdempotencyKey = idempotency_key.try_into()?;
+    if let Some(send_at) = send_at {
+        validate_send_at(send_at)?;
+    }
+    let audience = validate_audience(
+        &pool,
+        list_id.into_iter().chain(list_ids).collect(),
+        segment_id,
+    )
+    .await?;
+    let user_id = user_id.into_inner();
+
+    let mut transaction = match try_processing(&pool, &idempotency_key, &user_id).await? {
+        NextAction::StartProcessing(transaction) => transaction,
+        NextAction::ReturnSavedResponse(saved_response) => {
+            return Ok(saved_response);
+        }
+    };
+
+    let draft = sqlx::query_as!(
+        Draft,
+        r#"
+        SELECT draft_id, title, content_text, content_html, created_at, updated_at
+        FROM newsletter_drafts
+        WHERE draft_id = $1
+        FOR UPDATE
+        "#,
+        *draft_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to retrieve draft")?
+    .ok_or_else(draft_not_found)?;
+
+    if draft.title.trim().is_empty()
+        || draft.content_text.trim().is_empty()
+        || draft.content_html.trim().is_empty()
+    {
+        return Err(AppError::BadInputData(
+            "A draft needs a title and both contents before it can be published".to_string(),
+        ));
+    }
+
+    transaction
+        .execute(sqlx::query!(
+            "DELETE FROM newsletter_drafts WHERE draft_id = $1",
+            draft.draft_id
+        ))
+        .await
+        .context("Failed to delete published draft")?;
+
+    let issue = NewsletterIssue::from(draft);
+    issue.validate()?;
+    let response = publish_issue(
+        &mut transaction,
+        &issue,
+        &audience,
+        send_at,
+        exclude_from_archive,
+    )
+    .await?;
+    let response = save_response(transaction, &idempotency_key, &user_id, response).await?;
+    Ok(response)
+}
+
+impl From<Draft> for NewsletterIssue {
+    fn from(draft: Draft) -> Self {
+        Self {
+            title: draft.title,
+            content_text: draft.content_text,
+            content_html: draft.content_html,
+        }
+    }
+}
+
+async fn fetch_draft(pool: &PgPool, draft_id: Uuid) -> Result<Draft, AppError> {
+    let draft = sqlx::query_as!(
+        Draft,
+        r#"
+        SELECT draft_id, title, content_text, content_html, created_at, updated_at
+        FROM newsletter_drafts
+        WHERE draft_id = $1
+        "#,
+        draft_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve draft")?
+    .ok_or_else(draft_not_found)?;
+    Ok(draft)
+}
+
+fn draft_not_found() -> AppError {
+    AppError::NotFound("No such draft".to_string())
+}
@@ -0,0 +1,106 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::utils::AppError;
+
+const MAX_NAME_LENGTH: usize = 100;
+
+#[derive(serde::Serialize)]
+pub struct List {
+    list_id: Uuid,
+    name: String,
+    description: String,
+    /// Whether signups and issues that don't name a list use this one.
+    is_default: bool,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+pub struct ListSummary {
+    #[serde(flatten)]
+    list: List,
+    confirmed_members: i64,
+    pending_members: i64,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ListData {
+    name: String,
+    #[serde(default)]
+    description: String,
+}
+
+#[tracing::instrument(name = "List lists", skip(pool))]
+pub async fn list_lists(pool: web::Data<PgPool>) -> Result<HttpResponse, AppError> {
+    let lists: Vec<ListSummary> = sqlx::query!(
+        r#"
+        SELECT
+            lists.list_id,
+            lists.name,
+            lists.description,
+            lists.is_default,
+            lists.created_at,
+            count(*) FILTER (WHERE list_memberships.status = 'confirmed') AS "confirmed_members!",
+            count(*) FILTER (
+                WHERE list_memberships.status = 'pending_confirmation'
+            ) AS "pending_members!"
+        FROM lists
+        LEFT JOIN list_memberships ON list_memberships.list_id = lists.list_id
+        GROUP BY lists.list_id
+        ORDER BY lists.name
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve lists")?
+    .into_iter()
+    .map(|row| ListSummary {
+        list: List {
+            list_id: row.list_id,
+            name: row.name,
+            description: row.description,
+            is_default: row.is_default,
+            created_at: row.created_at,
+        },
+        confirmed_members: row.confirmed_members,
+        pending_members: row.pending_members,
+    })
+    .collect();
+
+    Ok(HttpResponse::Ok().json(lists))
+}
+
+#[tracing::instrument(name = "Create list", skip(form, pool))]
+pub async fn create_list(
+    form: web::Json<ListData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let name = form.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(AppError::BadInputData(format!(
+            "A list needs a name of at most {MAX_NAME_LENGTH} characters"
+        )));
+    }
+
+    let list = sqlx::query_as!(
+        List,
+        r#"
+        INSERT INTO lists (list_id, name, description)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (name) DO NOTHING
+        RETURNING list_id, name, description, is_default, created_at
+        "#,
+        Uuid::new_v4(),
+        name,
+        form.description.trim()
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to store list")?
+    .ok_or_else(|| AppError::Conflict(format!("There already is a list named '{name}'")))?;
+
+    Ok(HttpResponse::Created().json(list))
+}
@@ -1,9 +1,33 @@
 mod dashboard;
+mod data_requests;
+mod delivery_failures;
+mod drafts;
+mod lists;
 mod logout;
+mod newsletter_state;
+mod newsletter_status;
 mod newsletters;
 mod password;
+mod scheduled_newsletters;
+mod segments;
+mod subscriber_export;
+mod subscriber_import;
+mod subscribers;
+mod test_newsletter;
 
 pub use dashboard::user_metadata;
+pub use data_requests::*;
+pub use delivery_failures::*;
+pub use drafts::*;
+pub use lists::*;
 pub use logout::log_out;
+pub use newsletter_state::*;
+pub use newsletter_status::*;
 pub use newsletters::*;
 pub use password::*;
+pub use scheduled_newsletters::*;
+pub use segments::*;
+pub use subscriber_export::*;
+pub use subscriber_import::*;
+pub use subscribers::*;
+pub use test_newsletter::*;
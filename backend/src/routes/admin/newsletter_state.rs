@@ -0,0 +1,160 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres};
+use uuid::Uuid;
+
+use crate::{issue_delivery_worker::notify_workers, utils::AppError};
+
+/// Stop delivering an issue that is being sent, e.g. to fix a typo. Deliveries that are already
+/// in flight still go out.
+#[tracing::instrument(name = "Pause newsletter", skip(pool))]
+pub async fn pause_newsletter(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+
+    let updated = set_status(pool.get_ref(), newsletter_issue_id, &["sending"], "paused")
+        .await
+        .context("Failed to pause newsletter")?;
+    if updated == 0 {
+        return Err(state_conflict(
+            &pool,
+            newsletter_issue_id,
+            "The newsletter issue is not being sent",
+        )
+        .await);
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(name = "Resume newsletter", skip(pool))]
+pub async fn resume_newsletter(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+
+    let updated = set_status(pool.get_ref(), newsletter_issue_id, &["paused"], "sending")
+        .await
+        .context("Failed to resume newsletter")?;
+    if updated == 0 {
+        return Err(state_conflict(
+            &pool,
+            newsletter_issue_id,
+            "The newsletter issue is not paused",
+        )
+        .await);
+    }
+    // The workers have been ignoring the issue's tasks, they may all be idle by now
+    notify_workers(pool.get_ref())
+        .await
+        .context("Failed to notify the delivery workers")?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Cancel a scheduled, sending or paused issue and drop all its pending deliveries.
+/// The delivery log of the subscribers who already received it is kept.
+#[tracing::instrument(name = "Cancel newsletter", skip(pool))]
+pub async fn cancel_newsletter(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let mut transaction = pool.begin().await.context("Failed to start transaction")?;
+
+    let updated = set_status(
+        &mut *transaction,
+        newsletter_issue_id,
+        &["scheduled", "sending", "paused"],
+        "cancelled",
+    )
+    .await
+    .context("Failed to cancel newsletter")?;
+    if updated == 0 {
+        return Err(state_conflict(
+            &pool,
+            newsletter_issue_id,
+            "The newsletter issue has already been sent or cancelled",
+        )
+        .await);
+    }
+
+    // Waits for workers that are delivering one of these tasks right now
+    let purged = transaction
+        .execute(sqlx::query!(
+            "DELETE FROM issue_delivery_queue WHERE newsletter_issue_id = $1",
+            newsletter_issue_id
+        ))
+        .await
+        .context("Failed to purge queued deliveries")?
+        .rows_affected();
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            UPDATE issue_deliveries
+            SET outcome = 'cancelled'
+            WHERE newsletter_issue_id = $1 AND outcome IN ('retrying', 'requeued')
+            "#,
+            newsletter_issue_id
+        ))
+        .await
+        .context("Failed to update the delivery log")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit cancellation")?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "purged": purged
+    })))
+}
+
+/// Move the issue to the `to` state, if it is in one of the `from` states.
+/// Returns the number of updated issues, i.e. 0 or 1.
+async fn set_status<'e>(
+    executor: impl Executor<'e, Database = Postgres>,
+    newsletter_issue_id: Uuid,
+    from: &[&str],
+    to: &str,
+) -> Result<u64, sqlx::Error> {
+    let from: Vec<String> = from.iter().map(|s| s.to_string()).collect();
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = $3
+        WHERE newsletter_issue_id = $1 AND status = ANY($2)
+        "#,
+        newsletter_issue_id,
+        &from,
+        to
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// The error for an issue that isn't in the right state for an action, which is a 404 if the
+/// issue doesn't exist at all.
+pub(crate) async fn state_conflict(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    message: &str,
+) -> AppError {
+    let exists = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await;
+
+    match exists {
+        Ok(Some(_)) => AppError::Conflict(message.to_string()),
+        Ok(None) => AppError::NotFound("No such newsletter issue".to_string()),
+        Err(e) => AppError::DatabaseError(e),
+    }
+}
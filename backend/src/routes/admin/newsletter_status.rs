@@ -0,0 +1,65 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::utils::AppError;
+
+#[derive(serde::Serialize)]
+pub struct NewsletterStatus {
+    newsletter_issue_id: Uuid,
+    status: String,
+    /// Where the issue is in the public archive, see `exclude_from_archive`.
+    slug: String,
+    exclude_from_archive: bool,
+    /// Waiting to be sent, including deliveries that will be retried.
+    queued: i64,
+    sent: i64,
+    failed: i64,
+}
+
+/// Delivery progress of an issue, so the frontend can show how far along it is.
+#[tracing::instrument(name = "Get newsletter status", skip(pool))]
+pub async fn get_newsletter_status(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let status = sqlx::query_as!(
+        NewsletterStatus,
+        r#"
+        SELECT
+            newsletter_issue_id,
+            status,
+            slug,
+            exclude_from_archive,
+            (
+                SELECT count(*)
+                FROM issue_delivery_queue
+                WHERE issue_delivery_queue.newsletter_issue_id = newsletter_issues.newsletter_issue_id
+            ) AS "queued!",
+            (
+                SELECT count(*)
+                FROM issue_deliveries
+                WHERE
+                    issue_deliveries.newsletter_issue_id = newsletter_issues.newsletter_issue_id AND
+                    outcome = 'sent'
+            ) AS "sent!",
+            (
+                SELECT count(*)
+                FROM issue_deliveries
+                WHERE
+                    issue_deliveries.newsletter_issue_id = newsletter_issues.newsletter_issue_id AND
+                    outcome = 'failed'
+            ) AS "failed!"
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        *newsletter_issue_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to retrieve newsletter status")?
+    .ok_or_else(|| AppError::NotFound("No such newsletter issue".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(status))
+}
@@ -1,11 +1,15 @@
 use actix_web::{HttpResponse, web};
 use anyhow::Context;
+use chrono::{DateTime, Utc};
 use sqlx::{Executor, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
     authentication::UserId,
+    domain::{NewsletterIssue, archive_slug},
     idempotency::{IdempotencyKey, NextAction, save_response, try_processing},
+    issue_delivery_worker::{enqueue_deliveries, notify_workers},
+    lists::target_list,
     utils::AppError,
 };
 
@@ -15,6 +19,19 @@ pub struct PublishNewsletterData {
     content_text: String,
     content_html: String,
     idempotency_key: String,
+    /// Deliver the issue at this time instead of right away.
+    send_at: Option<DateTime<Utc>>,
+    /// The list whose members receive the issue, like `list_ids` with only this list.
+    list_id: Option<Uuid>,
+    /// The lists whose members receive the issue. The default list if neither this nor
+    /// `list_id` is given.
+    #[serde(default)]
+    list_ids: Vec<Uuid>,
+    /// Only send the issue to the lists' members in this segment.
+    segment_id: Option<Uuid>,
+    /// Keep the issue out of the public archive.
+    #[serde(default)]
+    exclude_from_archive: bool,
 }
 
 #[tracing::instrument(name = "Publish newsletter", skip(form, pool, user_id))]
@@ -28,8 +45,28 @@ pub async fn publish_newsletter(
         content_text,
         content_html,
         idempotency_key,
+        send_at,
+        list_id,
+        list_ids,
+        segment_id,
+        exclude_from_archive,
     } = form.0;
     let idempotency_key: IdempotencyKey = idempotency_key.try_into()?;
+    if let Some(send_at) = send_at {
+        validate_send_at(send_at)?;
+    }
+    let audience = validate_audience(
+        &pool,
+        list_id.into_iter().chain(list_ids).collect(),
+        segment_id,
+    )
+    .await?;
+    let issue = NewsletterIssue {
+        title,
+        content_text,
+        content_html,
+    };
+    issue.validate()?;
     let user_id = user_id.into_inner();
 
     let mut transaction = match try_processing(&pool, &idempotency_key, &user_id).await? {
@@ -39,25 +76,151 @@ pub async fn publish_newsletter(
         }
     };
 
-    let issue_id = insert_newsletter_issue(&mut transaction, &title, &content_text, &content_html)
-        .await
-        .context("Failed to store newsletter issue details")?;
+    let response = publish_issue(
+        &mut transaction,
+        &issue,
+        &audience,
+        send_at,
+        exclude_from_archive,
+    )
+    .await?;
+    let response = save_response(transaction, &idempotency_key, &user_id, response).await?;
+    Ok(response)
+}
+
+#[derive(serde::Deserialize)]
+pub struct ArchiveVisibilityData {
+    exclude_from_archive: bool,
+}
+
+/// Keep an issue out of the public archive, or put it back.
+#[tracing::instrument(name = "Set archive visibility", skip(form, pool))]
+pub async fn set_archive_visibility(
+    newsletter_issue_id: web::Path<Uuid>,
+    form: web::Json<ArchiveVisibilityData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let issue = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET exclude_from_archive = $2
+        WHERE newsletter_issue_id = $1
+        RETURNING newsletter_issue_id, slug, exclude_from_archive
+        "#,
+        *newsletter_issue_id,
+        form.exclude_from_archive
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to update the newsletter issue")?
+    .ok_or_else(|| AppError::NotFound("No such newsletter issue".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "newsletter_issue_id": issue.newsletter_issue_id,
+        "slug": issue.slug,
+        "exclude_from_archive": issue.exclude_from_archive,
+    })))
+}
+
+/// Who receives an issue: the confirmed members of the lists, narrowed down to the segment if
+/// there is one.
+pub(crate) struct Audience {
+    list_ids: Vec<Uuid>,
+    segment_id: Option<Uuid>,
+}
+
+/// Store the issue and enqueue its delivery to the audience, or leave it for the worker if it
+/// is scheduled. Returns the response the publishing endpoints should send.
+pub(crate) async fn publish_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue: &NewsletterIssue,
+    audience: &Audience,
+    send_at: Option<DateTime<Utc>>,
+    exclude_from_archive: bool,
+) -> Result<HttpResponse, anyhow::Error> {
+    let issue_id =
+        insert_newsletter_issue(transaction, issue, audience, send_at, exclude_from_archive)
+            .await
+            .context("Failed to store newsletter issue details")?;
 
-    enqueue_delivery_tasks(&mut transaction, issue_id)
+    if let Some(send_at) = send_at {
+        return Ok(HttpResponse::Accepted().json(serde_json::json!({
+            "newsletter_issue_id": issue_id,
+            "send_at": send_at,
+        })));
+    }
+
+    enqueue_deliveries(transaction, issue_id).await?;
+    notify_workers(&mut **transaction)
         .await
-        .context("Failed to enqueue delivery tasks")?;
+        .context("Failed to notify the delivery workers")?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "newsletter_issue_id": issue_id,
+    })))
+}
 
-    let response = HttpResponse::Ok().finish();
-    let response = save_response(transaction, &idempotency_key, &user_id, response).await?;
-    Ok(response)
+/// Scheduling an issue in the past is almost certainly a mistake, e.g. a wrong time zone.
+pub(crate) fn validate_send_at(send_at: DateTime<Utc>) -> Result<(), AppError> {
+    if send_at <= Utc::now() {
+        return Err(AppError::BadInputData(
+            "send_at must be in the future".to_string(),
+        ));
+    }
+    Ok(())
 }
 
+/// Who an issue is sent to: the lists `list_ids` refer to, or the default list if there are
+/// none, and the segment if one is given.
+pub(crate) async fn validate_audience(
+    pool: &PgPool,
+    mut list_ids: Vec<Uuid>,
+    segment_id: Option<Uuid>,
+) -> Result<Audience, AppError> {
+    list_ids.sort_unstable();
+    list_ids.dedup();
+    if list_ids.is_empty() {
+        let default_list = target_list(pool, None)
+            .await?
+            .ok_or_else(|| AppError::BadInputData("No such list".to_string()))?;
+        list_ids.push(default_list);
+    }
+    let known_lists = sqlx::query!(
+        r#"SELECT count(*) AS "count!" FROM lists WHERE list_id = ANY($1)"#,
+        &list_ids
+    )
+    .fetch_one(pool)
+    .await?
+    .count;
+    if known_lists != list_ids.len() as i64 {
+        return Err(AppError::BadInputData("No such list".to_string()));
+    }
+    if let Some(segment_id) = segment_id {
+        let exists = sqlx::query!(
+            "SELECT segment_id FROM segments WHERE segment_id = $1",
+            segment_id
+        )
+        .fetch_optional(pool)
+        .await?
+        .is_some();
+        if !exists {
+            return Err(AppError::BadInputData("No such segment".to_string()));
+        }
+    }
+    Ok(Audience {
+        list_ids,
+        segment_id,
+    })
+}
+
+/// Issues with a `send_at` are stored as `scheduled` and published later by the background
+/// worker, see `issue_delivery_worker::enqueue_due_issues`.
 #[tracing::instrument(skip_all)]
 async fn insert_newsletter_issue(
     transaction: &mut Transaction<'_, Postgres>,
-    title: &str,
-    content_text: &str,
-    content_html: &str,
+    issue: &NewsletterIssue,
+    audience: &Audience,
+    send_at: Option<DateTime<Utc>>,
+    exclude_from_archive: bool,
 ) -> Result<Uuid, sqlx::Error> {
     let newsletter_issue_id = Uuid::new_v4();
     let query = sqlx::query!(
@@ -67,36 +230,40 @@ async fn insert_newsletter_issue(
             title,
             content_text,
             content_html,
-            published_at
+            segment_id,
+            status,
+            send_at,
+            published_at,
+            slug,
+            exclude_from_archive
+        )
+        VALUES (
+            $1, $2, $3, $4, $5,
+            CASE WHEN $6::timestamptz IS NULL THEN 'sending' ELSE 'scheduled' END,
+            $6,
+            CASE WHEN $6::timestamptz IS NULL THEN now() END,
+            $7,
+            $8
         )
-        VALUES ($1, $2, $3, $4, now())
         "#,
         newsletter_issue_id,
-        title,
-        content_text,
-        content_html,
+        issue.title,
+        issue.content_text,
+        issue.content_html,
+        audience.segment_id,
+        send_at,
+        archive_slug(&issue.title, newsletter_issue_id),
+        exclude_from_archive
     );
     transaction.execute(query).await?;
-    Ok(newsletter_issue_id)
-}
-
-#[tracing::instrument(skip_all)]
-async fn enqueue_delivery_tasks(
-    transaction: &mut Transaction<'_, Postgres>,
-    newsletter_issue_id: Uuid,
-) -> Result<(), sqlx::Error> {
     let query = sqlx::query!(
         r#"
-        INSERT INTO issue_delivery_queue (
-            newsletter_issue_id,
-            subscriber_email
-        )
-        SELECT $1, email
-        FROM subscriptions
-        WHERE status = 'confirmed'
+        INSERT INTO newsletter_issue_lists (newsletter_issue_id, list_id)
+        SELECT $1, unnest($2::uuid[])
         "#,
         newsletter_issue_id,
+        &audience.list_ids
     );
     transaction.execute(query).await?;
-    Ok(())
+    Ok(newsletter_issue_id)
 }
@@ -0,0 +1,72 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{state_conflict, validate_send_at};
+use crate::utils::AppError;
+
+#[derive(serde::Serialize)]
+pub struct ScheduledNewsletter {
+    newsletter_issue_id: Uuid,
+    title: String,
+    send_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "List scheduled newsletters", skip(pool))]
+pub async fn list_scheduled_newsletters(pool: web::Data<PgPool>) -> Result<HttpResponse, AppError> {
+    let newsletters = sqlx::query_as!(
+        ScheduledNewsletter,
+        r#"
+        SELECT newsletter_issue_id, title, send_at as "send_at!"
+        FROM newsletter_issues
+        WHERE status = 'scheduled'
+        ORDER BY send_at
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve scheduled newsletters")?;
+
+    Ok(HttpResponse::Ok().json(newsletters))
+}
+
+#[derive(serde::Deserialize)]
+pub struct RescheduleNewsletterData {
+    send_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Reschedule newsletter", skip(form, pool))]
+pub async fn reschedule_newsletter(
+    newsletter_issue_id: web::Path<Uuid>,
+    form: web::Json<RescheduleNewsletterData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    validate_send_at(form.send_at)?;
+
+    let updated = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET send_at = $2
+        WHERE newsletter_issue_id = $1 AND status = 'scheduled'
+        "#,
+        newsletter_issue_id,
+        form.send_at
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to reschedule newsletter")?
+    .rows_affected();
+
+    if updated == 0 {
+        return Err(state_conflict(
+            &pool,
+            newsletter_issue_id,
+            "The newsletter issue is not scheduled",
+        )
+        .await);
+    }
+    Ok(HttpResponse::Ok().finish())
+}
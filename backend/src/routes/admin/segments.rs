@@ -0,0 +1,228 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::{domain::SegmentFilter, utils::AppError};
+
+const MAX_NAME_LENGTH: usize = 100;
+
+#[derive(serde::Serialize)]
+pub struct Segment {
+    segment_id: Uuid,
+    name: String,
+    filter: serde_json::Value,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+pub struct SegmentDetails {
+    #[serde(flatten)]
+    segment: Segment,
+    /// How many subscribers, whatever their status, are in the segment right now.
+    matching_subscribers: i64,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SegmentData {
+    name: String,
+    filter: serde_json::Value,
+}
+
+impl SegmentData {
+    /// The trimmed name and the filter, if both are valid.
+    fn parse(self) -> Result<(String, SegmentFilter), AppError> {
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+            return Err(AppError::BadInputData(format!(
+                "A segment needs a name of at most {MAX_NAME_LENGTH} characters"
+            )));
+        }
+        let filter = SegmentFilter::parse(self.filter).map_err(AppError::BadInputData)?;
+        Ok((name.to_string(), filter))
+    }
+}
+
+#[tracing::instrument(name = "List segments", skip(pool))]
+pub async fn list_segments(pool: web::Data<PgPool>) -> Result<HttpResponse, AppError> {
+    let segments = sqlx::query_as!(
+        Segment,
+        r#"
+        SELECT segment_id, name, filter, created_at, updated_at
+        FROM segments
+        ORDER BY name
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve segments")?;
+
+    Ok(HttpResponse::Ok().json(segments))
+}
+
+#[tracing::instrument(name = "Create segment", skip(form, pool))]
+pub async fn create_segment(
+    form: web::Json<SegmentData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let (name, filter) = form.0.parse()?;
+
+    let segment = sqlx::query_as!(
+        Segment,
+        r#"
+        INSERT INTO segments (segment_id, name, filter, created_at, updated_at)
+        VALUES ($1, $2, $3, now(), now())
+        ON CONFLICT (name) DO NOTHING
+        RETURNING segment_id, name, filter, created_at, updated_at
+        "#,
+        Uuid::new_v4(),
+        name,
+        serde_json::to_value(&filter).context("Failed to serialize the segment filter")?
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to store segment")?
+    .ok_or_else(|| segment_name_taken(&name))?;
+
+    Ok(HttpResponse::Created().json(segment))
+}
+
+#[tracing::instrument(name = "Get segment", skip(pool))]
+pub async fn get_segment(
+    segment_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let segment = sqlx::query_as!(
+        Segment,
+        r#"
+        SELECT segment_id, name, filter, created_at, updated_at
+        FROM segments
+        WHERE segment_id = $1
+        "#,
+        *segment_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to retrieve segment")?
+    .ok_or_else(segment_not_found)?;
+    let filter = SegmentFilter::parse(segment.filter.clone())
+        .map_err(anyhow::Error::msg)
+        .context("The stored segment filter is invalid")?;
+
+    let mut query = QueryBuilder::<Postgres>::new("SELECT count(*) FROM subscriptions WHERE ");
+    filter.push_sql(&mut query, Utc::now());
+    let matching_subscribers = query
+        .build_query_scalar()
+        .fetch_one(pool.get_ref())
+        .await
+        .context("Failed to count the subscribers in the segment")?;
+
+    Ok(HttpResponse::Ok().json(SegmentDetails {
+        segment,
+        matching_subscribers,
+    }))
+}
+
+/// Rename the segment or change its filter. Scheduled issues targeting it use the new filter.
+#[tracing::instrument(name = "Update segment", skip(form, pool))]
+pub async fn update_segment(
+    segment_id: web::Path<Uuid>,
+    form: web::Json<SegmentData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let (name, filter) = form.0.parse()?;
+
+    let segment = sqlx::query_as!(
+        Segment,
+        r#"
+        UPDATE segments
+        SET name = $2, filter = $3, updated_at = now()
+        WHERE segment_id = $1
+        RETURNING segment_id, name, filter, created_at, updated_at
+        "#,
+        *segment_id,
+        name,
+        serde_json::to_value(&filter).context("Failed to serialize the segment filter")?
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(|e| match e.as_database_error() {
+        Some(error) if error.is_unique_violation() => segment_name_taken(&name),
+        _ => anyhow::Error::new(e)
+            .context("Failed to update segment")
+            .into(),
+    })?
+    .ok_or_else(segment_not_found)?;
+
+    Ok(HttpResponse::Ok().json(segment))
+}
+
+/// Delete a segment that no issue still has to be sent to. Issues that were already sent to it
+/// keep their statistics but forget the segment.
+#[tracing::instrument(name = "Delete segment", skip(pool))]
+pub async fn delete_segment(
+    segment_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+
+    let segment = sqlx::query!(
+        "SELECT segment_id FROM segments WHERE segment_id = $1 FOR UPDATE",
+        *segment_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to retrieve segment")?;
+    if segment.is_none() {
+        return Err(segment_not_found());
+    }
+
+    let pending_issues = sqlx::query!(
+        r#"
+        SELECT count(*) AS "count!"
+        FROM newsletter_issues
+        WHERE segment_id = $1 AND status IN ('scheduled', 'sending', 'paused')
+        "#,
+        *segment_id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .context("Failed to count the issues targeting the segment")?
+    .count;
+    if pending_issues > 0 {
+        return Err(AppError::Conflict(format!(
+            "{pending_issues} issue(s) still have to be sent to this segment"
+        )));
+    }
+
+    sqlx::query!(
+        "UPDATE newsletter_issues SET segment_id = NULL WHERE segment_id = $1",
+        *segment_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to detach the segment from its issues")?;
+    sqlx::query!("DELETE FROM segments WHERE segment_id = $1", *segment_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete segment")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the deletion")?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+fn segment_not_found() -> AppError {
+    AppError::NotFound("No such segment".to_string())
+}
+
+fn segment_name_taken(name: &str) -> AppError {
+    AppError::Conflict(format!("There already is a segment named '{name}'"))
+}
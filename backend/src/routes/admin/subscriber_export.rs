@@ -0,0 +1,205 @@
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures_util::StreamExt;
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use super::SubscriberFilters;
+use crate::csv::write_record;
+
+/// Rows are sent to the client in chunks of about this many bytes.
+const CHUNK_SIZE: usize = 64 * 1024;
+/// How many chunks may wait for a slow client before the export stops reading rows.
+const BUFFERED_CHUNKS: usize = 4;
+
+#[derive(serde::Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    /// One JSON object per line.
+    Ndjson,
+}
+
+impl ExportFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    fn file_extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ExportParameters {
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+#[derive(serde::Serialize)]
+struct ExportedSubscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    confirmed_at: Option<DateTime<Utc>>,
+    source: String,
+}
+
+const CSV_HEADER: [&str; 7] = [
+    "id",
+    "email",
+    "name",
+    "status",
+    "subscribed_at",
+    "confirmed_at",
+    "source",
+];
+
+impl ExportedSubscriber {
+    fn write_csv(&self, buffer: &mut String) {
+        let confirmed_at = self
+            .confirmed_at
+            .map(|confirmed_at| confirmed_at.to_rfc3339_opts(SecondsFormat::Micros, true))
+            .unwrap_or_default();
+        write_record(
+            buffer,
+            &[
+                &self.id.to_string(),
+                &self.email,
+                &self.name,
+                &self.status,
+                &self
+                    .subscribed_at
+                    .to_rfc3339_opts(SecondsFormat::Micros, true),
+                &confirmed_at,
+                &self.source,
+            ],
+        );
+    }
+
+    fn write_json(&self, buffer: &mut String) -> Result<(), serde_json::Error> {
+        buffer.push_str(&serde_json::to_string(self)?);
+        buffer.push('\n');
+        Ok(())
+    }
+}
+
+/// Download the subscribers matching the same filters as the subscriber list, oldest first.
+///
+/// The rows are streamed from the database to the client as they are read, so that exporting a
+/// large list doesn't hold it in memory.
+#[tracing::instrument(name = "Export subscribers", skip(pool))]
+pub async fn export_subscribers(
+    filters: web::Query<SubscriberFilters>,
+    parameters: web::Query<ExportParameters>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let format = parameters.format;
+    let (sender, receiver) = mpsc::channel(BUFFERED_CHUNKS);
+    tokio::spawn(
+        send_export(pool.get_ref().clone(), filters.into_inner(), format, sender)
+            .instrument(tracing::Span::current()),
+    );
+    let body = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "subscribers.{}",
+                format.file_extension()
+            ))],
+        })
+        .streaming(body)
+}
+
+type Chunk = Result<web::Bytes, std::io::Error>;
+
+async fn send_export(
+    pool: PgPool,
+    filters: SubscriberFilters,
+    format: ExportFormat,
+    sender: mpsc::Sender<Chunk>,
+) {
+    if let Err(e) = try_send_export(&pool, &filters, format, &sender).await {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to export subscribers"
+        );
+        // Break off the response, so that the client can't mistake it for a complete export
+        let _ = sender
+            .send(Err(std::io::Error::other("Failed to export subscribers")))
+            .await;
+    }
+}
+
+async fn try_send_export(
+    pool: &PgPool,
+    filters: &SubscriberFilters,
+    format: ExportFormat,
+    sender: &mpsc::Sender<Chunk>,
+) -> Result<(), anyhow::Error> {
+    let search_pattern = filters.search_pattern();
+    let mut subscribers = sqlx::query_as!(
+        ExportedSubscriber,
+        r#"
+        SELECT id, email, name, status, subscribed_at, confirmed_at, source
+        FROM subscriptions
+        WHERE
+            ($1::text IS NULL OR email ILIKE $1 OR name ILIKE $1) AND
+            ($2::text IS NULL OR status = $2) AND
+            ($3::timestamptz IS NULL OR subscribed_at >= $3) AND
+            ($4::timestamptz IS NULL OR subscribed_at < $4) AND
+            ($5::text IS NULL OR $5 = ANY(tags))
+        ORDER BY subscribed_at, id
+        "#,
+        search_pattern,
+        filters.status,
+        filters.subscribed_since,
+        filters.subscribed_before,
+        filters.tag
+    )
+    .fetch(pool);
+
+    let mut buffer = String::new();
+    if let ExportFormat::Csv = format {
+        write_record(&mut buffer, &CSV_HEADER);
+    }
+    while let Some(subscriber) = subscribers.next().await {
+        let subscriber = subscriber.context("Failed to read a subscriber")?;
+        match format {
+            ExportFormat::Csv => subscriber.write_csv(&mut buffer),
+            ExportFormat::Ndjson => subscriber
+                .write_json(&mut buffer)
+                .context("Failed to serialize a subscriber")?,
+        }
+        if buffer.len() >= CHUNK_SIZE {
+            let chunk = std::mem::take(&mut buffer);
+            if sender.send(Ok(chunk.into())).await.is_err() {
+                tracing::info!("The client went away before the export was complete");
+                return Ok(());
+            }
+        }
+    }
+    if !buffer.is_empty() {
+        let _ = sender.send(Ok(buffer.into())).await;
+    }
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use futures_util::StreamExt;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::configuration::{DataRequestSettings, SubscriptionSettings};
+use crate::email_client::EmailSender;
+use crate::lists::target_list;
+use crate::startup::ApplicationBaseUrl;
+use crate::subscriber_import::{ImportError, ImportMode, SubscriberImport};
+use crate::utils::AppError;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ImportParameters {
+    mode: ImportMode,
+    /// The list the subscribers join, the default list if missing.
+    list_id: Option<Uuid>,
+}
+
+/// Import the subscribers in the CSV file sent as the request body, reading it as it arrives.
+/// The file needs an `email` and a `name` column; other columns are ignored.
+#[tracing::instrument(
+    name = "Import subscribers",
+    skip(payload, pool, email_client, base_url, settings, data_request_settings)
+)]
+pub async fn import_subscribers(
+    parameters: web::Query<ImportParameters>,
+    mut payload: web::Payload,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<dyn EmailSender>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+    data_request_settings: web::Data<DataRequestSettings>,
+) -> Result<HttpResponse, AppError> {
+    let list_id = target_list(pool.get_ref(), parameters.list_id)
+        .await?
+        .ok_or_else(|| AppError::BadInputData("No such list".to_string()))?;
+    let mut import = SubscriberImport::new(
+        &pool,
+        email_client.get_ref(),
+        &base_url.0,
+        parameters.mode,
+        list_id,
+        settings.confirmation_token_ttl(),
+        &data_request_settings.hash_key,
+    );
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.context("Failed to read the uploaded file")?;
+        import.push(&chunk).await?;
+    }
+    let report = import.finish().await?;
+
+    tracing::info!(
+        accepted = report.accepted,
+        rejected = report.rejected,
+        "Imported subscribers"
+    );
+    Ok(HttpResponse::Ok().json(report))
+}
+
+impl From<ImportError> for AppError {
+    fn from(e: ImportError) -> Self {
+        match e {
+            ImportError::InvalidFile(message) => AppError::BadInputData(message),
+            ImportError::UnexpectedError(e) => AppError::UnexpectedError(e),
+        }
+    }
+}
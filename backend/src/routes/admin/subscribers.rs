@@ -0,0 +1,559 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool};
+use uuid::Uuid;
+
+use crate::{
+    domain::{SubscriberName, SubscriberTags},
+    lists::{Membership, memberships},
+    utils::AppError,
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(serde::Serialize)]
+pub struct Subscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    confirmed_at: Option<DateTime<Utc>>,
+    unsubscribed_at: Option<DateTime<Utc>>,
+    /// How the subscriber was added: `signup_form` or `import`.
+    source: String,
+    tags: Vec<String>,
+    /// Custom attributes, a JSON object.
+    attributes: serde_json::Value,
+}
+
+#[derive(serde::Serialize)]
+pub struct SubscriberDetails {
+    #[serde(flatten)]
+    subscriber: Subscriber,
+    lists: Vec<Membership>,
+    subscription_tokens: Vec<String>,
+    unsubscribe_token: Option<String>,
+    deliveries: Vec<SubscriberDelivery>,
+}
+
+/// One issue that was delivered, or is being delivered, to the subscriber.
+#[derive(serde::Serialize)]
+pub struct SubscriberDelivery {
+    newsletter_issue_id: Uuid,
+    title: String,
+    outcome: String,
+    n_attempts: i16,
+    last_error: Option<String>,
+    last_attempted_at: DateTime<Utc>,
+    sent_at: Option<DateTime<Utc>>,
+}
+
+/// Narrows down the subscribers to list or export. Every filter is optional.
+#[derive(serde::Deserialize, Debug)]
+pub struct SubscriberFilters {
+    /// Part of the email or the name, ignoring case.
+    pub(super) search: Option<String>,
+    pub(super) status: Option<String>,
+    /// Only subscribers who signed up at or after this time.
+    pub(super) subscribed_since: Option<DateTime<Utc>>,
+    /// Only subscribers who signed up before this time.
+    pub(super) subscribed_before: Option<DateTime<Utc>>,
+    /// Only subscribers with this tag.
+    pub(super) tag: Option<String>,
+}
+
+impl SubscriberFilters {
+    /// The `search` filter as an `ILIKE` pattern, with its wildcards taken literally.
+    pub(super) fn search_pattern(&self) -> Option<String> {
+        self.search.as_ref().map(|search| {
+            let escaped = search
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            format!("%{escaped}%")
+        })
+    }
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct PageParameters {
+    /// Starts at 1.
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+#[derive(serde::Serialize)]
+pub struct SubscriberPage {
+    subscribers: Vec<Subscriber>,
+    page: i64,
+    per_page: i64,
+    /// How many subscribers match the filters, on all pages together.
+    total: i64,
+}
+
+/// The newest subscribers first.
+#[tracing::instrument(name = "List subscribers", skip(pool))]
+pub async fn list_subscribers(
+    filters: web::Query<SubscriberFilters>,
+    page: web::Query<PageParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let page_number = page.page.unwrap_or(1);
+    let per_page = page.per_page.unwrap_or(DEFAULT_PAGE_SIZE);
+    if page_number < 1 {
+        return Err(AppError::BadInputData(
+            "The page number must be at least 1".to_string(),
+        ));
+    }
+    if !(1..=MAX_PAGE_SIZE).contains(&per_page) {
+        return Err(AppError::BadInputData(format!(
+            "The page size must be between 1 and {MAX_PAGE_SIZE}"
+        )));
+    }
+
+    let search_pattern = filters.search_pattern();
+    let subscribers = sqlx::query_as!(
+        Subscriber,
+        r#"
+        SELECT
+            id,
+            email,
+            name,
+            status,
+            subscribed_at,
+            confirmed_at,
+            unsubscribed_at,
+            source,
+            tags,
+            attributes
+        FROM subscriptions
+        WHERE
+            ($1::text IS NULL OR email ILIKE $1 OR name ILIKE $1) AND
+            ($2::text IS NULL OR status = $2) AND
+            ($3::timestamptz IS NULL OR subscribed_at >= $3) AND
+            ($4::timestamptz IS NULL OR subscribed_at < $4) AND
+            ($5::text IS NULL OR $5 = ANY(tags))
+        ORDER BY subscribed_at DESC, id
+        LIMIT $6
+        OFFSET $7
+        "#,
+        search_pattern,
+        filters.status,
+        filters.subscribed_since,
+        filters.subscribed_before,
+        filters.tag,
+        per_page,
+        (page_number - 1).saturating_mul(per_page)
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve subscribers")?;
+
+    let total = sqlx::query!(
+        r#"
+        SELECT count(*) AS "count!"
+        FROM subscriptions
+        WHERE
+            ($1::text IS NULL OR email ILIKE $1 OR name ILIKE $1) AND
+            ($2::text IS NULL OR status = $2) AND
+            ($3::timestamptz IS NULL OR subscribed_at >= $3) AND
+            ($4::timestamptz IS NULL OR subscribed_at < $4) AND
+            ($5::text IS NULL OR $5 = ANY(tags))
+        "#,
+        search_pattern,
+        filters.status,
+        filters.subscribed_since,
+        filters.subscribed_before,
+        filters.tag
+    )
+    .fetch_one(pool.get_ref())
+    .await
+    .context("Failed to count subscribers")?
+    .count;
+
+    Ok(HttpResponse::Ok().json(SubscriberPage {
+        subscribers,
+        page: page_number,
+        per_page,
+        total,
+    }))
+}
+
+#[tracing::instrument(name = "Get subscriber", skip(pool))]
+pub async fn get_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let subscriber = fetch_subscriber(&pool, *subscriber_id).await?;
+
+    let lists = memberships(pool.get_ref(), subscriber.id)
+        .await
+        .context("Failed to retrieve the subscriber's lists")?;
+
+    let subscription_tokens = sqlx::query!(
+        r#"
+        SELECT subscription_token
+        FROM subscription_tokens
+        WHERE subscriber_id = $1
+        "#,
+        subscriber.id
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve subscription tokens")?
+    .into_iter()
+    .map(|row| row.subscription_token)
+    .collect();
+
+    let unsubscribe_token = sqlx::query!(
+        r#"
+        SELECT unsubscribe_token
+        FROM unsubscribe_tokens
+        WHERE subscriber_id = $1
+        "#,
+        subscriber.id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to retrieve unsubscribe token")?
+    .map(|row| row.unsubscribe_token);
+
+    let deliveries = sqlx::query_as!(
+        SubscriberDelivery,
+        r#"
+        SELECT
+            issue_deliveries.newsletter_issue_id,
+            newsletter_issues.title,
+            issue_deliveries.outcome,
+            issue_deliveries.n_attempts,
+            issue_deliveries.last_error,
+            issue_deliveries.last_attempted_at,
+            issue_deliveries.sent_at
+        FROM issue_deliveries
+        JOIN newsletter_issues
+            ON newsletter_issues.newsletter_issue_id = issue_deliveries.newsletter_issue_id
+        WHERE issue_deliveries.subscriber_email = $1
+        ORDER BY issue_deliveries.last_attempted_at DESC
+        "#,
+        subscriber.email
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve the subscriber's deliveries")?;
+
+    Ok(HttpResponse::Ok().json(SubscriberDetails {
+        subscriber,
+        lists,
+        subscription_tokens,
+        unsubscribe_token,
+        deliveries,
+    }))
+}
+
+#[derive(serde::Deserialize)]
+pub struct UpdateSubscriberData {
+    name: String,
+}
+
+#[tracing::instrument(name = "Update subscriber", skip(form, pool))]
+pub async fn update_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    form: web::Json<UpdateSubscriberData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let name = SubscriberName::parse(form.0.name).map_err(AppError::BadInputData)?;
+
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"
+        UPDATE subscriptions
+        SET name = $2
+        WHERE id = $1
+        RETURNING
+            id,
+            email,
+            name,
+            status,
+            subscribed_at,
+            confirmed_at,
+            unsubscribed_at,
+            source,
+            tags,
+            attributes
+        "#,
+        *subscriber_id,
+        name.as_ref()
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to update subscriber")?
+    .ok_or_else(subscriber_not_found)?;
+
+    Ok(HttpResponse::Ok().json(subscriber))
+}
+
+#[derive(serde::Deserialize)]
+pub struct SubscriberTagsData {
+    tags: Vec<String>,
+}
+
+/// Replace the subscriber's tags.
+#[tracing::instrument(name = "Set subscriber tags", skip(form, pool))]
+pub async fn set_subscriber_tags(
+    subscriber_id: web::Path<Uuid>,
+    form: web::Json<SubscriberTagsData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let tags = SubscriberTags::parse(form.0.tags).map_err(AppError::BadInputData)?;
+
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"
+        UPDATE subscriptions
+        SET tags = $2
+        WHERE id = $1
+        RETURNING
+            id,
+            email,
+            name,
+            status,
+            subscribed_at,
+            confirmed_at,
+            unsubscribed_at,
+            source,
+            tags,
+            attributes
+        "#,
+        *subscriber_id,
+        tags.as_ref()
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to update the subscriber's tags")?
+    .ok_or_else(subscriber_not_found)?;
+
+    Ok(HttpResponse::Ok().json(subscriber))
+}
+
+#[derive(serde::Deserialize)]
+pub struct SubscriberAttributesData {
+    attributes: serde_json::Value,
+}
+
+/// Replace the subscriber's custom attributes with a JSON object.
+#[tracing::instrument(name = "Set subscriber attributes", skip(form, pool))]
+pub async fn set_subscriber_attributes(
+    subscriber_id: web::Path<Uuid>,
+    form: web::Json<SubscriberAttributesData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let attributes = form.0.attributes;
+    match attributes.as_object() {
+        None => {
+            return Err(AppError::BadInputData(
+                "The attributes must be a JSON object".to_string(),
+            ));
+        }
+        Some(attributes) if attributes.keys().any(|key| key.trim().is_empty()) => {
+            return Err(AppError::BadInputData(
+                "Attribute names can't be empty".to_string(),
+            ));
+        }
+        Some(_) => {}
+    }
+
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"
+        UPDATE subscriptions
+        SET attributes = $2
+        WHERE id = $1
+        RETURNING
+            id,
+            email,
+            name,
+            status,
+            subscribed_at,
+            confirmed_at,
+            unsubscribed_at,
+            source,
+            tags,
+            attributes
+        "#,
+        *subscriber_id,
+        attributes
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to update the subscriber's attributes")?
+    .ok_or_else(subscriber_not_found)?;
+
+    Ok(HttpResponse::Ok().json(subscriber))
+}
+
+/// Confirm a pending subscription, and the lists it is pending for, on the subscriber's behalf,
+/// e.g. when they couldn't find the confirmation email. Subscribers who left or were suppressed
+/// can't be confirmed this way.
+#[tracing::instrument(name = "Manually confirm subscriber", skip(pool))]
+pub async fn manually_confirm_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to start a transaction")?;
+    // Locked so that the subscriber can't unsubscribe or be suppressed until this commits
+    let status = sqlx::query!(
+        "SELECT status FROM subscriptions WHERE id = $1 FOR UPDATE",
+        *subscriber_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to look up the subscriber")?
+    .ok_or_else(subscriber_not_found)?
+    .status;
+    match status.as_str() {
+        "confirmed" | "pending_confirmation" => {}
+        status => {
+            return Err(AppError::Conflict(format!(
+                "A subscriber with status '{status}' can't be confirmed"
+            )));
+        }
+    }
+
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            UPDATE subscriptions
+            SET status = 'confirmed', confirmed_at = now()
+            WHERE id = $1 AND status = 'pending_confirmation'
+            "#,
+            *subscriber_id
+        ))
+        .await
+        .context("Failed to confirm subscriber")?;
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            UPDATE list_memberships
+            SET status = 'confirmed', confirmed_at = now()
+            WHERE
+                subscriber_id = $1 AND
+                status = 'pending_confirmation' AND
+                EXISTS (SELECT 1 FROM subscriptions WHERE id = $1 AND status = 'confirmed')
+            "#,
+            *subscriber_id
+        ))
+        .await
+        .context("Failed to confirm the subscriber's lists")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the transaction")?;
+
+    let subscriber = fetch_subscriber(&pool, *subscriber_id).await?;
+    Ok(HttpResponse::Ok().json(subscriber))
+}
+
+/// Delete the subscriber and the issues still queued for them. Their delivery history is kept.
+#[tracing::instrument(name = "Delete subscriber", skip(pool))]
+pub async fn delete_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to start a transaction")?;
+
+    transaction
+        .execute(sqlx::query!(
+            "DELETE FROM subscription_tokens WHERE subscriber_id = $1",
+            *subscriber_id
+        ))
+        .await
+        .context("Failed to delete subscription tokens")?;
+    transaction
+        .execute(sqlx::query!(
+            "DELETE FROM unsubscribe_tokens WHERE subscriber_id = $1",
+            *subscriber_id
+        ))
+        .await
+        .context("Failed to delete unsubscribe token")?;
+    transaction
+        .execute(sqlx::query!(
+            "DELETE FROM email_change_tokens WHERE subscriber_id = $1",
+            *subscriber_id
+        ))
+        .await
+        .context("Failed to delete email change tokens")?;
+    transaction
+        .execute(sqlx::query!(
+            "DELETE FROM list_memberships WHERE subscriber_id = $1",
+            *subscriber_id
+        ))
+        .await
+        .context("Failed to delete list memberships")?;
+    transaction
+        .execute(sqlx::query!(
+            r#"
+            DELETE FROM issue_delivery_queue
+            WHERE subscriber_email = (SELECT email FROM subscriptions WHERE id = $1)
+            "#,
+            *subscriber_id
+        ))
+        .await
+        .context("Failed to delete queued deliveries")?;
+    let deleted = transaction
+        .execute(sqlx::query!(
+            "DELETE FROM subscriptions WHERE id = $1",
+            *subscriber_id
+        ))
+        .await
+        .context("Failed to delete subscriber")?
+        .rows_affected();
+
+    if deleted == 0 {
+        return Err(subscriber_not_found());
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the deletion")?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+async fn fetch_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<Subscriber, AppError> {
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"
+        SELECT
+            id,
+            email,
+            name,
+            status,
+            subscribed_at,
+            confirmed_at,
+            unsubscribed_at,
+            source,
+            tags,
+            attributes
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve subscriber")?
+    .ok_or_else(subscriber_not_found)?;
+    Ok(subscriber)
+}
+
+fn subscriber_not_found() -> AppError {
+    AppError::NotFound("No such subscriber".to_string())
+}
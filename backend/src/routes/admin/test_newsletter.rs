@@ -0,0 +1,74 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+
+use crate::{
+    domain::{NewsletterIssue, SubscriberEmail},
+    email_client::EmailSender,
+    startup::{ApplicationBaseUrl, TestSubjectPrefix},
+    utils::AppError,
+};
+
+/// Test issues are sent synchronously, so keep the list short.
+const MAX_TEST_RECIPIENTS: usize = 10;
+
+/// The same payload as `PublishNewsletterData`, plus who should receive the test issue.
+/// Fields that only matter when publishing, like `idempotency_key`, are ignored.
+#[derive(serde::Deserialize)]
+pub struct SendTestNewsletterData {
+    title: String,
+    content_text: String,
+    content_html: String,
+    recipients: Vec<String>,
+}
+
+/// Send the issue to a few reviewers right away. Nothing is stored and no subscriber is emailed.
+#[tracing::instrument(
+    name = "Send test newsletter",
+    skip(form, email_client, base_url, subject_prefix)
+)]
+pub async fn send_test_newsletter(
+    form: web::Json<SendTestNewsletterData>,
+    email_client: web::Data<dyn EmailSender>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    subject_prefix: web::Data<TestSubjectPrefix>,
+) -> Result<HttpResponse, AppError> {
+    let SendTestNewsletterData {
+        title,
+        content_text,
+        content_html,
+        recipients,
+    } = form.0;
+
+    if recipients.is_empty() || recipients.len() > MAX_TEST_RECIPIENTS {
+        return Err(AppError::BadInputData(format!(
+            "Provide between 1 and {MAX_TEST_RECIPIENTS} recipients"
+        )));
+    }
+    let recipients = recipients
+        .into_iter()
+        .map(SubscriberEmail::parse)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(AppError::BadInputData)?;
+
+    let issue = NewsletterIssue {
+        title: format!("{} {title}", subject_prefix.0),
+        content_text,
+        content_html,
+    };
+    issue.validate()?;
+
+    for recipient in &recipients {
+        let issue = issue.clone().preview(&base_url.0, recipient.as_ref())?;
+        email_client
+            .send_email(
+                recipient,
+                &issue.title,
+                &issue.content_html,
+                &issue.content_text,
+            )
+            .await
+            .with_context(|| format!("Failed to send test issue to {recipient}"))?;
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
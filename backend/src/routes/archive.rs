@@ -0,0 +1,277 @@
+//! The public archive of published issues, as JSON for the frontend and as server-rendered
+//! pages that can be shared and indexed.
+
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use minijinja::{Environment, Value, context};
+use sqlx::PgPool;
+
+use crate::domain::NewsletterIssue;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::AppError;
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ArchivePageParameters {
+    /// Starts at 1.
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+#[derive(serde::Serialize)]
+pub struct ArchivedIssueSummary {
+    slug: String,
+    title: String,
+    published_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+pub struct ArchivePage {
+    issues: Vec<ArchivedIssueSummary>,
+    page: i64,
+    per_page: i64,
+    /// How many issues are in the archive, on all pages together.
+    total: i64,
+}
+
+#[derive(serde::Serialize)]
+pub struct ArchivedIssue {
+    slug: String,
+    title: String,
+    published_at: DateTime<Utc>,
+    content_text: String,
+    content_html: String,
+}
+
+/// The published issues, newest first.
+#[tracing::instrument(name = "List archived issues", skip(pool))]
+pub async fn archived_issues(
+    parameters: web::Query<ArchivePageParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let page = fetch_archive_page(&pool, &parameters).await?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+#[tracing::instrument(name = "Get archived issue", skip(pool, base_url))]
+pub async fn archived_issue(
+    slug: web::Path<String>,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, AppError> {
+    let issue = fetch_archived_issue(&pool, &slug, &base_url.0).await?;
+    Ok(HttpResponse::Ok().json(issue))
+}
+
+#[tracing::instrument(name = "Render the archive page", skip(pool, base_url))]
+pub async fn archive_page(
+    parameters: web::Query<ArchivePageParameters>,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, AppError> {
+    let page = fetch_archive_page(&pool, &parameters).await?;
+    let issues: Vec<_> = page
+        .issues
+        .iter()
+        .map(|issue| {
+            context! {
+                slug => issue.slug,
+                title => issue.title,
+                published_at => issue.published_at.to_rfc3339(),
+                published_on => published_on(issue.published_at),
+            }
+        })
+        .collect();
+    render_page(
+        "index.html",
+        context! {
+            base_url => page_base_url(&base_url),
+            issues => issues,
+            page => page.page,
+            has_next_page => page.page * page.per_page < page.total,
+        },
+    )
+}
+
+#[tracing::instrument(name = "Render an archived issue page", skip(pool, base_url))]
+pub async fn archived_issue_page(
+    slug: web::Path<String>,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, AppError> {
+    let issue = fetch_archived_issue(&pool, &slug, &base_url.0).await?;
+    render_page(
+        "issue.html",
+        context! {
+            base_url => page_base_url(&base_url),
+            issue => context! {
+                slug => issue.slug,
+                title => issue.title,
+                published_at => issue.published_at.to_rfc3339(),
+                published_on => published_on(issue.published_at),
+                content_html => issue.content_html,
+            },
+        },
+    )
+}
+
+async fn fetch_archive_page(
+    pool: &PgPool,
+    parameters: &ArchivePageParameters,
+) -> Result<ArchivePage, AppError> {
+    let page = parameters.page.unwrap_or(1);
+    let per_page = parameters.per_page.unwrap_or(DEFAULT_PAGE_SIZE);
+    if page < 1 {
+        return Err(AppError::BadInputData(
+            "The page number must be at least 1".to_string(),
+        ));
+    }
+    if !(1..=MAX_PAGE_SIZE).contains(&per_page) {
+        return Err(AppError::BadInputData(format!(
+            "The page size must be between 1 and {MAX_PAGE_SIZE}"
+        )));
+    }
+
+    let issues = sqlx::query_as!(
+        ArchivedIssueSummary,
+        r#"
+        SELECT slug, title, published_at AS "published_at!"
+        FROM newsletter_issues
+        WHERE
+            published_at IS NOT NULL AND
+            status <> 'cancelled' AND
+            NOT exclude_from_archive
+        ORDER BY published_at DESC, newsletter_issue_id
+        LIMIT $1
+        OFFSET $2
+        "#,
+        per_page,
+        (page - 1).saturating_mul(per_page)
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve archived issues")?;
+
+    let total = sqlx::query!(
+        r#"
+        SELECT count(*) AS "count!"
+        FROM newsletter_issues
+        WHERE
+            published_at IS NOT NULL AND
+            status <> 'cancelled' AND
+            NOT exclude_from_archive
+        "#
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count archived issues")?
+    .count;
+
+    Ok(ArchivePage {
+        issues,
+        page,
+        per_page,
+        total,
+    })
+}
+
+/// The issue with `slug`, rendered for the archive. Issues that are scheduled, cancelled or
+/// excluded from the archive are not found.
+async fn fetch_archived_issue(
+    pool: &PgPool,
+    slug: &str,
+    base_url: &str,
+) -> Result<ArchivedIssue, AppError> {
+    let row = sqlx::query!(
+        r#"
+        SELECT slug, title, published_at AS "published_at!", content_text, content_html
+        FROM newsletter_issues
+        WHERE
+            slug = $1 AND
+            published_at IS NOT NULL AND
+            status <> 'cancelled' AND
+            NOT exclude_from_archive
+        "#,
+        slug
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve archived issue")?
+    .ok_or_else(|| AppError::NotFound("No such issue in the archive".to_string()))?;
+
+    let issue = render_archived_issue(
+        NewsletterIssue {
+            title: row.title,
+            content_text: row.content_text,
+            content_html: row.content_html,
+        },
+        &row.slug,
+        base_url,
+    );
+
+    Ok(ArchivedIssue {
+        slug: row.slug,
+        title: issue.title,
+        published_at: row.published_at,
+        content_text: issue.content_text,
+        content_html: issue.content_html,
+    })
+}
+
+/// Render `issue` for the archive, or show it as it was written if it doesn't render. Issues
+/// stored before issues were templates can contain a `{{` that was never meant as a placeholder,
+/// and one of them shouldn't take down its page or the feeds.
+pub(crate) fn render_archived_issue(
+    issue: NewsletterIssue,
+    slug: &str,
+    base_url: &str,
+) -> NewsletterIssue {
+    issue
+        .clone()
+        .render_for_archive(base_url)
+        .unwrap_or_else(|e| {
+            tracing::warn!(
+                error = %e,
+                slug,
+                "Showing an archived issue as it was written, since it doesn't render"
+            );
+            issue
+        })
+}
+
+/// The base URL comes from our configuration, so it doesn't need escaping to be put in an `href`.
+fn page_base_url(base_url: &ApplicationBaseUrl) -> Value {
+    Value::from_safe_string(base_url.0.clone())
+}
+
+fn render_page(name: &str, ctx: Value) -> Result<HttpResponse, AppError> {
+    let body = render_template(name, ctx).context("Failed to render the archive page")?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(body))
+}
+
+fn render_template(name: &str, ctx: Value) -> Result<String, minijinja::Error> {
+    let mut env = Environment::new();
+    env.add_template(
+        "base.html",
+        include_str!("../../templates/archive/base.html"),
+    )?;
+    env.add_template(
+        "index.html",
+        include_str!("../../templates/archive/index.html"),
+    )?;
+    env.add_template(
+        "issue.html",
+        include_str!("../../templates/archive/issue.html"),
+    )?;
+    env.get_template(name)?.render(ctx)
+}
+
+/// The publication date as shown to readers, e.g. "October 18, 2026".
+fn published_on(published_at: DateTime<Utc>) -> String {
+    published_at.format("%B %-d, %Y").to_string()
+}
@@ -0,0 +1,168 @@
+//! RSS and Atom feeds of the latest issues in the archive.
+
+use std::time::SystemTime;
+
+use actix_web::http::header::{
+    self, EntityTag, Header, HttpDate, IfModifiedSince, IfNoneMatch, LastModified,
+};
+use actix_web::{HttpRequest, HttpResponse, web};
+use anyhow::Context;
+use chrono::SubsecRound;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use crate::configuration::FeedSettings;
+use crate::domain::NewsletterIssue;
+use crate::feeds::{FeedChannel, FeedEntry, atom, rss};
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::AppError;
+
+use super::render_archived_issue;
+
+const FEED_TITLE: &str = "Newsletter";
+
+#[tracing::instrument(name = "Get the RSS feed", skip_all)]
+pub async fn rss_feed(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<FeedSettings>,
+) -> Result<HttpResponse, AppError> {
+    let entries = fetch_feed_entries(&pool, &base_url.0, settings.item_limit).await?;
+    let channel = FeedChannel {
+        title: FEED_TITLE,
+        link: &format!("{}/archive", base_url.0),
+        self_link: &format!("{}/feed.rss", base_url.0),
+    };
+    let body = rss(&channel, &entries);
+    Ok(feed_response(
+        &request,
+        "application/rss+xml; charset=utf-8",
+        body,
+        &entries,
+    ))
+}
+
+#[tracing::instrument(name = "Get the Atom feed", skip_all)]
+pub async fn atom_feed(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<FeedSettings>,
+) -> Result<HttpResponse, AppError> {
+    let entries = fetch_feed_entries(&pool, &base_url.0, settings.item_limit).await?;
+    let channel = FeedChannel {
+        title: FEED_TITLE,
+        link: &format!("{}/archive", base_url.0),
+        self_link: &format!("{}/feed.atom", base_url.0),
+    };
+    let body = atom(&channel, &entries);
+    Ok(feed_response(
+        &request,
+        "application/atom+xml; charset=utf-8",
+        body,
+        &entries,
+    ))
+}
+
+/// The latest issues in the archive, newest first, rendered like their archive pages.
+async fn fetch_feed_entries(
+    pool: &PgPool,
+    base_url: &str,
+    item_limit: i64,
+) -> Result<Vec<FeedEntry>, AppError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT slug, title, published_at AS "published_at!", content_text, content_html
+        FROM newsletter_issues
+        WHERE
+            published_at IS NOT NULL AND
+            status <> 'cancelled' AND
+            NOT exclude_from_archive
+        ORDER BY published_at DESC, newsletter_issue_id
+        LIMIT $1
+        "#,
+        item_limit
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the issues for the feed")?;
+
+    let entries = rows
+        .into_iter()
+        .map(|row| {
+            let issue = render_archived_issue(
+                NewsletterIssue {
+                    title: row.title,
+                    content_text: row.content_text,
+                    content_html: row.content_html,
+                },
+                &row.slug,
+                base_url,
+            );
+            FeedEntry {
+                title: issue.title,
+                permalink: format!("{base_url}/archive/{}", row.slug),
+                published_at: row.published_at,
+                content_html: issue.content_html,
+            }
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// Answer with the feed, or with a 304 if the reader already has it.
+///
+/// The ETag is a hash of the feed, so it changes whenever an issue is added, taken out of the
+/// archive or edited. `Last-Modified` is when the newest issue was published, which misses the
+/// latter two, so per RFC 9110 `If-Modified-Since` is only looked at without `If-None-Match`.
+fn feed_response(
+    request: &HttpRequest,
+    content_type: &str,
+    body: String,
+    entries: &[FeedEntry],
+) -> HttpResponse {
+    let etag = EntityTag::new_strong(body_hash(&body));
+    // HTTP dates have no fractions of a second, so neither can what we compare them with
+    let last_modified = entries
+        .first()
+        .map(|entry| HttpDate::from(SystemTime::from(entry.published_at.trunc_subsecs(0))));
+
+    let not_modified = if request.headers().contains_key(header::IF_NONE_MATCH) {
+        match IfNoneMatch::parse(request) {
+            Ok(IfNoneMatch::Any) => true,
+            Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+            Err(_) => false,
+        }
+    } else {
+        match (IfModifiedSince::parse(request), last_modified) {
+            (Ok(IfModifiedSince(since)), Some(last_modified)) => {
+                SystemTime::from(last_modified) <= SystemTime::from(since)
+            }
+            _ => false,
+        }
+    };
+
+    let mut response = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    response.insert_header(header::ETag(etag));
+    if let Some(last_modified) = last_modified {
+        response.insert_header(LastModified(last_modified));
+    }
+    if not_modified {
+        response.finish()
+    } else {
+        response
+            .insert_header((header::CONTENT_TYPE, content_type))
+            .body(body)
+    }
+}
+
+/// The same body has the same ETag across releases, so readers don't download it again after
+/// an upgrade.
+fn body_hash(body: &str) -> String {
+    hex::encode(Sha256::digest(body.as_bytes()))
+}
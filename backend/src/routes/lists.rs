@@ -0,0 +1,33 @@
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::utils::AppError;
+
+/// A list as shown to people signing up.
+#[derive(serde::Serialize)]
+pub struct PublicList {
+    list_id: Uuid,
+    name: String,
+    description: String,
+    is_default: bool,
+}
+
+/// The lists that can be joined, for the `list_id` of the subscription form.
+#[tracing::instrument(name = "List public lists", skip(pool))]
+pub async fn public_lists(pool: web::Data<PgPool>) -> Result<HttpResponse, AppError> {
+    let lists = sqlx::query_as!(
+        PublicList,
+        r#"
+        SELECT list_id, name, description, is_default
+        FROM lists
+        ORDER BY is_default DESC, name
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve lists")?;
+
+    Ok(HttpResponse::Ok().json(lists))
+}
@@ -1,11 +1,23 @@
 mod admin;
+mod archive;
+mod feeds;
 mod health_check;
+mod lists;
 mod login;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_preferences;
+mod subscriptions_unsubscribe;
+mod webhooks;
 
 pub use admin::*;
+pub use archive::*;
+pub use feeds::*;
 pub use health_check::*;
+pub use lists::*;
 pub use login::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_preferences::*;
+pub use subscriptions_unsubscribe::*;
+pub use webhooks::*;
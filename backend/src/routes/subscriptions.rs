@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, web};
 use chrono::Utc;
@@ -6,9 +8,12 @@ use rand::{Rng, thread_rng};
 use sqlx::{Executor, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
+use crate::configuration::{DataRequestSettings, SubscriptionSettings};
 use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
-use crate::email_client::EmailClient;
+use crate::email_client::{EmailError, EmailSender, OutgoingEmail};
+use crate::lists::{join_list, target_list};
 use crate::startup::ApplicationBaseUrl;
+use crate::suppressions::is_suppressed;
 use crate::utils::AppError;
 
 // TODO: Extract to a shared library that frontend and backend both use.
@@ -16,11 +21,13 @@ use crate::utils::AppError;
 pub struct SubscribeFormData {
     email: String,
     name: String,
+    /// The list to join, the default list if missing.
+    list_id: Option<Uuid>,
 }
 
 #[tracing::instrument(
     name = "Adding a new subscriber.",
-    skip(form, pool, email_client, base_url),
+    skip(form, pool, email_client, base_url, settings, data_request_settings),
     fields(
         subscriber_email = %form.email,
         subscriber_name = %form.name
@@ -29,28 +36,85 @@ pub struct SubscribeFormData {
 pub async fn subscribe(
     form: web::Json<SubscribeFormData>,
     pool: web::Data<PgPool>,
-    email_client: web::Data<EmailClient>,
+    email_client: web::Data<dyn EmailSender>,
     base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+    data_request_settings: web::Data<DataRequestSettings>,
 ) -> Result<HttpResponse, AppError> {
-    let new_subscriber = form.0.try_into().map_err(AppError::BadInputData)?;
+    let list_id = form.list_id;
+    let new_subscriber: NewSubscriber = form.0.try_into().map_err(AppError::BadInputData)?;
+    let list_id = target_list(pool.get_ref(), list_id)
+        .await?
+        .ok_or_else(|| AppError::BadInputData("No such list".to_string()))?;
 
-    // TODO: Should we check within the context of a transaction in order to get transaction isolation?
-    // TODO: What if multiple 'subscribe' requests come in simultaneously for the same user?
-    if already_subscribed(&new_subscriber, &pool).await? {
+    // Don't tell the caller, so that the endpoint doesn't reveal which addresses bounced
+    if is_suppressed(
+        pool.get_ref(),
+        new_subscriber.email.as_ref(),
+        &data_request_settings.hash_key,
+    )
+    .await?
+    {
+        tracing::info!("The address is suppressed, so no confirmation email is sent");
         return Ok(HttpResponse::new(StatusCode::OK));
     }
 
     let mut transaction = pool.begin().await?;
 
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
-        .await
-        .map_err(AppError::DatabaseError)?;
+    let existing_subscriber = lock_existing_subscriber(
+        &mut transaction,
+        &new_subscriber,
+        list_id,
+        settings.resend_confirmation_interval(),
+    )
+    .await?;
+    // TODO: What if multiple 'subscribe' requests come in simultaneously for a new user?
+    let subscriber_id = match existing_subscriber {
+        None => {
+            let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+                .await
+                .map_err(AppError::DatabaseError)?;
+            let unsubscribe_token = generate_subscription_token();
+            store_unsubscribe_token(&mut transaction, subscriber_id, &unsubscribe_token).await?;
+            subscriber_id
+        }
+        // Someone who lost the first email can only confirm if we send them another one, and a
+        // subscriber joining another list confirms it like they confirmed the first one
+        Some(subscriber)
+            if subscriber.status == "pending_confirmation" || subscriber.status == "confirmed" =>
+        {
+            if subscriber.list_status.as_deref() == Some("confirmed") {
+                return Ok(HttpResponse::new(StatusCode::OK));
+            }
+            if subscriber.recently_sent {
+                tracing::info!("A confirmation email was sent recently, so no other one is sent");
+                return Ok(HttpResponse::new(StatusCode::OK));
+            }
+            subscriber.id
+        }
+        // Someone who unsubscribed signs up again like a new subscriber, for this list only
+        Some(subscriber) if subscriber.status == "unsubscribed" => {
+            resubscribe(&mut transaction, subscriber.id).await?;
+            subscriber.id
+        }
+        // Addresses that bounced or complained are suppressed, so this is only reached if their
+        // suppression was lifted; they are left for an admin to sort out
+        Some(_) => return Ok(HttpResponse::new(StatusCode::OK)),
+    };
+    join_list(&mut *transaction, subscriber_id, list_id).await?;
 
     let subscription_token = generate_subscription_token();
-    store_token(&mut transaction, subscriber_id, &subscription_token).await?;
+    store_token(
+        &mut transaction,
+        subscriber_id,
+        list_id,
+        &subscription_token,
+        settings.confirmation_token_ttl(),
+    )
+    .await?;
 
     send_confirmation_email(
-        &email_client,
+        email_client.get_ref(),
         new_subscriber,
         &base_url.0,
         &subscription_token,
@@ -77,16 +141,49 @@ impl TryFrom<SubscribeFormData> for NewSubscriber {
     name = "Store subscription token in the database",
     skip(subscription_token, transaction)
 )]
-async fn store_token(
+pub(crate) async fn store_token(
     transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
+    list_id: Uuid,
     subscription_token: &str,
+    ttl: Duration,
 ) -> Result<(), sqlx::Error> {
     let query = sqlx::query!(
-        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id)
-        VALUES ($1, $2)"#,
+        r#"
+        INSERT INTO subscription_tokens (
+            subscription_token,
+            subscriber_id,
+            list_id,
+            created_at,
+            expires_at
+        )
+        VALUES ($1, $2, $3, now(), now() + make_interval(secs => $4))
+        "#,
         subscription_token,
         subscriber_id,
+        list_id,
+        ttl.as_secs_f64(),
+    );
+
+    transaction.execute(query).await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "Store unsubscribe token in the database",
+    skip(unsubscribe_token, transaction)
+)]
+pub(crate) async fn store_unsubscribe_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    unsubscribe_token: &str,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"INSERT INTO unsubscribe_tokens (unsubscribe_token, subscriber_id)
+        VALUES ($1, $2)"#,
+        unsubscribe_token,
+        subscriber_id,
     );
 
     transaction.execute(query).await?;
@@ -99,47 +196,130 @@ async fn store_token(
     skip(email_client, new_subscriber, base_url, subscription_token)
 )]
 async fn send_confirmation_email(
-    email_client: &EmailClient,
+    email_client: &dyn EmailSender,
     new_subscriber: NewSubscriber,
     base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), EmailError> {
+    let email = confirmation_email(new_subscriber.email, base_url, subscription_token);
+    email_client
+        .send_email(
+            &email.recipient,
+            &email.subject,
+            &email.html_content,
+            &email.text_content,
+        )
+        .await?;
+    Ok(())
+}
+
+/// The email asking a new subscriber to confirm their subscription.
+pub(crate) fn confirmation_email(
+    recipient: SubscriberEmail,
+    base_url: &str,
+    subscription_token: &str,
+) -> OutgoingEmail {
     let confirmation_link =
         format!("{base_url}/api/subscriptions/confirm?subscription_token={subscription_token}");
 
-    let html_body = format!(
+    let html_content = format!(
         "Welcome to our newsletter!<br />\
         Click <a href=\"{confirmation_link}\">here</a> to confirm your subscription."
     );
 
-    let plain_body = format!(
+    let text_content = format!(
         "Welcome to our newsletter!\nVisit {confirmation_link} to confirm your subscription."
     );
 
-    email_client
-        .send_email(&new_subscriber.email, "Welcome!", &html_body, &plain_body)
-        .await
+    OutgoingEmail {
+        recipient,
+        subject: "Welcome!".to_string(),
+        html_content,
+        text_content,
+        headers: Vec::new(),
+    }
+}
+
+struct ExistingSubscriber {
+    id: Uuid,
+    status: String,
+    /// The status of their membership of the list they are joining, if they joined it before.
+    list_status: Option<String>,
+    /// Whether a confirmation email for the list was sent within the resend interval.
+    recently_sent: bool,
 }
 
+/// Look up the subscriber with the same address, locking them until `transaction` ends so that
+/// concurrent signups don't both send a confirmation email.
 #[tracing::instrument(
     name = "Checking whether subscriber already exists",
-    skip(new_subscriber, pool)
+    skip(new_subscriber, transaction)
 )]
-async fn already_subscribed(
+async fn lock_existing_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
     new_subscriber: &NewSubscriber,
-    pool: &PgPool,
-) -> Result<bool, sqlx::Error> {
-    Ok(sqlx::query!(
+    list_id: Uuid,
+    resend_interval: Duration,
+) -> Result<Option<ExistingSubscriber>, sqlx::Error> {
+    sqlx::query_as!(
+        ExistingSubscriber,
         r#"
-        SELECT id
+        SELECT
+            id,
+            status,
+            (
+                SELECT list_memberships.status
+                FROM list_memberships
+                WHERE
+                    list_memberships.subscriber_id = subscriptions.id AND
+                    list_memberships.list_id = $2
+            ) AS list_status,
+            EXISTS (
+                SELECT 1
+                FROM subscription_tokens
+                WHERE
+                    subscription_tokens.subscriber_id = subscriptions.id AND
+                    subscription_tokens.list_id = $2 AND
+                    subscription_tokens.created_at > now() - make_interval(secs => $3)
+            ) AS "recently_sent!"
         FROM subscriptions
-        WHERE email = $1
+        WHERE lower(email) = lower($1)
+        FOR UPDATE
         "#,
-        new_subscriber.email.as_ref()
+        new_subscriber.email.as_ref(),
+        list_id,
+        resend_interval.as_secs_f64()
     )
-    .fetch_optional(pool)
-    .await?
-    .is_some())
+    .fetch_optional(&mut **transaction)
+    .await
+}
+
+/// Put a subscriber who unsubscribed back to pending their confirmation. They left every list
+/// when they unsubscribed, so confirming brings back only the list they are joining now.
+#[tracing::instrument(name = "Resubscribing a subscriber", skip(transaction))]
+async fn resubscribe(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'pending_confirmation', confirmed_at = NULL, unsubscribed_at = NULL
+        WHERE id = $1
+        "#,
+        subscriber_id
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"
+        UPDATE list_memberships
+        SET status = 'unsubscribed'
+        WHERE subscriber_id = $1 AND status <> 'unsubscribed'
+        "#,
+        subscriber_id
+    );
+    transaction.execute(query).await?;
+    Ok(())
 }
 
 #[tracing::instrument(
@@ -154,8 +334,8 @@ async fn insert_subscriber(
 
     let query = sqlx::query!(
         r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending_confirmation')
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, source)
+        VALUES ($1, $2, $3, $4, 'pending_confirmation', 'signup_form')
         "#,
         subscriber_id,
         new_subscriber.email.as_ref(),
@@ -169,7 +349,7 @@ async fn insert_subscriber(
 }
 
 /// Generate a random 25-character-long case-sensitive subscription token
-fn generate_subscription_token() -> String {
+pub(crate) fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
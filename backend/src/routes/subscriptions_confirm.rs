@@ -1,9 +1,11 @@
-use actix_web::{HttpResponse, web};
+use actix_web::{HttpResponse, HttpResponseBuilder, ResponseError, web};
 use actix_web_flash_messages::FlashMessage;
-use sqlx::PgPool;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool};
 use uuid::Uuid;
 
-use crate::utils::AppError;
+use crate::utils::error_chain_fmt;
 
 #[derive(serde::Deserialize)]
 pub struct Parameters {
@@ -14,14 +16,25 @@ pub struct Parameters {
 pub async fn confirm(
     parameters: web::Query<Parameters>,
     pool: web::Data<PgPool>,
-) -> Result<HttpResponse, AppError> {
-    let id = get_subscriber_id_from_token(&pool, &parameters.subscription_token).await?;
+) -> Result<HttpResponse, ConfirmError> {
+    let token = get_subscription_token(&pool, &parameters.subscription_token)
+        .await
+        .context("Failed to look up the subscription token")?;
 
-    let Some(subscriber_id) = id else {
-        return Err(anyhow::anyhow!("Invalid subscriber token").into());
+    let Some(token) = token else {
+        return Err(ConfirmError::InvalidToken);
     };
+    if token.expires_at <= Utc::now() {
+        return Err(ConfirmError::ExpiredToken);
+    }
 
-    confirm_subscriber(&pool, subscriber_id).await?;
+    let confirmed = confirm_subscriber(&pool, token.subscriber_id, token.list_id)
+        .await
+        .context("Failed to confirm the subscriber")?;
+    if !confirmed {
+        // An old link must not bring back someone who unsubscribed, bounced or complained
+        return Err(ConfirmError::InvalidToken);
+    }
 
     FlashMessage::info(
         "You have confirmed your newsletter subscription. Stay tuned for exciting newsletters!",
@@ -30,30 +43,119 @@ pub async fn confirm(
     Ok(HttpResponse::Ok().finish())
 }
 
+/// Mark the subscriber's address and their membership of the list as confirmed. Returns `false`,
+/// and changes nothing, if the subscriber left or was suppressed in the meantime.
 #[tracing::instrument(name = "Mark subscriber as confirmed", skip(subscriber_id, pool))]
-pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
+pub async fn confirm_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let query = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET
+            status = 'confirmed',
+            -- Keep the original time when the link is followed again
+            confirmed_at = CASE WHEN status = 'confirmed' THEN confirmed_at ELSE now() END
+        WHERE id = $1 AND status IN ('pending_confirmation', 'confirmed')
+        "#,
         subscriber_id
-    )
-    .execute(pool)
-    .await?;
+    );
+    if transaction.execute(query).await?.rows_affected() == 0 {
+        return Ok(false);
+    }
+    let query = sqlx::query!(
+        r#"
+        UPDATE list_memberships
+        SET
+            status = 'confirmed',
+            confirmed_at = CASE WHEN status = 'confirmed' THEN confirmed_at ELSE now() END
+        WHERE subscriber_id = $1 AND list_id = $2
+        "#,
+        subscriber_id,
+        list_id
+    );
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+
+    Ok(true)
+}
 
-    Ok(())
+pub struct SubscriptionToken {
+    pub subscriber_id: Uuid,
+    pub list_id: Uuid,
+    pub expires_at: DateTime<Utc>,
 }
 
-#[tracing::instrument(name = "Get subscriber_id from token", skip(subscription_token, pool))]
-pub async fn get_subscriber_id_from_token(
+#[tracing::instrument(name = "Get subscription token", skip(subscription_token, pool))]
+pub async fn get_subscription_token(
     pool: &PgPool,
     subscription_token: &str,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let result = sqlx::query!(
-        r#"SELECT subscriber_id FROM subscription_tokens
+) -> Result<Option<SubscriptionToken>, sqlx::Error> {
+    sqlx::query_as!(
+        SubscriptionToken,
+        r#"SELECT subscriber_id, list_id, expires_at FROM subscription_tokens
         WHERE subscription_token = $1"#,
         subscription_token
     )
     .fetch_optional(pool)
-    .await?;
+    .await
+}
+
+#[derive(thiserror::Error)]
+pub enum ConfirmError {
+    #[error("The confirmation link is invalid")]
+    InvalidToken,
+
+    /// Signing up again sends a new link.
+    #[error("The confirmation link has expired")]
+    ExpiredToken,
+
+    /// Another subscription already uses the address being confirmed.
+    #[error("The address is already subscribed")]
+    AddressTaken,
+
+    /// The address being confirmed must not be emailed, e.g. because it bounced.
+    #[error("We can't send emails to this address")]
+    AddressSuppressed,
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ConfirmError {
+    fn response_builder(&self) -> HttpResponseBuilder {
+        match self {
+            ConfirmError::InvalidToken => HttpResponse::BadRequest(),
+            ConfirmError::ExpiredToken => HttpResponse::Gone(),
+            ConfirmError::AddressTaken => HttpResponse::Conflict(),
+            ConfirmError::AddressSuppressed => HttpResponse::BadRequest(),
+            ConfirmError::UnexpectedError(_) => HttpResponse::InternalServerError(),
+        }
+    }
+    fn error_id(&self) -> &str {
+        match self {
+            ConfirmError::InvalidToken => "invalid_data",
+            ConfirmError::ExpiredToken => "expired_token",
+            ConfirmError::AddressTaken => "conflict",
+            ConfirmError::AddressSuppressed => "invalid_data",
+            ConfirmError::UnexpectedError(_) => "internal_error",
+        }
+    }
+}
+
+impl std::fmt::Debug for ConfirmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
 
-    Ok(result.map(|r| r.subscriber_id))
+impl ResponseError for ConfirmError {
+    fn error_response(&self) -> HttpResponse {
+        self.response_builder().json(serde_json::json!({
+            "error_id": self.error_id()
+        }))
+    }
 }
@@ -0,0 +1,471 @@
+//! The preference center, where subscribers manage their subscription without an account. They
+//! prove who they are with the unsubscribe token from the footer of every issue.
+
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::configuration::{DataRequestSettings, SubscriptionSettings};
+use crate::domain::{SubscriberEmail, SubscriberName};
+use crate::email_client::{EmailSender, OutgoingEmail};
+use crate::startup::ApplicationBaseUrl;
+use crate::suppressions::is_suppressed;
+use crate::utils::AppError;
+
+use super::{ConfirmError, generate_subscription_token, get_subscriber_id_from_unsubscribe_token};
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesParameters {
+    unsubscribe_token: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct Preferences {
+    name: String,
+    email: String,
+    status: String,
+    /// The address the subscriber asked to move to, until they confirm it.
+    pending_email: Option<String>,
+    /// Every list, whether the subscriber joined it or not.
+    lists: Vec<ListPreference>,
+}
+
+#[derive(serde::Serialize)]
+pub struct ListPreference {
+    list_id: Uuid,
+    name: String,
+    description: String,
+    /// The subscriber's membership: `pending_confirmation`, `confirmed` or `unsubscribed`, or
+    /// `None` if they never joined the list.
+    status: Option<String>,
+}
+
+/// The preferences to change. Those that are missing are left alone.
+#[derive(serde::Deserialize)]
+pub struct UpdatePreferencesData {
+    name: Option<String>,
+    /// Takes effect once the subscriber follows the link sent to the new address.
+    email: Option<String>,
+    /// Every list the subscriber wants to receive. They leave the lists that aren't in here.
+    lists: Option<Vec<Uuid>>,
+}
+
+#[tracing::instrument(name = "Get subscriber preferences", skip(parameters, pool))]
+pub async fn get_preferences(
+    parameters: web::Query<PreferencesParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let subscriber_id = authenticate(&pool, &parameters.unsubscribe_token).await?;
+    let preferences = fetch_preferences(&pool, subscriber_id).await?;
+    Ok(HttpResponse::Ok().json(preferences))
+}
+
+#[tracing::instrument(
+    name = "Update subscriber preferences",
+    skip(
+        parameters,
+        form,
+        pool,
+        email_client,
+        base_url,
+        settings,
+        data_request_settings
+    )
+)]
+pub async fn update_preferences(
+    parameters: web::Query<PreferencesParameters>,
+    form: web::Json<UpdatePreferencesData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<dyn EmailSender>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+    data_request_settings: web::Data<DataRequestSettings>,
+) -> Result<HttpResponse, AppError> {
+    let UpdatePreferencesData { name, email, lists } = form.0;
+    let name = name
+        .map(SubscriberName::parse)
+        .transpose()
+        .map_err(AppError::BadInputData)?;
+    let email = email
+        .map(SubscriberEmail::parse)
+        .transpose()
+        .map_err(AppError::BadInputData)?;
+    let subscriber_id = authenticate(&pool, &parameters.unsubscribe_token).await?;
+    if let Some(email) = &email
+        && is_suppressed(
+            pool.get_ref(),
+            email.as_ref(),
+            &data_request_settings.hash_key,
+        )
+        .await?
+    {
+        return Err(AppError::BadInputData(
+            "We can't send emails to this address".to_string(),
+        ));
+    }
+
+    let mut transaction = pool.begin().await?;
+    let subscriber = sqlx::query!(
+        "SELECT email, status FROM subscriptions WHERE id = $1 FOR UPDATE",
+        subscriber_id
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+
+    if let Some(name) = name {
+        let query = sqlx::query!(
+            "UPDATE subscriptions SET name = $2 WHERE id = $1",
+            subscriber_id,
+            name.as_ref()
+        );
+        transaction.execute(query).await?;
+    }
+    if let Some(lists) = lists {
+        update_lists(
+            &mut transaction,
+            subscriber_id,
+            &subscriber.email,
+            &subscriber.status,
+            lists,
+        )
+        .await?;
+    }
+    if let Some(email) = email
+        && email.as_ref() != subscriber.email
+    {
+        let email_change_token = generate_subscription_token();
+        store_email_change_token(
+            &mut transaction,
+            subscriber_id,
+            email.as_ref(),
+            &email_change_token,
+            settings.confirmation_token_ttl(),
+        )
+        .await?;
+        let email = email_change_email(email, &base_url.0, &email_change_token);
+        email_client
+            .send_email(
+                &email.recipient,
+                &email.subject,
+                &email.html_content,
+                &email.text_content,
+            )
+            .await
+            .map_err(AppError::SendConfirmationEmailError)?;
+    }
+    transaction.commit().await?;
+
+    let preferences = fetch_preferences(&pool, subscriber_id).await?;
+    Ok(HttpResponse::Ok().json(preferences))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ConfirmEmailParameters {
+    email_change_token: String,
+}
+
+/// Move the subscription to the new address once the subscriber proves they own it. Issues
+/// that are still queued for the old address go to the new one. The delivery history and
+/// failures stay on the old address, since that is where those issues were sent.
+#[tracing::instrument(
+    name = "Confirm a subscriber's new address",
+    skip(parameters, pool, data_request_settings)
+)]
+pub async fn confirm_email_change(
+    parameters: web::Query<ConfirmEmailParameters>,
+    pool: web::Data<PgPool>,
+    data_request_settings: web::Data<DataRequestSettings>,
+) -> Result<HttpResponse, ConfirmError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let token = sqlx::query!(
+        r#"
+        SELECT
+            email_change_tokens.subscriber_id,
+            email_change_tokens.new_email,
+            email_change_tokens.expires_at,
+            subscriptions.email AS old_email
+        FROM email_change_tokens
+        JOIN subscriptions ON subscriptions.id = email_change_tokens.subscriber_id
+        WHERE email_change_tokens.email_change_token = $1
+        FOR UPDATE OF subscriptions
+        "#,
+        parameters.email_change_token
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to look up the email change token")?
+    .ok_or(ConfirmError::InvalidToken)?;
+    if token.expires_at <= Utc::now() {
+        return Err(ConfirmError::ExpiredToken);
+    }
+    // The address may have bounced or been reported since the change was requested
+    if is_suppressed(
+        &mut *transaction,
+        &token.new_email,
+        &data_request_settings.hash_key,
+    )
+    .await
+    .context("Failed to check whether the new address is suppressed")?
+    {
+        return Err(ConfirmError::AddressSuppressed);
+    }
+
+    sqlx::query!(
+        "UPDATE subscriptions SET email = $2 WHERE id = $1",
+        token.subscriber_id,
+        token.new_email
+    )
+    .execute(&mut *transaction)
+    .await
+    .map_err(|e| match e.as_database_error() {
+        Some(error) if error.is_unique_violation() => ConfirmError::AddressTaken,
+        _ => anyhow::Error::new(e)
+            .context("Failed to change the subscriber's address")
+            .into(),
+    })?;
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET subscriber_email = $2
+        WHERE subscriber_email = $1
+        "#,
+        token.old_email,
+        token.new_email
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to move the queued deliveries to the new address")?;
+    sqlx::query!(
+        "DELETE FROM email_change_tokens WHERE subscriber_id = $1",
+        token.subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete the email change tokens")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the address change")?;
+
+    FlashMessage::info("Your email address has been changed.").send();
+    Ok(HttpResponse::Ok().finish())
+}
+
+async fn authenticate(pool: &PgPool, unsubscribe_token: &str) -> Result<Uuid, AppError> {
+    get_subscriber_id_from_unsubscribe_token(pool, unsubscribe_token)
+        .await?
+        .ok_or_else(|| AppError::BadInputData("Invalid unsubscribe token".to_string()))
+}
+
+#[tracing::instrument(name = "Fetch subscriber preferences", skip(pool))]
+async fn fetch_preferences(pool: &PgPool, subscriber_id: Uuid) -> Result<Preferences, AppError> {
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT
+            name,
+            email,
+            status,
+            (
+                SELECT new_email
+                FROM email_change_tokens
+                WHERE
+                    email_change_tokens.subscriber_id = subscriptions.id AND
+                    email_change_tokens.expires_at > now()
+                ORDER BY email_change_tokens.created_at DESC
+                LIMIT 1
+            ) AS pending_email
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let lists = sqlx::query_as!(
+        ListPreference,
+        r#"
+        SELECT
+            lists.list_id,
+            lists.name,
+            lists.description,
+            list_memberships.status AS "status?"
+        FROM lists
+        LEFT JOIN list_memberships
+            ON list_memberships.list_id = lists.list_id AND list_memberships.subscriber_id = $1
+        ORDER BY lists.is_default DESC, lists.name
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Preferences {
+        name: subscriber.name,
+        email: subscriber.email,
+        status: subscriber.status,
+        pending_email: subscriber.pending_email,
+        lists,
+    })
+}
+
+/// Make `list_ids` the lists the subscriber receives. Joining doesn't need another confirmation,
+/// since the token proves the subscriber reads the confirmed address, but subscribers who left
+/// altogether have to sign up again.
+#[tracing::instrument(name = "Update the subscriber's lists", skip(transaction, email))]
+async fn update_lists(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    email: &str,
+    status: &str,
+    mut list_ids: Vec<Uuid>,
+) -> Result<(), AppError> {
+    list_ids.sort_unstable();
+    list_ids.dedup();
+    let known_lists = sqlx::query!(
+        r#"SELECT count(*) AS "count!" FROM lists WHERE list_id = ANY($1)"#,
+        &list_ids
+    )
+    .fetch_one(&mut **transaction)
+    .await?
+    .count;
+    if known_lists != list_ids.len() as i64 {
+        return Err(AppError::BadInputData("No such list".to_string()));
+    }
+
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (subscriber_id, list_id, status, confirmed_at)
+        SELECT $1, unnest($2::uuid[]), 'confirmed', now()
+        ON CONFLICT (subscriber_id, list_id) DO UPDATE
+        SET status = 'confirmed', confirmed_at = now()
+        WHERE list_memberships.status <> 'confirmed'
+        "#,
+        subscriber_id,
+        &list_ids
+    );
+    let joined = transaction.execute(query).await?.rows_affected();
+    if joined > 0 && status != "confirmed" {
+        return Err(AppError::Conflict(
+            "Only confirmed subscribers can join lists, sign up again first".to_string(),
+        ));
+    }
+
+    let left: Vec<Uuid> = sqlx::query!(
+        r#"
+        UPDATE list_memberships
+        SET status = 'unsubscribed'
+        WHERE
+            subscriber_id = $1 AND
+            status <> 'unsubscribed' AND
+            NOT (list_id = ANY($2))
+        RETURNING list_id
+        "#,
+        subscriber_id,
+        &list_ids
+    )
+    .fetch_all(&mut **transaction)
+    .await?
+    .into_iter()
+    .map(|row| row.list_id)
+    .collect();
+    // Like unsubscribing, leaving a list stops the issues already queued for it, unless they
+    // were also sent to a list the subscriber is still on
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE
+            subscriber_email = $1 AND
+            newsletter_issue_id IN (
+                SELECT newsletter_issue_id FROM newsletter_issue_lists WHERE list_id = ANY($2)
+            ) AND
+            NOT EXISTS (
+                SELECT 1
+                FROM newsletter_issue_lists
+                JOIN list_memberships
+                    ON newsletter_issue_lists.list_id = list_memberships.list_id
+                WHERE
+                    newsletter_issue_lists.newsletter_issue_id =
+                        issue_delivery_queue.newsletter_issue_id AND
+                    list_memberships.subscriber_id = $3 AND
+                    list_memberships.status = 'confirmed'
+            )
+        "#,
+        email,
+        &left,
+        subscriber_id
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+/// Replace any earlier request to change the subscriber's address.
+#[tracing::instrument(
+    name = "Store email change token in the database",
+    skip(transaction, new_email, email_change_token)
+)]
+async fn store_email_change_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    new_email: &str,
+    email_change_token: &str,
+    ttl: std::time::Duration,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        "DELETE FROM email_change_tokens WHERE subscriber_id = $1",
+        subscriber_id
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO email_change_tokens (
+            email_change_token,
+            subscriber_id,
+            new_email,
+            created_at,
+            expires_at
+        )
+        VALUES ($1, $2, $3, now(), now() + make_interval(secs => $4))
+        "#,
+        email_change_token,
+        subscriber_id,
+        new_email,
+        ttl.as_secs_f64()
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+/// The email asking a subscriber to confirm the address they want to move to.
+fn email_change_email(
+    recipient: SubscriberEmail,
+    base_url: &str,
+    email_change_token: &str,
+) -> OutgoingEmail {
+    let confirmation_link = format!(
+        "{base_url}/api/subscriptions/preferences/confirm_email?email_change_token={email_change_token}"
+    );
+
+    let html_content = format!(
+        "You asked to receive our newsletter at this address.<br />\
+        Click <a href=\"{confirmation_link}\">here</a> to confirm it."
+    );
+
+    let text_content = format!(
+        "You asked to receive our newsletter at this address.\nVisit {confirmation_link} to confirm it."
+    );
+
+    OutgoingEmail {
+        recipient,
+        subject: "Confirm your new address".to_string(),
+        html_content,
+        text_content,
+        headers: Vec::new(),
+    }
+}
@@ -0,0 +1,112 @@
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::{Executor, PgPool};
+use uuid::Uuid;
+
+use crate::utils::AppError;
+
+#[derive(serde::Deserialize)]
+pub struct UnsubscribeParameters {
+    unsubscribe_token: String,
+}
+
+#[tracing::instrument(name = "Unsubscribe a subscriber", skip(parameters, pool))]
+pub async fn unsubscribe(
+    parameters: web::Query<UnsubscribeParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    unsubscribe_with_token(&pool, &parameters.unsubscribe_token).await?;
+
+    FlashMessage::info(
+        "You have unsubscribed from our newsletter. You will not receive any more issues.",
+    )
+    .send();
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// One-click unsubscribe as described in RFC 8058.
+///
+/// Mail clients POST here from the `List-Unsubscribe` header of an issue, without a browser
+/// session, so we don't require any particular body and don't send a flash message.
+#[tracing::instrument(name = "One-click unsubscribe a subscriber", skip(parameters, pool))]
+pub async fn unsubscribe_one_click(
+    parameters: web::Query<UnsubscribeParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    unsubscribe_with_token(&pool, &parameters.unsubscribe_token).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+async fn unsubscribe_with_token(pool: &PgPool, unsubscribe_token: &str) -> Result<(), AppError> {
+    let id = get_subscriber_id_from_unsubscribe_token(pool, unsubscribe_token).await?;
+
+    let Some(subscriber_id) = id else {
+        return Err(AppError::BadInputData(
+            "Invalid unsubscribe token".to_string(),
+        ));
+    };
+
+    unsubscribe_subscriber(pool, subscriber_id).await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Mark subscriber as unsubscribed", skip(subscriber_id, pool))]
+pub async fn unsubscribe_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
+    // Keep the original timestamp if the subscriber clicks the link more than once.
+    let query = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'unsubscribed', unsubscribed_at = now()
+        WHERE id = $1 AND status <> 'unsubscribed'
+        "#,
+        subscriber_id
+    );
+    transaction.execute(query).await?;
+
+    // Issues that were queued before the subscriber left must not be delivered.
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE subscriber_email = (SELECT email FROM subscriptions WHERE id = $1)
+        "#,
+        subscriber_id
+    );
+    transaction.execute(query).await?;
+
+    // Following an old confirmation link must not subscribe them again.
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM subscription_tokens
+        WHERE subscriber_id = $1
+        "#,
+        subscriber_id
+    );
+    transaction.execute(query).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "Get subscriber_id from unsubscribe token",
+    skip(unsubscribe_token, pool)
+)]
+pub async fn get_subscriber_id_from_unsubscribe_token(
+    pool: &PgPool,
+    unsubscribe_token: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"SELECT subscriber_id FROM unsubscribe_tokens
+        WHERE unsubscribe_token = $1"#,
+        unsubscribe_token
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result.map(|r| r.subscriber_id))
+}
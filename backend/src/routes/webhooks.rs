@@ -0,0 +1,188 @@
+use actix_web::http::header::{self, HeaderMap};
+use actix_web::{HttpRequest, HttpResponse, HttpResponseBuilder, ResponseError, web};
+use anyhow::Context;
+use base64::Engine;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::authentication::Credentials;
+use crate::configuration::WebhookSettings;
+use crate::suppressions::{SuppressionReason, suppress};
+use crate::utils::error_chain_fmt;
+
+/// Bounce types after which the address will never accept our emails. Soft bounces, e.g. a full
+/// mailbox, are left to the delivery retries.
+const HARD_BOUNCE_TYPES: [&str; 3] = ["HardBounce", "BadEmailAddress", "ManuallyDeactivated"];
+
+/// The records Postmark posts to its webhooks. Other record types, e.g. deliveries or opens, are
+/// acknowledged and ignored.
+#[derive(serde::Deserialize)]
+#[serde(tag = "RecordType")]
+enum PostmarkRecord {
+    Bounce(BounceRecord),
+    SpamComplaint(SpamComplaintRecord),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct BounceRecord {
+    #[serde(rename = "Type")]
+    bounce_type: String,
+    email: String,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SpamComplaintRecord {
+    email: String,
+}
+
+/// Suppress the addresses that Postmark reports as hard-bounced or complaining about spam.
+///
+/// The body is only parsed once the request is authenticated, so anonymous callers can't probe it.
+#[tracing::instrument(
+    name = "Handle a Postmark webhook",
+    skip_all,
+    fields(record_type=tracing::field::Empty, subscriber_email=tracing::field::Empty)
+)]
+pub async fn postmark_webhook(
+    request: HttpRequest,
+    body: web::Bytes,
+    pool: web::Data<PgPool>,
+    settings: web::Data<WebhookSettings>,
+) -> Result<HttpResponse, WebhookError> {
+    let credentials = basic_authentication(request.headers()).map_err(WebhookError::AuthError)?;
+    authenticate(&credentials, &settings).map_err(WebhookError::AuthError)?;
+
+    let record: PostmarkRecord =
+        serde_json::from_slice(&body).map_err(WebhookError::InvalidPayload)?;
+
+    let (email, reason, details) = match record {
+        PostmarkRecord::Bounce(bounce) => {
+            tracing::Span::current().record("record_type", "Bounce");
+            if !HARD_BOUNCE_TYPES.contains(&bounce.bounce_type.as_str()) {
+                tracing::info!(bounce_type = bounce.bounce_type, "Ignoring soft bounce");
+                return Ok(HttpResponse::Ok().finish());
+            }
+            (
+                bounce.email,
+                SuppressionReason::HardBounce,
+                bounce.description,
+            )
+        }
+        PostmarkRecord::SpamComplaint(complaint) => {
+            tracing::Span::current().record("record_type", "SpamComplaint");
+            (complaint.email, SuppressionReason::SpamComplaint, None)
+        }
+        PostmarkRecord::Other => return Ok(HttpResponse::Ok().finish()),
+    };
+    tracing::Span::current().record("subscriber_email", tracing::field::display(&email));
+
+    suppress(&pool, &email, reason, details.as_deref())
+        .await
+        .context("Failed to suppress the address")?;
+    tracing::info!(reason = reason.as_str(), "Suppressed address");
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+fn basic_authentication(headers: &HeaderMap) -> Result<Credentials, anyhow::Error> {
+    let header_value = headers
+        .get(header::AUTHORIZATION)
+        .context("The 'Authorization' header was missing")?
+        .to_str()
+        .context("The 'Authorization' header was not a valid UTF8 string")?;
+    let base64_encoded_segment = header_value
+        .strip_prefix("Basic ")
+        .context("The authorization scheme was not 'Basic'")?;
+    let decoded_bytes = base64::engine::general_purpose::STANDARD
+        .decode(base64_encoded_segment)
+        .context("Failed to base64-decode 'Basic' credentials")?;
+    let decoded_credentials = String::from_utf8(decoded_bytes)
+        .context("The decoded credential string is not valid UTF8")?;
+
+    let (username, password) = decoded_credentials
+        .split_once(':')
+        .context("A password must be provided in 'Basic' auth")?;
+
+    Ok(Credentials {
+        username: username.to_string(),
+        password: Secret::new(password.to_string()),
+    })
+}
+
+fn authenticate(
+    credentials: &Credentials,
+    settings: &WebhookSettings,
+) -> Result<(), anyhow::Error> {
+    let username_matches = constant_time_eq(
+        credentials.username.as_bytes(),
+        settings.username.as_bytes(),
+    );
+    let password_matches = constant_time_eq(
+        credentials.password.expose_secret().as_bytes(),
+        settings.password.expose_secret().as_bytes(),
+    );
+    if username_matches && password_matches {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("Invalid webhook credentials"))
+    }
+}
+
+/// Compare without stopping at the first difference, so that the time taken doesn't reveal how
+/// much of the password was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[derive(thiserror::Error)]
+pub enum WebhookError {
+    #[error("Authentication failed")]
+    AuthError(#[source] anyhow::Error),
+
+    #[error("Invalid webhook payload")]
+    InvalidPayload(#[source] serde_json::Error),
+
+    #[error("Something went wrong")]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl WebhookError {
+    fn response_builder(&self) -> HttpResponseBuilder {
+        match self {
+            WebhookError::AuthError(_) => {
+                let mut builder = HttpResponse::Unauthorized();
+                builder.insert_header((header::WWW_AUTHENTICATE, r#"Basic realm="webhooks""#));
+                builder
+            }
+            WebhookError::InvalidPayload(_) => HttpResponse::BadRequest(),
+            WebhookError::UnexpectedError(_) => HttpResponse::InternalServerError(),
+        }
+    }
+    fn error_id(&self) -> &str {
+        match self {
+            WebhookError::AuthError(_) => "invalid_credentials",
+            WebhookError::InvalidPayload(_) => "invalid_data",
+            WebhookError::UnexpectedError(_) => "internal",
+        }
+    }
+}
+
+impl std::fmt::Debug for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for WebhookError {
+    fn error_response(&self) -> HttpResponse {
+        self.response_builder().json(serde_json::json!({
+            "error_id": self.error_id()
+        }))
+    }
+}
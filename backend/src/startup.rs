@@ -1,15 +1,29 @@
 use crate::authentication::reject_anonymous_users;
-use crate::configuration::{DatabaseSettings, Settings};
-use crate::email_client::EmailClient;
+use crate::configuration::{
+    DataRequestSettings, DatabaseSettings, FeedSettings, Settings, SubscriptionSettings,
+    WebhookSettings,
+};
+use crate::email_client::EmailSender;
 use crate::routes::{
-    change_password, confirm, health_check, log_out, login, publish_newsletter, subscribe,
-    user_metadata,
+    archive_page, archived_issue, archived_issue_page, archived_issues, atom_feed,
+    cancel_newsletter, change_password, confirm, confirm_email_change, create_draft, create_list,
+    create_segment, delete_draft, delete_segment, delete_subscriber, export_subscribers, get_draft,
+    get_newsletter_status, get_preferences, get_segment, get_subscriber, health_check,
+    import_subscribers, list_data_requests, list_delivery_failures, list_drafts, list_lists,
+    list_scheduled_newsletters, list_segments, list_subscribers, log_out, login,
+    manually_confirm_subscriber, pause_newsletter, postmark_webhook, preview_draft, public_lists,
+    publish_draft, publish_newsletter, request_data_access, request_data_erasure,
+    requeue_delivery_failures, reschedule_newsletter, resume_newsletter, rss_feed,
+    send_test_newsletter, set_archive_visibility, set_subscriber_attributes, set_subscriber_tags,
+    subscribe, unsubscribe, unsubscribe_one_click, update_draft, update_preferences,
+    update_segment, update_subscriber, user_metadata,
 };
-use actix_files::Files;
+use actix_files::{Files, NamedFile};
 use actix_session::SessionMiddleware;
 use actix_session::storage::RedisSessionStore;
 use actix_web::HttpResponse;
 use actix_web::cookie::Key;
+use actix_web::dev::{ServiceRequest, ServiceResponse, fn_service};
 use actix_web::middleware::from_fn;
 use actix_web::{
     App, HttpServer,
@@ -22,6 +36,8 @@ use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 use sqlx::postgres::PgPoolOptions;
 use std::net::TcpListener;
+use std::path::Path;
+use std::sync::Arc;
 use tracing_actix_web::TracingLogger;
 
 pub struct Application {
@@ -55,6 +71,11 @@ impl Application {
             configuration.redis_uri,
             configuration.application.secure_cookies,
             configuration.application.frontend_files_directory,
+            configuration.application.test_subject_prefix,
+            configuration.webhook,
+            configuration.subscriptions,
+            configuration.feeds,
+            configuration.data_requests,
         )
         .await
         .expect("Failed to run server");
@@ -77,23 +98,43 @@ pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
 
 #[tracing::instrument(
     name = "Run server",
-    skip(listener, db_pool, email_client, cookie_store_key, redis_uri)
+    skip(
+        listener,
+        db_pool,
+        email_client,
+        cookie_store_key,
+        redis_uri,
+        webhook_settings,
+        subscription_settings,
+        feed_settings,
+        data_request_settings
+    )
 )]
 #[allow(clippy::too_many_arguments)]
 async fn run(
     listener: TcpListener,
     db_pool: PgPool,
-    email_client: EmailClient,
+    email_client: Arc<dyn EmailSender>,
     base_url: String,
     cookie_store_key: Secret<String>,
     redis_uri: Secret<String>,
     secure_cookies: bool,
     frontend_files_directory: String,
+    test_subject_prefix: String,
+    webhook_settings: WebhookSettings,
+    subscription_settings: SubscriptionSettings,
+    feed_settings: FeedSettings,
+    data_request_settings: DataRequestSettings,
 ) -> Result<Server, anyhow::Error> {
     // Wrap the pool in a smart pointer
     let db_pool = Data::new(db_pool);
-    let email_client = Data::new(email_client);
+    let email_client = Data::from(email_client);
     let base_url = Data::new(ApplicationBaseUrl(base_url));
+    let test_subject_prefix = Data::new(TestSubjectPrefix(test_subject_prefix));
+    let webhook_settings = Data::new(webhook_settings);
+    let subscription_settings = Data::new(subscription_settings);
+    let feed_settings = Data::new(feed_settings);
+    let data_request_settings = Data::new(data_request_settings);
 
     let secret_key = Key::from(cookie_store_key.expose_secret().as_bytes());
     let message_store =
@@ -113,27 +154,140 @@ async fn run(
             )
             .wrap(TracingLogger::default())
             .app_data(json_config())
+            .app_data(query_config())
             // Backend
             .service(
                 web::scope("/api")
                     .route("/login", web::post().to(login))
                     .route("/health_check", web::get().to(health_check))
+                    .route("/lists", web::get().to(public_lists))
+                    .route("/archive", web::get().to(archived_issues))
+                    .route("/archive/{slug}", web::get().to(archived_issue))
                     .route("/subscriptions", web::post().to(subscribe))
                     .route("/subscriptions/confirm", web::get().to(confirm))
+                    .route("/subscriptions/preferences", web::get().to(get_preferences))
+                    .route(
+                        "/subscriptions/preferences",
+                        web::patch().to(update_preferences),
+                    )
+                    .route(
+                        "/subscriptions/preferences/confirm_email",
+                        web::get().to(confirm_email_change),
+                    )
+                    .route("/subscriptions/unsubscribe", web::get().to(unsubscribe))
+                    .route(
+                        "/subscriptions/unsubscribe",
+                        web::post().to(unsubscribe_one_click),
+                    )
+                    .route("/webhooks/postmark", web::post().to(postmark_webhook))
                     .service(
                         web::scope("/admin")
                             .wrap(from_fn(reject_anonymous_users))
                             .route("/user", web::get().to(user_metadata))
                             .route("/password", web::post().to(change_password))
                             .route("/newsletters", web::post().to(publish_newsletter))
+                            .route("/newsletters/test", web::post().to(send_test_newsletter))
+                            .route(
+                                "/newsletters/failures",
+                                web::get().to(list_delivery_failures),
+                            )
+                            .route(
+                                "/newsletters/failures/requeue",
+                                web::post().to(requeue_delivery_failures),
+                            )
+                            .route(
+                                "/newsletters/scheduled",
+                                web::get().to(list_scheduled_newsletters),
+                            )
+                            .route(
+                                "/newsletters/{newsletter_issue_id}/status",
+                                web::get().to(get_newsletter_status),
+                            )
+                            .route(
+                                "/newsletters/{newsletter_issue_id}/reschedule",
+                                web::post().to(reschedule_newsletter),
+                            )
+                            .route(
+                                "/newsletters/{newsletter_issue_id}/pause",
+                                web::post().to(pause_newsletter),
+                            )
+                            .route(
+                                "/newsletters/{newsletter_issue_id}/resume",
+                                web::post().to(resume_newsletter),
+                            )
+                            .route(
+                                "/newsletters/{newsletter_issue_id}/cancel",
+                                web::post().to(cancel_newsletter),
+                            )
+                            .route(
+                                "/newsletters/{newsletter_issue_id}/archive",
+                                web::put().to(set_archive_visibility),
+                            )
+                            .route("/drafts", web::get().to(list_drafts))
+                            .route("/drafts", web::post().to(create_draft))
+                            .route("/drafts/{draft_id}", web::get().to(get_draft))
+                            .route("/drafts/{draft_id}", web::patch().to(update_draft))
+                            .route("/drafts/{draft_id}", web::delete().to(delete_draft))
+                            .route("/drafts/{draft_id}/preview", web::get().to(preview_draft))
+                            .route("/drafts/{draft_id}/publish", web::post().to(publish_draft))
+                            .route("/lists", web::get().to(list_lists))
+                            .route("/lists", web::post().to(create_list))
+                            .route("/segments", web::get().to(list_segments))
+                            .route("/segments", web::post().to(create_segment))
+                            .route("/segments/{segment_id}", web::get().to(get_segment))
+                            .route("/segments/{segment_id}", web::put().to(update_segment))
+                            .route("/segments/{segment_id}", web::delete().to(delete_segment))
+                            .route("/subscribers", web::get().to(list_subscribers))
+                            .route("/subscribers/export", web::get().to(export_subscribers))
+                            .route("/subscribers/import", web::post().to(import_subscribers))
+                            .route(
+                                "/subscribers/{subscriber_id}",
+                                web::get().to(get_subscriber),
+                            )
+                            .route(
+                                "/subscribers/{subscriber_id}",
+                                web::patch().to(update_subscriber),
+                            )
+                            .route(
+                                "/subscribers/{subscriber_id}",
+                                web::delete().to(delete_subscriber),
+                            )
+                            .route(
+                                "/subscribers/{subscriber_id}/tags",
+                                web::put().to(set_subscriber_tags),
+                            )
+                            .route(
+                                "/subscribers/{subscriber_id}/attributes",
+                                web::put().to(set_subscriber_attributes),
+                            )
+                            .route(
+                                "/subscribers/{subscriber_id}/confirm",
+                                web::post().to(manually_confirm_subscriber),
+                            )
+                            .route("/data_requests", web::get().to(list_data_requests))
+                            .route("/data_requests/access", web::post().to(request_data_access))
+                            .route(
+                                "/data_requests/erasure",
+                                web::post().to(request_data_erasure),
+                            )
                             .route("/logout", web::get().to(log_out)),
                     ),
             )
             .app_data(db_pool.clone())
             .app_data(email_client.clone())
             .app_data(base_url.clone())
+            .app_data(test_subject_prefix.clone())
+            .app_data(webhook_settings.clone())
+            .app_data(subscription_settings.clone())
+            .app_data(feed_settings.clone())
+            .app_data(data_request_settings.clone())
+            // Server-rendered pages
+            .route("/archive", web::get().to(archive_page))
+            .route("/archive/{slug}", web::get().to(archived_issue_page))
+            .route("/feed.rss", web::get().to(rss_feed))
+            .route("/feed.atom", web::get().to(atom_feed))
             // Frontend
-            .service(Files::new("/", &frontend_files_directory).index_file("index.html"))
+            .service(frontend_files(&frontend_files_directory))
     })
     .listen(listener)?
     .run();
@@ -141,6 +295,29 @@ async fn run(
     Ok(server)
 }
 
+/// Serve the frontend, and its `index.html` for any other path without a file extension, so
+/// that links to the frontend's pages (e.g. `/preferences` in emails) are handled by its router.
+/// Missing files, e.g. an old script, are a 404 rather than the page.
+fn frontend_files(directory: &str) -> Files {
+    let index_file = format!("{directory}/index.html");
+    Files::new("/", directory)
+        .index_file("index.html")
+        .default_handler(fn_service(move |request: ServiceRequest| {
+            let index_file = index_file.clone();
+            async move {
+                let (request, _) = request.into_parts();
+                let response = if Path::new(request.path()).extension().is_some() {
+                    HttpResponse::NotFound().finish()
+                } else {
+                    NamedFile::open_async(index_file)
+                        .await?
+                        .into_response(&request)
+                };
+                Ok(ServiceResponse::new(request, response))
+            }
+        }))
+}
+
 fn json_config() -> web::JsonConfig {
     web::JsonConfig::default().error_handler(|err, _| {
         actix_web::error::InternalError::from_response(
@@ -151,7 +328,19 @@ fn json_config() -> web::JsonConfig {
     })
 }
 
+fn query_config() -> web::QueryConfig {
+    web::QueryConfig::default().error_handler(|err, _| {
+        actix_web::error::InternalError::from_response(
+            err,
+            HttpResponse::BadRequest().json(serde_json::json!({ "error_id": "invalid_data" })),
+        )
+        .into()
+    })
+}
+
 pub struct ApplicationBaseUrl(pub String);
 
+pub struct TestSubjectPrefix(pub String);
+
 // TODO: Remove?
 pub struct HmacSecret(pub Secret<String>);
@@ -0,0 +1,540 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::Utc;
+use secrecy::Secret;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::csv::{CsvReader, CsvRecord};
+use crate::data_requests::email_hash;
+use crate::domain::{SubscriberEmail, SubscriberName};
+use crate::email_client::EmailSender;
+use crate::routes::{
+    confirmation_email, generate_subscription_token, store_token, store_unsubscribe_token,
+};
+use crate::utils::error_chain_fmt;
+
+/// How many valid rows are checked against the database and stored together.
+const BATCH_SIZE: usize = 500;
+
+/// What happens to the subscribers that are imported.
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// They already confirmed with the previous provider, so they are confirmed right away.
+    Confirmed,
+    /// They are stored as pending and get the same confirmation email as a new subscriber.
+    SendConfirmation,
+}
+
+impl std::str::FromStr for ImportMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "confirmed" => Ok(ImportMode::Confirmed),
+            "send_confirmation" => Ok(ImportMode::SendConfirmation),
+            other => Err(format!(
+                "'{other}' is not an import mode. Use either 'confirmed' or 'send_confirmation'."
+            )),
+        }
+    }
+}
+
+#[derive(serde::Serialize, Debug, Default)]
+pub struct ImportReport {
+    pub accepted: usize,
+    pub rejected: usize,
+    /// Every row of the file after the header, in order.
+    pub rows: Vec<ImportedRow>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct ImportedRow {
+    /// Where the row starts in the file, counting from 1 with the header.
+    pub line: usize,
+    /// The email as written in the file, if the row has one.
+    pub email: Option<String>,
+    #[serde(flatten)]
+    pub outcome: RowOutcome,
+}
+
+#[derive(serde::Serialize, Debug, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RowOutcome {
+    Accepted,
+    Rejected { reason: String },
+}
+
+#[derive(thiserror::Error)]
+pub enum ImportError {
+    /// The file can't be imported at all, e.g. because a column is missing.
+    #[error("{0}")]
+    InvalidFile(String),
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Where the columns we need are in the file.
+struct Columns {
+    email: usize,
+    name: usize,
+}
+
+impl Columns {
+    fn from_header(header: &[String]) -> Result<Self, ImportError> {
+        let position = |column: &str| {
+            header.iter().position(|field| {
+                // Spreadsheets like to start their exports with a byte order mark
+                field
+                    .trim_start_matches('\u{feff}')
+                    .trim()
+                    .eq_ignore_ascii_case(column)
+            })
+        };
+        match (position("email"), position("name")) {
+            (Some(email), Some(name)) => Ok(Self { email, name }),
+            _ => Err(ImportError::InvalidFile(
+                "The first line must name the columns, including 'email' and 'name'".to_string(),
+            )),
+        }
+    }
+}
+
+/// A row that passed validation and still has to be checked against the database.
+struct ValidRow {
+    line: usize,
+    email: SubscriberEmail,
+    name: SubscriberName,
+}
+
+/// Imports a CSV file of subscribers as it is read, one chunk at a time.
+///
+/// Rows are validated like the subscription form, and addresses that are already subscribed,
+/// suppressed or repeated in the file are rejected. Every row ends up in the report, accepted or
+/// rejected with a reason; only a file without the expected columns fails the whole import.
+pub struct SubscriberImport<'a> {
+    pool: &'a PgPool,
+    email_client: &'a dyn EmailSender,
+    base_url: &'a str,
+    mode: ImportMode,
+    /// The list the subscribers join.
+    list_id: Uuid,
+    confirmation_token_ttl: Duration,
+    /// The key of the hashes that erased addresses are kept as among the suppressions.
+    hash_key: &'a Secret<String>,
+    reader: CsvReader,
+    columns: Option<Columns>,
+    /// The line where each (lowercased) address was first seen, to reject repeats.
+    seen: HashMap<String, usize>,
+    batch: Vec<ValidRow>,
+    report: ImportReport,
+}
+
+impl<'a> SubscriberImport<'a> {
+    pub fn new(
+        pool: &'a PgPool,
+        email_client: &'a dyn EmailSender,
+        base_url: &'a str,
+        mode: ImportMode,
+        list_id: Uuid,
+        confirmation_token_ttl: Duration,
+        hash_key: &'a Secret<String>,
+    ) -> Self {
+        Self {
+            pool,
+            email_client,
+            base_url,
+            mode,
+            list_id,
+            confirmation_token_ttl,
+            hash_key,
+            reader: CsvReader::default(),
+            columns: None,
+            seen: HashMap::new(),
+            batch: Vec::new(),
+            report: ImportReport::default(),
+        }
+    }
+
+    /// Import the rows completed by the next chunk of the file.
+    pub async fn push(&mut self, chunk: &[u8]) -> Result<(), ImportError> {
+        for record in self.reader.feed(chunk) {
+            self.add_record(record)?;
+            if self.batch.len() >= BATCH_SIZE {
+                self.store_batch().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Import what is left once the whole file was read.
+    pub async fn finish(mut self) -> Result<ImportReport, ImportError> {
+        if let Some(record) = std::mem::take(&mut self.reader).finish() {
+            self.add_record(record)?;
+        }
+        if self.columns.is_none() {
+            return Err(ImportError::InvalidFile("The file is empty".to_string()));
+        }
+        self.store_batch().await?;
+        // Rejected rows are reported as soon as they are read, accepted ones once stored
+        self.report.rows.sort_by_key(|row| row.line);
+        Ok(self.report)
+    }
+
+    fn add_record(&mut self, record: CsvRecord) -> Result<(), ImportError> {
+        let line = record.line;
+        let fields = match (record.fields, &self.columns) {
+            (Ok(fields), Some(_)) => fields,
+            (Ok(header), None) => {
+                self.columns = Some(Columns::from_header(&header)?);
+                return Ok(());
+            }
+            (Err(e), None) => return Err(ImportError::InvalidFile(e.description().to_string())),
+            (Err(e), Some(_)) => {
+                self.reject(line, None, e.description());
+                return Ok(());
+            }
+        };
+
+        let columns = self.columns.as_ref().unwrap();
+        let email = fields
+            .get(columns.email)
+            .map(|email| email.trim().to_string());
+        let name = fields.get(columns.name).map(|name| name.trim().to_string());
+        let (Some(email), Some(name)) = (email.clone(), name) else {
+            self.reject(
+                line,
+                email,
+                "The line is missing the 'email' or 'name' column",
+            );
+            return Ok(());
+        };
+
+        let parsed = SubscriberEmail::parse(email.clone())
+            .and_then(|email| Ok((email, SubscriberName::parse(name)?)));
+        let (subscriber_email, subscriber_name) = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                self.reject(line, Some(email), &e);
+                return Ok(());
+            }
+        };
+
+        // Mailbox providers don't tell `A@x.com` and `a@x.com` apart
+        let key = email.to_lowercase();
+        if let Some(first_line) = self.seen.get(&key) {
+            let reason = format!("The address is already on line {first_line}");
+            self.reject(line, Some(email), &reason);
+            return Ok(());
+        }
+        self.seen.insert(key, line);
+
+        self.batch.push(ValidRow {
+            line,
+            email: subscriber_email,
+            name: subscriber_name,
+        });
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "Store a batch of imported subscribers",
+        skip(self),
+        fields(rows = self.batch.len())
+    )]
+    async fn store_batch(&mut self) -> Result<(), ImportError> {
+        let batch = std::mem::take(&mut self.batch);
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let emails: Vec<String> = batch.iter().map(|row| row.email.to_string()).collect();
+        let subscribed = subscribed_addresses(self.pool, &emails).await?;
+        let suppressed = suppressed_addresses(self.pool, &emails, self.hash_key).await?;
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to start a transaction")?;
+        let mut stored = Vec::new();
+        let mut confirmation_emails = Vec::new();
+        for row in batch {
+            let email = row.email.to_string();
+            if suppressed.contains(&email.to_lowercase()) {
+                self.reject(row.line, Some(email), "The address is suppressed");
+                continue;
+            }
+            if subscribed.contains(&email.to_lowercase()) {
+                self.reject(row.line, Some(email), "The address is already subscribed");
+                continue;
+            }
+
+            let subscription_token = generate_subscription_token();
+            if !self
+                .store_row(&mut transaction, &row, &subscription_token)
+                .await?
+            {
+                // Someone subscribed in the meantime
+                self.reject(row.line, Some(email), "The address is already subscribed");
+                continue;
+            }
+            if self.mode == ImportMode::SendConfirmation {
+                confirmation_emails.push(confirmation_email(
+                    row.email,
+                    self.base_url,
+                    &subscription_token,
+                ));
+            }
+            stored.push((row.line, email));
+        }
+
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit the imported subscribers")?;
+
+        // Only send links to tokens that are stored, and without holding the batch's locks
+        let results = if confirmation_emails.is_empty() {
+            Vec::new()
+        } else {
+            self.email_client.send_emails(&confirmation_emails).await
+        };
+        let mut unsent = Vec::new();
+        for (i, (line, email)) in stored.into_iter().enumerate() {
+            if let Some(Err(e)) = results.get(i) {
+                tracing::warn!(
+                    error.cause_chain = ?e,
+                    subscriber_email = email,
+                    "Failed to send the confirmation email to an imported subscriber"
+                );
+                self.reject(
+                    line,
+                    Some(email.clone()),
+                    "Failed to send the confirmation email",
+                );
+                unsent.push(email);
+            } else {
+                self.accept(line, email);
+            }
+        }
+
+        if !unsent.is_empty() {
+            // Don't keep pending subscribers who were never asked to confirm
+            let mut transaction = self
+                .pool
+                .begin()
+                .await
+                .context("Failed to start a transaction")?;
+            for email in &unsent {
+                delete_subscriber(&mut transaction, email).await?;
+            }
+            transaction
+                .commit()
+                .await
+                .context("Failed to delete the subscribers whose confirmation email failed")?;
+        }
+        Ok(())
+    }
+
+    /// Store the subscriber and their tokens. Returns `false` when the address is taken, in any
+    /// case.
+    async fn store_row(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+        row: &ValidRow,
+        subscription_token: &str,
+    ) -> Result<bool, ImportError> {
+        let now = Utc::now();
+        let (status, confirmed_at) = match self.mode {
+            ImportMode::Confirmed => ("confirmed", Some(now)),
+            ImportMode::SendConfirmation => ("pending_confirmation", None),
+        };
+        let subscriber_id = Uuid::new_v4();
+        let query = sqlx::query!(
+            r#"
+            INSERT INTO subscriptions (id, email, name, subscribed_at, status, confirmed_at, source)
+            SELECT $1, $2, $3, $4, $5, $6, 'import'
+            WHERE NOT EXISTS (SELECT 1 FROM subscriptions WHERE lower(email) = lower($2))
+            ON CONFLICT (email) DO NOTHING
+            "#,
+            subscriber_id,
+            row.email.as_ref(),
+            row.name.as_ref(),
+            now,
+            status,
+            confirmed_at
+        );
+        let inserted = transaction
+            .execute(query)
+            .await
+            .context("Failed to insert an imported subscriber")?
+            .rows_affected();
+        if inserted == 0 {
+            return Ok(false);
+        }
+
+        store_unsubscribe_token(transaction, subscriber_id, &generate_subscription_token())
+            .await
+            .context("Failed to store an unsubscribe token")?;
+        let query = sqlx::query!(
+            r#"
+            INSERT INTO list_memberships (subscriber_id, list_id, status, joined_at, confirmed_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            subscriber_id,
+            self.list_id,
+            status,
+            now,
+            confirmed_at
+        );
+        transaction
+            .execute(query)
+            .await
+            .context("Failed to add an imported subscriber to the list")?;
+        if self.mode == ImportMode::SendConfirmation {
+            store_token(
+                transaction,
+                subscriber_id,
+                self.list_id,
+                subscription_token,
+                self.confirmation_token_ttl,
+            )
+            .await
+            .context("Failed to store a subscription token")?;
+        }
+        Ok(true)
+    }
+
+    fn accept(&mut self, line: usize, email: String) {
+        self.report.accepted += 1;
+        self.report.rows.push(ImportedRow {
+            line,
+            email: Some(email),
+            outcome: RowOutcome::Accepted,
+        });
+    }
+
+    fn reject(&mut self, line: usize, email: Option<String>, reason: &str) {
+        self.report.rejected += 1;
+        self.report.rows.push(ImportedRow {
+            line,
+            email,
+            outcome: RowOutcome::Rejected {
+                reason: reason.to_string(),
+            },
+        });
+    }
+}
+
+/// The subscribed addresses among `emails`, lowercased.
+async fn subscribed_addresses(
+    pool: &PgPool,
+    emails: &[String],
+) -> Result<HashSet<String>, anyhow::Error> {
+    let emails: Vec<String> = emails.iter().map(|email| email.to_lowercase()).collect();
+    let rows = sqlx::query!(
+        r#"SELECT lower(email) AS "email!" FROM subscriptions WHERE lower(email) = ANY($1)"#,
+        &emails
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to look up existing subscriptions")?;
+    Ok(rows.into_iter().map(|row| row.email).collect())
+}
+
+/// The suppressed addresses among `emails`, lowercased, including those that were erased and
+/// are only kept as their hash.
+async fn suppressed_addresses(
+    pool: &PgPool,
+    emails: &[String],
+    hash_key: &Secret<String>,
+) -> Result<HashSet<String>, anyhow::Error> {
+    let emails: Vec<String> = emails.iter().map(|email| email.to_lowercase()).collect();
+    let by_hash: HashMap<String, &String> = emails
+        .iter()
+        .map(|email| (email_hash(hash_key, email), email))
+        .collect();
+    let hashes: Vec<String> = by_hash.keys().cloned().collect();
+    let rows = sqlx::query!(
+        r#"
+        SELECT email
+        FROM suppressions
+        WHERE lower(email) = ANY($1) OR email = ANY($2)
+        "#,
+        &emails,
+        &hashes
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to look up suppressed addresses")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| match by_hash.get(&row.email) {
+            Some(email) => (*email).clone(),
+            None => row.email.to_lowercase(),
+        })
+        .collect())
+}
+
+async fn delete_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+) -> Result<(), anyhow::Error> {
+    // Only an unconfirmed subscriber, in case they are gone or signed up since they were stored
+    let Some(subscriber) = sqlx::query!(
+        r#"
+        SELECT id
+        FROM subscriptions
+        WHERE email = $1 AND status = 'pending_confirmation'
+        FOR UPDATE
+        "#,
+        email
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to look up an imported subscriber")?
+    else {
+        return Ok(());
+    };
+    let subscriber_id = subscriber.id;
+    transaction
+        .execute(sqlx::query!(
+            "DELETE FROM subscription_tokens WHERE subscriber_id = $1",
+            subscriber_id
+        ))
+        .await
+        .context("Failed to delete a subscription token")?;
+    transaction
+        .execute(sqlx::query!(
+            "DELETE FROM unsubscribe_tokens WHERE subscriber_id = $1",
+            subscriber_id
+        ))
+        .await
+        .context("Failed to delete an unsubscribe token")?;
+    transaction
+        .execute(sqlx::query!(
+            "DELETE FROM list_memberships WHERE subscriber_id = $1",
+            subscriber_id
+        ))
+        .await
+        .context("Failed to delete a list membership")?;
+    transaction
+        .execute(sqlx::query!(
+            "DELETE FROM subscriptions WHERE id = $1",
+            subscriber_id
+        ))
+        .await
+        .context("Failed to delete an imported subscriber")?;
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use secrecy::Secret;
+use sqlx::{Executor, PgPool, Postgres};
+
+use crate::data_requests::email_hash;
+
+/// Why an address must not be emailed anymore.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SuppressionReason {
+    /// The receiving server rejected the address for good, e.g. because it doesn't exist.
+    HardBounce,
+    /// The recipient reported one of our emails as spam.
+    SpamComplaint,
+}
+
+impl SuppressionReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SuppressionReason::HardBounce => "hard_bounce",
+            SuppressionReason::SpamComplaint => "spam_complaint",
+        }
+    }
+
+    /// The status the subscription of a suppressed address is moved to.
+    fn subscription_status(&self) -> &'static str {
+        match self {
+            SuppressionReason::HardBounce => "bounced",
+            SuppressionReason::SpamComplaint => "complained",
+        }
+    }
+}
+
+/// Stop emailing `email`: record the suppression, update the matching subscription, and drop the
+/// deliveries that are still queued for it or could be requeued, and its confirmation links. Addresses are matched
+/// case-insensitively, as email services don't necessarily report them as they were subscribed.
+#[tracing::instrument(skip(pool, details))]
+pub async fn suppress(
+    pool: &PgPool,
+    email: &str,
+    reason: SuppressionReason,
+    details: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO suppressions (email, reason, details, suppressed_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT ((lower(email))) DO UPDATE
+        SET
+            reason = EXCLUDED.reason,
+            details = EXCLUDED.details,
+            suppressed_at = EXCLUDED.suppressed_at
+        "#,
+        email,
+        reason.as_str(),
+        details
+    );
+    transaction.execute(query).await?;
+
+    let query = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = $2
+        WHERE lower(email) = lower($1)
+        "#,
+        email,
+        reason.subscription_status()
+    );
+    transaction.execute(query).await?;
+
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM subscription_tokens
+        WHERE subscriber_id IN (SELECT id FROM subscriptions WHERE lower(email) = lower($1))
+        "#,
+        email
+    );
+    transaction.execute(query).await?;
+
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE lower(subscriber_email) = lower($1)
+        "#,
+        email
+    );
+    transaction.execute(query).await?;
+
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_failures
+        WHERE lower(subscriber_email) = lower($1)
+        "#,
+        email
+    );
+    transaction.execute(query).await?;
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Whether `email` must not be emailed. Suppressions of addresses that were erased on request
+/// only hold the address's `email_hash`, made with `hash_key`.
+#[tracing::instrument(
+    name = "Check whether an address is suppressed",
+    skip(executor, hash_key)
+)]
+pub async fn is_suppressed<'e>(
+    executor: impl Executor<'e, Database = Postgres>,
+    email: &str,
+    hash_key: &Secret<String>,
+) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT email
+        FROM suppressions
+        WHERE lower(email) = lower($1) OR email = $2
+        "#,
+        email,
+        email_hash(hash_key, email)
+    )
+    .fetch_optional(executor)
+    .await?;
+    Ok(row.is_some())
+}
@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::configuration::RateLimitSettings;
+
+/// How long a task is deferred when its domain already has `domain_concurrency` sends in flight.
+const DOMAIN_BUSY_DELAY: Duration = Duration::from_secs(1);
+
+/// Keeps the delivery workers of this process within the configured send rates.
+///
+/// The limits are tracked in memory, so every process that runs workers gets the full budget.
+pub struct Throttle {
+    settings: RateLimitSettings,
+    state: Mutex<State>,
+}
+
+struct State {
+    global: Option<Pace>,
+    /// Set when the email service asked us to slow down, e.g. with a `Retry-After` header.
+    paused_until: Option<Instant>,
+    domains: HashMap<String, Domain>,
+}
+
+struct Domain {
+    pace: Option<Pace>,
+    in_flight: usize,
+}
+
+impl Throttle {
+    pub fn new(settings: RateLimitSettings) -> Self {
+        // Up to a second's worth of emails may go out at once, so that batches aren't split up
+        let global = settings
+            .messages_per_second
+            .map(|n| Pace::new(Duration::from_secs(1) / n.get(), n.get(), Instant::now()));
+        Self {
+            settings,
+            state: Mutex::new(State {
+                global,
+                paused_until: None,
+                domains: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Take between one and `max` slots from the global budget, or return how long to wait for
+    /// the next one. The slots are given back when dropped, unless they are spent on emails.
+    pub fn acquire(&self, max: usize) -> Result<Slots<'_>, Duration> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        if let Some(paused_until) = state.paused_until {
+            if now < paused_until {
+                return Err(paused_until - now);
+            }
+            state.paused_until = None;
+        }
+        let n = match &mut state.global {
+            Some(pace) => pace.try_take(now, max)?,
+            None => max,
+        };
+        Ok(Slots { throttle: self, n })
+    }
+
+    /// Give back `n` slots taken with `acquire`.
+    fn release(&self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        if let Some(pace) = &mut self.state.lock().unwrap().global {
+            pace.give_back(n);
+        }
+    }
+
+    /// Take a slot for sending to `domain`, or return how long to defer the email.
+    ///
+    /// Emails deferred because of `domain_messages_per_minute` are spread out over the following
+    /// slots, so that they don't all come back at the same time.
+    pub fn acquire_domain(&self, domain: &str) -> Result<DomainPermit<'_>, Duration> {
+        if self.settings.domain_concurrency.is_none()
+            && self.settings.domain_messages_per_minute.is_none()
+        {
+            return Ok(DomainPermit {
+                throttle: self,
+                domain: None,
+            });
+        }
+
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let domain_state = state
+            .domains
+            .entry(domain.to_string())
+            .or_insert_with(|| Domain {
+                pace: self
+                    .settings
+                    .domain_messages_per_minute
+                    .map(|n| Pace::new(Duration::from_secs(60) / n.get(), 1, now)),
+                in_flight: 0,
+            });
+
+        if let Some(concurrency) = self.settings.domain_concurrency
+            && domain_state.in_flight >= concurrency.get()
+        {
+            return Err(DOMAIN_BUSY_DELAY);
+        }
+        if let Some(pace) = &mut domain_state.pace
+            && pace.try_take(now, 1).is_err()
+        {
+            return Err(pace.reserve_later(now));
+        }
+        domain_state.in_flight += 1;
+        Ok(DomainPermit {
+            throttle: self,
+            domain: Some(domain.to_string()),
+        })
+    }
+
+    /// Stop sending anything for `delay`.
+    pub fn pause_for(&self, delay: Duration) {
+        let until = Instant::now() + delay;
+        let mut state = self.state.lock().unwrap();
+        state.paused_until = Some(state.paused_until.map_or(until, |u| u.max(until)));
+    }
+}
+
+/// Slots of the global budget, which are given back when dropped unless they were spent. This
+/// way a batch that fails halfway doesn't use up the budget of the emails it didn't send.
+pub struct Slots<'a> {
+    throttle: &'a Throttle,
+    n: usize,
+}
+
+impl Slots<'_> {
+    pub fn count(&self) -> usize {
+        self.n
+    }
+
+    /// Give back the slots beyond the first `n`.
+    pub fn truncate(&mut self, n: usize) {
+        if n < self.n {
+            self.throttle.release(self.n - n);
+            self.n = n;
+        }
+    }
+
+    /// Keep the slots taken, because as many emails were sent.
+    pub fn spend(mut self) {
+        self.n = 0;
+    }
+}
+
+impl Drop for Slots<'_> {
+    fn drop(&mut self) {
+        self.throttle.release(self.n);
+    }
+}
+
+/// A send to a domain that is in flight, which counts towards `domain_concurrency` until dropped.
+pub struct DomainPermit<'a> {
+    throttle: &'a Throttle,
+    domain: Option<String>,
+}
+
+impl Drop for DomainPermit<'_> {
+    fn drop(&mut self) {
+        let Some(domain) = &self.domain else {
+            return;
+        };
+        let now = Instant::now();
+        let mut state = self.throttle.state.lock().unwrap();
+        if let Some(domain_state) = state.domains.get_mut(domain) {
+            domain_state.in_flight -= 1;
+            // Forget idle domains, otherwise we'd keep every domain we ever sent to
+            let is_idle = domain_state.in_flight == 0
+                && domain_state.pace.as_ref().is_none_or(|p| p.is_idle(now));
+            if is_idle {
+                state.domains.remove(domain);
+            }
+        }
+    }
+}
+
+/// Lets `burst` events happen at once, and one more every `interval` after that.
+struct Pace {
+    interval: Duration,
+    burst: u32,
+    /// When the next event may happen if there was no burst, i.e. the events taken so far are
+    /// paid off.
+    next: Instant,
+    /// When the last of the events deferred by `reserve_later` may happen.
+    reserved_until: Instant,
+}
+
+impl Pace {
+    fn new(interval: Duration, burst: u32, now: Instant) -> Self {
+        Self {
+            // A zero interval would make every division below panic
+            interval: interval.max(Duration::from_nanos(1)),
+            burst,
+            next: now,
+            reserved_until: now,
+        }
+    }
+
+    /// Take between one and `max` events, or return how long until the next one may happen.
+    fn try_take(&mut self, now: Instant, max: usize) -> Result<usize, Duration> {
+        let next = self.next.max(now);
+        let limit = now + self.interval * self.burst;
+        let available = limit.saturating_duration_since(next).as_nanos() / self.interval.as_nanos();
+        if available == 0 {
+            return Err(next + self.interval - limit);
+        }
+        let n = max.min(usize::try_from(available).unwrap_or(usize::MAX));
+        self.next = next + self.interval * u32::try_from(n).unwrap_or(u32::MAX);
+        Ok(n)
+    }
+
+    fn give_back(&mut self, n: usize) {
+        let taken = self.interval * u32::try_from(n).unwrap_or(u32::MAX);
+        self.next = self.next.checked_sub(taken).unwrap_or(self.next);
+    }
+
+    /// Reserve the first slot after the ones that have already been reserved, and return how long
+    /// until it comes.
+    fn reserve_later(&mut self, now: Instant) -> Duration {
+        let slot = self.next.max(self.reserved_until);
+        self.reserved_until = slot + self.interval;
+        slot.saturating_duration_since(now)
+    }
+
+    fn is_idle(&self, now: Instant) -> bool {
+        now >= self.next && now >= self.reserved_until
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::{NonZeroU32, NonZeroUsize};
+    use std::time::Duration;
+
+    use claims::assert_ok;
+
+    use super::Throttle;
+    use crate::configuration::RateLimitSettings;
+
+    #[test]
+    fn nothing_is_throttled_without_limits() {
+        let throttle = Throttle::new(RateLimitSettings::default());
+        for _ in 0..100 {
+            assert_eq!(assert_ok!(throttle.acquire(500)).count(), 500);
+            assert!(throttle.acquire_domain("gmail.com").is_ok());
+        }
+    }
+
+    #[test]
+    fn batches_take_up_to_a_second_of_the_global_budget() {
+        let throttle = Throttle::new(RateLimitSettings {
+            messages_per_second: NonZeroU32::new(10),
+            ..Default::default()
+        });
+
+        let slots = assert_ok!(throttle.acquire(100));
+        assert_eq!(slots.count(), 10);
+        slots.spend();
+        let wait = throttle.acquire(100).err().unwrap();
+        assert!(wait > Duration::ZERO && wait <= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn the_global_budget_is_spaced_out() {
+        let throttle = Throttle::new(RateLimitSettings {
+            messages_per_second: NonZeroU32::new(2),
+            ..Default::default()
+        });
+
+        assert_ok!(throttle.acquire(1)).spend();
+        let slots = assert_ok!(throttle.acquire(5));
+        assert_eq!(slots.count(), 1);
+        slots.spend();
+        let wait = throttle.acquire(1).err().unwrap();
+        assert!(wait > Duration::ZERO && wait <= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn unspent_slots_can_be_taken_again() {
+        let throttle = Throttle::new(RateLimitSettings {
+            messages_per_second: NonZeroU32::new(1),
+            ..Default::default()
+        });
+
+        drop(assert_ok!(throttle.acquire(1)));
+        assert_ok!(throttle.acquire(1)).spend();
+        assert!(throttle.acquire(1).is_err());
+    }
+
+    #[test]
+    fn truncated_slots_can_be_taken_again() {
+        let throttle = Throttle::new(RateLimitSettings {
+            messages_per_second: NonZeroU32::new(10),
+            ..Default::default()
+        });
+
+        let mut slots = assert_ok!(throttle.acquire(10));
+        slots.truncate(4);
+        slots.spend();
+
+        assert_eq!(assert_ok!(throttle.acquire(10)).count(), 6);
+    }
+
+    #[test]
+    fn pausing_stops_all_sends() {
+        let throttle = Throttle::new(RateLimitSettings::default());
+
+        throttle.pause_for(Duration::from_secs(30));
+
+        let wait = throttle.acquire(1).err().unwrap();
+        assert!(wait > Duration::from_secs(29) && wait <= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn concurrent_sends_to_a_domain_are_capped() {
+        let throttle = Throttle::new(RateLimitSettings {
+            domain_concurrency: NonZeroUsize::new(1),
+            ..Default::default()
+        });
+
+        let permit = throttle.acquire_domain("gmail.com");
+        assert!(permit.is_ok());
+        assert!(throttle.acquire_domain("gmail.com").is_err());
+        // Other domains are not affected
+        assert!(throttle.acquire_domain("example.com").is_ok());
+
+        drop(permit);
+        assert!(throttle.acquire_domain("gmail.com").is_ok());
+    }
+
+    #[test]
+    fn deferred_sends_to_a_domain_are_spread_out() {
+        let throttle = Throttle::new(RateLimitSettings {
+            domain_messages_per_minute: NonZeroU32::new(60),
+            ..Default::default()
+        });
+
+        assert!(throttle.acquire_domain("gmail.com").is_ok());
+        let first = throttle.acquire_domain("gmail.com").err().unwrap();
+        let second = throttle.acquire_domain("gmail.com").err().unwrap();
+
+        assert!(first <= Duration::from_secs(1));
+        assert!(second > first && second <= first + Duration::from_secs(1));
+    }
+}
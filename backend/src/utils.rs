@@ -1,5 +1,7 @@
 use actix_web::{HttpResponse, HttpResponseBuilder, ResponseError};
 
+use crate::email_client::EmailError;
+
 pub fn error_chain_fmt(
     e: &impl std::error::Error,
     f: &mut std::fmt::Formatter<'_>,
@@ -18,11 +20,20 @@ pub enum AppError {
     #[error("Invalid form data: '{0}'")]
     BadInputData(String),
 
+    #[error("Invalid template in the newsletter issue")]
+    InvalidTemplate(#[from] minijinja::Error),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    Conflict(String),
+
     #[error("Database error")]
     DatabaseError(#[from] sqlx::Error),
 
     #[error("Unable to send confirmation email")]
-    SendConfirmationEmailError(#[from] reqwest::Error),
+    SendConfirmationEmailError(#[from] EmailError),
 
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
@@ -32,6 +43,9 @@ impl AppError {
     fn response_builder(&self) -> HttpResponseBuilder {
         match self {
             AppError::BadInputData(_) => HttpResponse::BadRequest(),
+            AppError::InvalidTemplate(_) => HttpResponse::BadRequest(),
+            AppError::NotFound(_) => HttpResponse::NotFound(),
+            AppError::Conflict(_) => HttpResponse::Conflict(),
             AppError::DatabaseError(_) => HttpResponse::InternalServerError(),
             AppError::SendConfirmationEmailError(_) => HttpResponse::InternalServerError(),
             AppError::UnexpectedError(_) => HttpResponse::InternalServerError(),
@@ -40,6 +54,9 @@ impl AppError {
     fn error_id(&self) -> &str {
         match self {
             AppError::BadInputData(_) => "invalid_data",
+            AppError::InvalidTemplate(_) => "invalid_template",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
             AppError::DatabaseError(_) => "internal",
             AppError::SendConfirmationEmailError(_) => "send_confirmation_email",
             AppError::UnexpectedError(_) => "internal_error",
@@ -56,8 +73,15 @@ impl std::fmt::Debug for AppError {
 // TODO: De-dupe with SubscribeError etc.
 impl ResponseError for AppError {
     fn error_response(&self) -> HttpResponse {
-        self.response_builder().json(serde_json::json!({
-            "error_id": self.error_id()
-        }))
+        match self {
+            // Tell the editor what is wrong with their template, e.g. "unexpected end of input"
+            AppError::InvalidTemplate(e) => self.response_builder().json(serde_json::json!({
+                "error_id": self.error_id(),
+                "message": e.to_string(),
+            })),
+            _ => self.response_builder().json(serde_json::json!({
+                "error_id": self.error_id()
+            })),
+        }
     }
 }
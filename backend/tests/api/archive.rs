@@ -0,0 +1,222 @@
+use chrono::{TimeDelta, Utc};
+use uuid::Uuid;
+
+use crate::helpers::{TestApp, assert_error_response, assert_successful_response, spawn_app};
+
+#[tokio::test]
+async fn the_archive_lists_published_issues_newest_first() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    app.publish_archived_issue("First issue", false).await;
+    app.publish_archived_issue("Second issue", false).await;
+    app.publish_archived_issue("Third issue", false).await;
+    schedule(&app, "Upcoming issue").await;
+
+    // Act
+    let response = app.get_archive("per_page=2").await;
+
+    // Assert
+    assert_successful_response(&response);
+    let page: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(page["total"], 3);
+    let titles: Vec<_> = page["issues"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|issue| issue["title"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(titles, ["Third issue", "Second issue"]);
+
+    let page: serde_json::Value = app
+        .get_archive("page=2&per_page=2")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(page["issues"].as_array().unwrap().len(), 1);
+    assert_eq!(page["issues"][0]["title"], "First issue");
+}
+
+#[tokio::test]
+async fn invalid_pages_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let page_response = app.get_archive("page=0").await;
+    let size_response = app.get_archive("per_page=1000").await;
+
+    // Assert
+    assert_error_response(page_response, 400, "invalid_data").await;
+    assert_error_response(size_response, 400, "invalid_data").await;
+}
+
+#[tokio::test]
+async fn archived_issues_are_found_by_their_slug() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let slug = app.publish_archived_issue("Hello, World!", false).await;
+
+    // Act
+    let response = app.get_archived_issue(&slug).await;
+
+    // Assert
+    assert!(slug.starts_with("hello-world-"));
+    assert_successful_response(&response);
+    let issue: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(issue["title"], "Hello, World!");
+    // Rendered for nobody in particular, without the unsubscribe footer
+    assert_eq!(issue["content_text"], "Hi there");
+    assert_eq!(issue["content_html"], "<p>Hi there</p>");
+
+    let response = app.get_archived_issue("no-such-issue").await;
+    assert_error_response(response, 404, "not_found").await;
+}
+
+#[tokio::test]
+async fn excluded_issues_are_not_in_the_archive() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let slug = app.publish_archived_issue("Members only", true).await;
+
+    // Act
+    let page: serde_json::Value = app.get_archive("").await.json().await.unwrap();
+    let response = app.get_archived_issue(&slug).await;
+
+    // Assert
+    assert_eq!(page["total"], 0);
+    assert_error_response(response, 404, "not_found").await;
+}
+
+#[tokio::test]
+async fn issues_can_be_taken_out_of_the_archive_and_put_back() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let newsletter_issue_id = app.publish_issue("Oops", false).await;
+    let slug = app.issue_slug(&newsletter_issue_id).await;
+
+    // Act
+    let response = app
+        .put_archive_visibility(
+            &newsletter_issue_id,
+            &serde_json::json!({ "exclude_from_archive": true }),
+        )
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    assert_error_response(app.get_archived_issue(&slug).await, 404, "not_found").await;
+
+    app.put_archive_visibility(
+        &newsletter_issue_id,
+        &serde_json::json!({ "exclude_from_archive": false }),
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+    assert_successful_response(&app.get_archived_issue(&slug).await);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_change_the_archive() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .put_archive_visibility(
+            &Uuid::new_v4().to_string(),
+            &serde_json::json!({ "exclude_from_archive": true }),
+        )
+        .await;
+
+    // Assert
+    assert_error_response(response, 401, "not_logged_in").await;
+}
+
+#[tokio::test]
+async fn the_archive_is_rendered_as_html_pages() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let slug = app.publish_archived_issue("Tips & <tricks>", false).await;
+
+    // Act
+    let index_response = app.get_archive_page("").await;
+    let issue_response = app.get_archived_issue_page(&slug).await;
+
+    // Assert
+    assert_successful_response(&index_response);
+    assert!(
+        index_response.headers()["Content-Type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html")
+    );
+    let index = index_response.text().await.unwrap();
+    assert!(index.contains("Tips &amp; &lt;tricks&gt;"));
+    assert!(index.contains(&format!("/archive/{slug}")));
+
+    assert_successful_response(&issue_response);
+    let page = issue_response.text().await.unwrap();
+    assert!(page.contains("<title>Tips &amp; &lt;tricks&gt;</title>"));
+    // The body is the issue's own HTML
+    assert!(page.contains("<p>Hi there</p>"));
+    let canonical = format!("rel=\"canonical\" href=\"{}/archive/{slug}\"", app.base_url);
+    assert!(page.contains(&canonical));
+
+    let response = app.get_archived_issue_page("no-such-issue").await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn issues_that_do_not_render_are_shown_as_they_were_written() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let slug = app.publish_archived_issue("Before templates", false).await;
+    // Like an issue stored before issues were templates
+    sqlx::query!(
+        "UPDATE newsletter_issues SET content_text = 'Use {{ braces', content_html = '<p>Use {{ braces</p>'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    let response = app.get_archived_issue(&slug).await;
+    let page_response = app.get_archived_issue_page(&slug).await;
+    let feed_response = app.get_feed("feed.rss", &[]).await;
+
+    // Assert
+    assert_successful_response(&response);
+    let issue: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(issue["content_text"], "Use {{ braces");
+    assert_eq!(issue["content_html"], "<p>Use {{ braces</p>");
+    assert_successful_response(&page_response);
+    assert_successful_response(&feed_response);
+    assert!(
+        feed_response
+            .text()
+            .await
+            .unwrap()
+            .contains("&lt;p&gt;Use {{ braces&lt;/p&gt;")
+    );
+}
+
+async fn schedule(app: &TestApp, title: &str) {
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": title,
+            "content_text": "Soon",
+            "content_html": "<p>Soon</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "send_at": (Utc::now() + TimeDelta::days(1)).to_rfc3339(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+}
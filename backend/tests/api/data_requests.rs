@@ -0,0 +1,371 @@
+use uuid::Uuid;
+use wiremock::{Mock, ResponseTemplate, matchers::any};
+
+use crate::helpers::{
+    TestApp, assert_error_response, assert_successful_response, create_confirmed_subscriber,
+    spawn_app,
+};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_handle_data_requests() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = serde_json::json!({ "email": "ursula@example.com" });
+
+    // Act
+    let responses = vec![
+        app.post_data_access_request(&body).await,
+        app.post_data_erasure_request(&body).await,
+        app.get_data_requests("").await,
+    ];
+
+    // Assert
+    for response in responses {
+        assert_error_response(response, 401, "not_logged_in").await;
+    }
+}
+
+#[tokio::test]
+async fn the_access_report_has_everything_stored_about_the_address() {
+    // Arrange
+    let app = spawn_app().await;
+    let email = subscriber_with_a_delivery(&app).await;
+    sqlx::query!(
+        "INSERT INTO suppressions (email, reason, suppressed_at) VALUES ($1, 'spam_complaint', now())",
+        email
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    let response = app
+        .post_data_access_request(&serde_json::json!({ "email": email }))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert!(report["request_id"].is_string());
+    assert_eq!(report["email"], email);
+    assert_eq!(report["subscription"]["email"], email);
+    assert_eq!(report["subscription"]["status"], "confirmed");
+    assert_eq!(report["subscription_tokens"].as_array().unwrap().len(), 1);
+    assert!(report["unsubscribe_token"].is_string());
+    let deliveries = report["deliveries"].as_array().unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0]["title"], "Newsletter title");
+    assert_eq!(deliveries[0]["outcome"], "sent");
+    assert_eq!(report["queued_deliveries"], serde_json::json!([]));
+    assert_eq!(report["delivery_failures"], serde_json::json!([]));
+    assert_eq!(report["suppression"]["reason"], "spam_complaint");
+}
+
+#[tokio::test]
+async fn requests_about_unknown_addresses_are_not_recorded_as_fulfilled() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let body = serde_json::json!({ "email": "nobody@example.com" });
+
+    // Act
+    let access_response = app.post_data_access_request(&body).await;
+    let erasure_response = app.post_data_erasure_request(&body).await;
+
+    // Assert
+    assert_error_response(access_response, 404, "not_found").await;
+    assert_error_response(erasure_response, 404, "not_found").await;
+    let requests: serde_json::Value = app.get_data_requests("").await.json().await.unwrap();
+    assert_eq!(requests, serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn erasure_removes_the_address_from_every_table() {
+    // Arrange
+    let app = spawn_app().await;
+    let email = subscriber_with_a_delivery(&app).await;
+    let newsletter_issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_failures (
+            newsletter_issue_id, subscriber_email, n_retries, last_error, failed_at
+        )
+        VALUES ($1, $2, 5, 'Mailbox full', now())
+        "#,
+        newsletter_issue_id,
+        email
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "INSERT INTO suppressions (email, reason, suppressed_at) VALUES ($1, 'hard_bounce', now())",
+        email
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    // E.g. a saved response that listed the address
+    sqlx::query!(
+        r#"
+        INSERT INTO idempotency (user_id, idempotency_key, response_body, created_at)
+        VALUES ($1, $2, convert_to($3, 'UTF8'), now())
+        "#,
+        app.test_user.user_id,
+        Uuid::new_v4().to_string(),
+        format!(r#"{{"recipients":["{email}"]}}"#)
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    // Saved responses that aren't text are skipped rather than failing the erasure
+    sqlx::query!(
+        r#"
+        INSERT INTO idempotency (user_id, idempotency_key, response_body, created_at)
+        VALUES ($1, $2, '\xfffe'::bytea, now())
+        "#,
+        app.test_user.user_id,
+        Uuid::new_v4().to_string()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    // The same issue delivered to another spelling of the address
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_deliveries (
+            newsletter_issue_id, subscriber_email, n_attempts, outcome, first_attempted_at,
+            last_attempted_at
+        )
+        VALUES ($1, $2, 1, 'sent', now(), now())
+        "#,
+        newsletter_issue_id,
+        email.to_uppercase()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    // Addresses are matched regardless of case
+    let response = app
+        .post_data_erasure_request(&serde_json::json!({ "email": email.to_uppercase() }))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let request_id = body["request_id"].as_str().unwrap();
+    assert_eq!(
+        body["erased"],
+        serde_json::json!({
+            "subscriptions": 1,
+            "pending_email_changes": 0,
+            "queued_deliveries": 0,
+            "delivery_failures": 1,
+            "pseudonymized_deliveries": 2,
+            "suppressions": 1,
+            "idempotency_records": 1,
+        })
+    );
+
+    let remaining = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT count(*) FROM subscriptions) AS "subscriptions!",
+            (SELECT count(*) FROM subscription_tokens) AS "subscription_tokens!",
+            (SELECT count(*) FROM unsubscribe_tokens) AS "unsubscribe_tokens!",
+            (SELECT count(*) FROM issue_delivery_failures) AS "delivery_failures!",
+            (
+                SELECT count(*) FROM suppressions
+                WHERE position(lower($1) IN lower(coalesce(details, '') || email)) > 0
+            ) AS "suppressions!",
+            (
+                SELECT count(*) FROM idempotency
+                WHERE position(convert_to($1, 'UTF8') IN response_body) > 0
+            ) AS "idempotency!"
+        "#,
+        email
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(remaining.subscriptions, 0);
+    assert_eq!(remaining.subscription_tokens, 0);
+    assert_eq!(remaining.unsubscribe_tokens, 0);
+    assert_eq!(remaining.delivery_failures, 0);
+    assert_eq!(remaining.suppressions, 0);
+    assert_eq!(remaining.idempotency, 0);
+
+    // The deliveries still count towards the issue's statistics
+    let deliveries = sqlx::query!(
+        r#"
+        SELECT subscriber_email, outcome, provider_message_id
+        FROM issue_deliveries
+        ORDER BY subscriber_email
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(deliveries.len(), 2);
+    assert_eq!(
+        deliveries[0].subscriber_email,
+        format!("erased-{request_id}-1")
+    );
+    assert_eq!(
+        deliveries[1].subscriber_email,
+        format!("erased-{request_id}-2")
+    );
+    assert_eq!(deliveries[0].outcome, "sent");
+    assert_eq!(deliveries[0].provider_message_id, None);
+
+    let response = app
+        .post_data_access_request(&serde_json::json!({ "email": email }))
+        .await;
+    assert_error_response(response, 404, "not_found").await;
+}
+
+#[tokio::test]
+async fn erased_addresses_that_were_suppressed_are_still_not_emailed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    sqlx::query!(
+        r#"
+        INSERT INTO suppressions (email, reason, details, suppressed_at)
+        VALUES ('ursula@example.com', 'hard_bounce', 'No such user ursula@example.com', now())
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.post_data_erasure_request(&serde_json::json!({ "email": "ursula@example.com" }))
+        .await
+        .error_for_status()
+        .unwrap();
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions(serde_json::json!({
+            "name": "Ursula",
+            "email": "Ursula@example.com",
+        }))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    let suppression = sqlx::query!("SELECT email, details FROM suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(!suppression.email.contains("ursula"));
+    assert_eq!(suppression.details, None);
+    let subscriptions = sqlx::query!(r#"SELECT count(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriptions.count, 0);
+}
+
+#[tokio::test]
+async fn fulfilled_requests_are_recorded_without_the_address() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    for email in ["ursula@example.com", "octavia@example.com"] {
+        sqlx::query!(
+            "INSERT INTO suppressions (email, reason, suppressed_at) VALUES ($1, 'hard_bounce', now())",
+            email
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+    let body = serde_json::json!({ "email": "ursula@example.com" });
+    let response = app.post_data_access_request(&body).await;
+    assert_successful_response(&response);
+    let response = app.post_data_erasure_request(&body).await;
+    assert_successful_response(&response);
+    let response = app
+        .post_data_access_request(&serde_json::json!({ "email": "octavia@example.com" }))
+        .await;
+    assert_successful_response(&response);
+
+    // Act
+    let response = app.get_data_requests("email=Ursula@Example.com").await;
+
+    // Assert
+    assert_successful_response(&response);
+    let requests: serde_json::Value = response.json().await.unwrap();
+    let requests = requests.as_array().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0]["kind"], "erasure");
+    assert_eq!(requests[1]["kind"], "access");
+    for request in requests {
+        assert_eq!(request["fulfilled_by"], app.test_user.username);
+        assert_eq!(request["email_hash"], requests[0]["email_hash"]);
+        assert!(!request.to_string().contains("ursula"));
+    }
+
+    let response = app.get_data_requests("").await;
+    let requests: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(requests.as_array().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn data_requests_return_400_without_an_email() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let body = serde_json::json!({ "email": "  " });
+
+    // Act
+    let responses = vec![
+        app.post_data_access_request(&body).await,
+        app.post_data_erasure_request(&body).await,
+    ];
+
+    // Assert
+    for response in responses {
+        assert_error_response(response, 400, "invalid_data").await;
+    }
+}
+
+/// A confirmed subscriber who was sent one issue. Leaves the app logged in.
+async fn subscriber_with_a_delivery(app: &TestApp) -> String {
+    create_confirmed_subscriber(app).await;
+    app.login().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "ErrorCode": 0,
+            "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817",
+        })))
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "content_text": "Newsletter body as plain text",
+            "content_html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_successful_response(&response);
+    app.dispatch_all_pending_emails().await;
+
+    sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email
+}
@@ -0,0 +1,403 @@
+use uuid::Uuid;
+use wiremock::{
+    Mock, Request, Respond, ResponseTemplate,
+    matchers::{any, path},
+};
+
+use crate::helpers::{
+    TestApp, assert_error_response, assert_successful_response, create_confirmed_subscriber,
+    spawn_app,
+};
+
+#[tokio::test]
+async fn transient_delivery_failures_are_retried_later() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+
+    // Act - Part 1 - The first attempt fails
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let task = sqlx::query!(
+        "SELECT n_retries, execute_after > now() AS \"postponed!\" FROM issue_delivery_queue"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("The task should still be queued");
+    assert_eq!(task.n_retries, 1);
+    assert!(task.postponed);
+
+    // Act - Part 2 - The retry succeeds once it is due
+    make_queued_tasks_due(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(queued_task_count(&app).await, 0);
+    // Mock verifies on Drop that we have attempted delivery twice
+}
+
+#[tokio::test]
+async fn deliveries_that_exhaust_their_retries_are_moved_to_failures() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+
+    // Act
+    for _ in 0..20 {
+        app.dispatch_all_pending_emails().await;
+        make_queued_tasks_due(&app).await;
+    }
+
+    // Assert
+    assert_eq!(queued_task_count(&app).await, 0);
+
+    let response = app.get_delivery_failures().await;
+    assert_successful_response(&response);
+    let failures: Vec<serde_json::Value> = response.json().await.unwrap();
+    assert_eq!(failures.len(), 1);
+    assert!(failures[0]["n_retries"].as_i64().unwrap() > 0);
+    assert!(failures[0]["last_error"].as_str().unwrap().contains("503"));
+}
+
+#[tokio::test]
+async fn permanent_delivery_failures_are_not_retried() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(422))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(queued_task_count(&app).await, 0);
+
+    let failures: Vec<serde_json::Value> = app.get_delivery_failures().await.json().await.unwrap();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0]["n_retries"], 0);
+}
+
+#[tokio::test]
+async fn rate_limited_deliveries_are_deferred_without_using_up_retries() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "120"))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let task = sqlx::query!(
+        "SELECT n_retries, execute_after > now() + interval '1 minute' AS \"postponed!\" FROM issue_delivery_queue"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("The task should still be queued");
+    assert_eq!(task.n_retries, 0);
+    assert!(task.postponed);
+}
+
+#[tokio::test]
+async fn issues_are_delivered_in_batches() {
+    // Arrange
+    let app = spawn_app().await;
+    for _ in 0..3 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.login().await;
+
+    Mock::given(path("/email/batch"))
+        .respond_with(BatchResponder {
+            error_codes: vec![],
+        })
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+
+    // Act
+    app.dispatch_all_pending_emails_in_batches_of(10).await;
+
+    // Assert
+    assert_eq!(queued_task_count(&app).await, 0);
+    let deliveries = sqlx::query!("SELECT outcome, provider_message_id FROM issue_deliveries")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(deliveries.len(), 3);
+    for delivery in deliveries {
+        assert_eq!(delivery.outcome, "sent");
+        assert!(delivery.provider_message_id.is_some());
+    }
+}
+
+#[tokio::test]
+async fn failures_within_a_batch_are_handled_per_recipient() {
+    // Arrange
+    let app = spawn_app().await;
+    for _ in 0..3 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.login().await;
+
+    // The first email is rejected for good, the second one may succeed later
+    Mock::given(path("/email/batch"))
+        .respond_with(BatchResponder {
+            error_codes: vec![406, 405],
+        })
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+
+    // Act
+    app.dispatch_all_pending_emails_in_batches_of(10).await;
+
+    // Assert
+    let failures: Vec<serde_json::Value> = app.get_delivery_failures().await.json().await.unwrap();
+    assert_eq!(failures.len(), 1);
+    assert!(failures[0]["last_error"].as_str().unwrap().contains("406"));
+
+    let task = sqlx::query!("SELECT n_retries FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("The transient failure should still be queued");
+    assert_eq!(task.n_retries, 1);
+
+    let sent =
+        sqlx::query!("SELECT count(*) AS \"count!\" FROM issue_deliveries WHERE outcome = 'sent'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(sent.count, 1);
+}
+
+#[tokio::test]
+async fn delivery_failures_can_be_requeued() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    let mock_guard = Mock::given(any())
+        .respond_with(ResponseTemplate::new(422))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+    drop(mock_guard);
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_requeue_delivery_failures(&serde_json::json!({}))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["requeued"], 1);
+
+    let failures: Vec<serde_json::Value> = app.get_delivery_failures().await.json().await.unwrap();
+    assert!(failures.is_empty());
+
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(queued_task_count(&app).await, 0);
+    // Mock verifies on Drop that the requeued issue was delivered
+}
+
+#[tokio::test]
+async fn failures_of_addresses_that_unsubscribed_since_are_not_requeued() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    let mock_guard = Mock::given(any())
+        .respond_with(ResponseTemplate::new(422))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+    drop(mock_guard);
+
+    let unsubscribe_token = sqlx::query!("SELECT unsubscribe_token FROM unsubscribe_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .unsubscribe_token;
+    reqwest::get(format!(
+        "{}/api/subscriptions/unsubscribe?unsubscribe_token={unsubscribe_token}",
+        app.address
+    ))
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_requeue_delivery_failures(&serde_json::json!({}))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["requeued"], 0);
+
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(queued_task_count(&app).await, 0);
+    // Mock verifies on Drop that nothing was sent
+}
+
+#[tokio::test]
+async fn queued_deliveries_to_addresses_that_unsubscribed_since_are_dropped() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    // Behind the back of the unsubscribe endpoint, which would also empty the queue
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(queued_task_count(&app).await, 0);
+    let failures: Vec<serde_json::Value> = app.get_delivery_failures().await.json().await.unwrap();
+    assert!(failures.is_empty());
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_delivery_failures() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_delivery_failures().await;
+
+    // Assert
+    assert_error_response(response, 401, "not_logged_in").await;
+}
+
+async fn publish_newsletter(app: &TestApp) {
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content_text": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_successful_response(&response);
+}
+
+/// Skip the backoff delay of any rescheduled tasks.
+async fn make_queued_tasks_due(app: &TestApp) {
+    sqlx::query!("UPDATE issue_delivery_queue SET execute_after = now()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+async fn queued_task_count(app: &TestApp) -> i64 {
+    sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count
+}
+
+/// Answers Postmark batch requests with one result per email, failing the emails at the start of
+/// the batch with `error_codes`.
+struct BatchResponder {
+    error_codes: Vec<i64>,
+}
+
+impl Respond for BatchResponder {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let emails: Vec<serde_json::Value> = serde_json::from_slice(&request.body).unwrap();
+        let results: Vec<_> = (0..emails.len())
+            .map(|i| match self.error_codes.get(i) {
+                Some(error_code) => serde_json::json!({
+                    "ErrorCode": error_code,
+                    "Message": "Rejected",
+                }),
+                None => serde_json::json!({
+                    "ErrorCode": 0,
+                    "Message": "OK",
+                    "MessageID": Uuid::new_v4().to_string(),
+                }),
+            })
+            .collect();
+        ResponseTemplate::new(200).set_body_json(results)
+    }
+}
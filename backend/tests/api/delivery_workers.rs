@@ -0,0 +1,167 @@
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+use wiremock::{Mock, ResponseTemplate, matchers::any};
+use zero2prod::{
+    configuration::{RateLimitSettings, WorkerSettings},
+    issue_delivery_worker::run_workers,
+};
+
+use crate::helpers::{TestApp, assert_successful_response, create_confirmed_subscriber, spawn_app};
+
+#[tokio::test]
+async fn a_pool_of_workers_delivers_the_issue_to_every_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    for _ in 0..5 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(5)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = publish_newsletter(&app).await;
+
+    // Act
+    let shutdown = CancellationToken::new();
+    let workers = spawn_workers(&app, shutdown.clone());
+    wait_for_status(&app, issue_id, "completed").await;
+    shutdown.cancel();
+
+    // Assert
+    tokio::time::timeout(Duration::from_secs(5), workers)
+        .await
+        .expect("The workers didn't stop after shutdown")
+        .unwrap()
+        .unwrap();
+    // Mock verifies on Drop that every subscriber got exactly one email
+}
+
+#[tokio::test]
+async fn idle_workers_are_woken_up_when_an_issue_is_published() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let shutdown = CancellationToken::new();
+    let workers = spawn_workers(&app, shutdown.clone());
+    // Let the workers find the queue empty and go to sleep
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // Act
+    let issue_id = publish_newsletter(&app).await;
+
+    // Assert
+    // Much sooner than `empty_queue_sleep_milliseconds`
+    wait_for_status(&app, issue_id, "completed").await;
+    shutdown.cancel();
+    workers.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn deliveries_in_flight_are_finished_on_shutdown() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(500)))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    // Includes the confirmation email
+    let n_requests = app.email_server.received_requests().await.unwrap().len();
+
+    // Act
+    let shutdown = CancellationToken::new();
+    let workers = spawn_workers(&app, shutdown.clone());
+    while app.email_server.received_requests().await.unwrap().len() == n_requests {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    shutdown.cancel();
+    workers.await.unwrap().unwrap();
+
+    // Assert
+    let delivery = sqlx::query!("SELECT outcome FROM issue_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(delivery.outcome, "sent");
+    let queued = sqlx::query!("SELECT count(*) AS \"count!\" FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.count, 0);
+}
+
+fn spawn_workers(app: &TestApp, shutdown: CancellationToken) -> JoinHandle<anyhow::Result<()>> {
+    let settings = WorkerSettings {
+        concurrency: NonZeroUsize::new(3).unwrap(),
+        // One email per request, so that the mocks count deliveries
+        batch_size: NonZeroUsize::new(1).unwrap(),
+        poll_interval_milliseconds: 50,
+        // Long enough that the tests would time out if shutdown didn't interrupt the sleep
+        empty_queue_sleep_milliseconds: 60_000,
+        rate_limit: RateLimitSettings::default(),
+    };
+    tokio::spawn(run_workers(
+        app.db_pool.clone(),
+        app.email_client.clone(),
+        app.base_url.clone(),
+        settings,
+        shutdown,
+    ))
+}
+
+async fn wait_for_status(app: &TestApp, newsletter_issue_id: Uuid, status: &str) {
+    for _ in 0..200 {
+        let issue = sqlx::query!(
+            "SELECT status FROM newsletter_issues WHERE newsletter_issue_id = $1",
+            newsletter_issue_id
+        )
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+        if issue.status == status {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("The newsletter issue never reached the '{status}' status");
+}
+
+/// Publish a newsletter right away and return its id.
+async fn publish_newsletter(app: &TestApp) -> Uuid {
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "content_text": "Newsletter body as plain text",
+            "content_html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_successful_response(&response);
+    let body: serde_json::Value = response.json().await.unwrap();
+    body["newsletter_issue_id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap()
+}
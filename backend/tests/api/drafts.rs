@@ -0,0 +1,241 @@
+use uuid::Uuid;
+use wiremock::{Mock, ResponseTemplate, matchers::any};
+
+use crate::helpers::{
+    TestApp, assert_error_response, assert_successful_response, create_confirmed_subscriber,
+    spawn_app,
+};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_drafts() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_draft(&serde_json::json!({})).await;
+
+    // Assert
+    assert_error_response(response, 401, "not_logged_in").await;
+}
+
+#[tokio::test]
+async fn drafts_can_be_created_and_retrieved() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    let draft_id = create_draft(&app).await;
+    let response = app.get_draft(&draft_id).await;
+
+    // Assert
+    assert_successful_response(&response);
+    let draft: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(draft["title"], "Draft title");
+    assert_eq!(draft["content_text"], "Draft body as plain text");
+    assert_eq!(draft["content_html"], "<p>Draft body as HTML</p>");
+
+    let drafts: Vec<serde_json::Value> = app.get_drafts().await.json().await.unwrap();
+    assert_eq!(drafts.len(), 1);
+    assert_eq!(drafts[0]["draft_id"], draft_id);
+}
+
+#[tokio::test]
+async fn updating_a_draft_only_changes_the_given_fields() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let draft_id = create_draft(&app).await;
+
+    // Act
+    let response = app
+        .patch_draft(&draft_id, &serde_json::json!({ "title": "New title" }))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    let draft: serde_json::Value = app.get_draft(&draft_id).await.json().await.unwrap();
+    assert_eq!(draft["title"], "New title");
+    assert_eq!(draft["content_text"], "Draft body as plain text");
+    assert_eq!(draft["content_html"], "<p>Draft body as HTML</p>");
+    assert_ne!(draft["updated_at"], draft["created_at"]);
+}
+
+#[tokio::test]
+async fn deleted_drafts_are_gone() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let draft_id = create_draft(&app).await;
+
+    // Act
+    let response = app.delete_draft(&draft_id).await;
+
+    // Assert
+    assert_successful_response(&response);
+    assert_error_response(app.get_draft(&draft_id).await, 404, "not_found").await;
+    assert_error_response(app.delete_draft(&draft_id).await, 404, "not_found").await;
+}
+
+#[tokio::test]
+async fn unknown_drafts_return_404() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let draft_id = Uuid::new_v4().to_string();
+
+    // Act
+    let response = app
+        .patch_draft(&draft_id, &serde_json::json!({ "title": "New title" }))
+        .await;
+
+    // Assert
+    assert_error_response(response, 404, "not_found").await;
+}
+
+#[tokio::test]
+async fn preview_matches_the_email_that_is_sent() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+    let draft_id = create_draft(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.get_draft_preview(&draft_id).await;
+    assert_successful_response(&response);
+    let preview: serde_json::Value = response.json().await.unwrap();
+
+    publish_draft(&app, &draft_id).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let received_requests = app.email_server.received_requests().await.unwrap();
+    let email_request = received_requests.last().unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let unsubscribe_token = app
+        .get_unsubscribe_link(email_request)
+        .query_pairs()
+        .find(|(name, _)| name == "unsubscribe_token")
+        .unwrap()
+        .1
+        .into_owned();
+    let with_preview_token = |s: &str| s.replace(&unsubscribe_token, "preview");
+
+    assert_eq!(preview["subject"], email["Subject"]);
+    assert_eq!(
+        preview["content_html"].as_str().unwrap(),
+        with_preview_token(email["HtmlBody"].as_str().unwrap())
+    );
+    assert_eq!(
+        preview["content_text"].as_str().unwrap(),
+        with_preview_token(email["TextBody"].as_str().unwrap())
+    );
+}
+
+#[tokio::test]
+async fn publishing_a_draft_delivers_it_and_removes_the_draft() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+    let draft_id = create_draft(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    publish_draft(&app, &draft_id).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_error_response(app.get_draft(&draft_id).await, 404, "not_found").await;
+    // Mock verifies on Drop that we have sent the newsletter email
+}
+
+#[tokio::test]
+async fn publishing_a_draft_is_idempotent() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+    let draft_id = create_draft(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let body = serde_json::json!({ "idempotency_key": Uuid::new_v4().to_string() });
+
+    // Act - Publish the same draft twice concurrently, as with a double click
+    let (response1, response2) = tokio::join!(
+        app.post_publish_draft(&draft_id, &body),
+        app.post_publish_draft(&draft_id, &body)
+    );
+
+    // Assert
+    assert_successful_response(&response1);
+    assert_successful_response(&response2);
+
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that we have sent the newsletter email only once.
+}
+
+#[tokio::test]
+async fn incomplete_drafts_cannot_be_published() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let response = app
+        .post_draft(&serde_json::json!({ "title": "Only a title" }))
+        .await;
+    let draft: serde_json::Value = response.json().await.unwrap();
+    let draft_id = draft["draft_id"].as_str().unwrap();
+
+    // Act
+    let response = app
+        .post_publish_draft(
+            draft_id,
+            &serde_json::json!({ "idempotency_key": Uuid::new_v4().to_string() }),
+        )
+        .await;
+
+    // Assert
+    assert_error_response(response, 400, "invalid_data").await;
+    assert_successful_response(&app.get_draft(draft_id).await);
+}
+
+/// Create a complete draft and return its id.
+async fn create_draft(app: &TestApp) -> String {
+    let response = app
+        .post_draft(&serde_json::json!({
+            "title": "Draft title",
+            "content_text": "Draft body as plain text",
+            "content_html": "<p>Draft body as HTML</p>",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let draft: serde_json::Value = response.json().await.unwrap();
+    draft["draft_id"].as_str().unwrap().to_string()
+}
+
+async fn publish_draft(app: &TestApp, draft_id: &str) {
+    let response = app
+        .post_publish_draft(
+            draft_id,
+            &serde_json::json!({ "idempotency_key": Uuid::new_v4().to_string() }),
+        )
+        .await;
+    assert_successful_response(&response);
+}
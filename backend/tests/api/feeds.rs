@@ -0,0 +1,127 @@
+use crate::helpers::{assert_successful_response, spawn_app};
+
+#[tokio::test]
+async fn the_rss_feed_lists_the_latest_issues() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    app.publish_archived_issue("First issue", false).await;
+    app.publish_archived_issue("Second issue", false).await;
+    let slug = app.publish_archived_issue("Tips & <tricks>", false).await;
+
+    // Act
+    let response = app.get_feed("feed.rss", &[]).await;
+
+    // Assert
+    assert_successful_response(&response);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/rss+xml; charset=utf-8"
+    );
+    let feed = response.text().await.unwrap();
+    // The feeds are limited to two items in tests
+    assert_eq!(feed.matches("<item>").count(), 2);
+    assert!(!feed.contains("First issue"));
+    let newest = feed
+        .find("<title>Tips &amp; &lt;tricks&gt;</title>")
+        .unwrap();
+    let older = feed.find("<title>Second issue</title>").unwrap();
+    assert!(newest < older);
+    let permalink = format!("{}/archive/{slug}", app.base_url);
+    assert!(feed.contains(&format!("<link>{permalink}</link>")));
+    assert!(feed.contains(&format!("<guid isPermaLink=\"true\">{permalink}</guid>")));
+    // The issue's HTML, rendered for the archive and escaped
+    assert!(feed.contains("<description>&lt;p&gt;Hi there&lt;/p&gt;</description>"));
+}
+
+#[tokio::test]
+async fn the_atom_feed_lists_the_latest_issues() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let slug = app.publish_archived_issue("Tips & <tricks>", false).await;
+
+    // Act
+    let response = app.get_feed("feed.atom", &[]).await;
+
+    // Assert
+    assert_successful_response(&response);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/atom+xml; charset=utf-8"
+    );
+    let feed = response.text().await.unwrap();
+    assert_eq!(feed.matches("<entry>").count(), 1);
+    assert!(feed.contains("<title>Tips &amp; &lt;tricks&gt;</title>"));
+    assert!(feed.contains(&format!("<id>{}/archive/{slug}</id>", app.base_url)));
+    assert!(feed.contains("<content type=\"html\">&lt;p&gt;Hi there&lt;/p&gt;</content>"));
+}
+
+#[tokio::test]
+async fn issues_excluded_from_the_archive_are_not_in_the_feeds() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    app.publish_archived_issue("Members only", true).await;
+
+    // Act
+    let rss = app.get_feed("feed.rss", &[]).await.text().await.unwrap();
+    let atom = app.get_feed("feed.atom", &[]).await.text().await.unwrap();
+
+    // Assert
+    assert!(!rss.contains("<item>"));
+    assert!(!atom.contains("<entry>"));
+}
+
+#[tokio::test]
+async fn feeds_the_reader_already_has_are_not_sent_again() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    app.publish_archived_issue("First issue", false).await;
+    let response = app.get_feed("feed.rss", &[]).await;
+    let etag = response.headers()["ETag"].to_str().unwrap().to_string();
+    let last_modified = response.headers()["Last-Modified"]
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    // Act
+    let etag_response = app.get_feed("feed.rss", &[("If-None-Match", &etag)]).await;
+    let date_response = app
+        .get_feed("feed.rss", &[("If-Modified-Since", &last_modified)])
+        .await;
+
+    // Assert
+    assert_eq!(etag_response.status().as_u16(), 304);
+    assert_eq!(etag_response.headers()["ETag"], etag.as_str());
+    assert!(etag_response.text().await.unwrap().is_empty());
+    assert_eq!(date_response.status().as_u16(), 304);
+}
+
+#[tokio::test]
+async fn feeds_are_sent_again_once_they_changed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let newsletter_issue_id = app.publish_issue("First issue", false).await;
+    let response = app.get_feed("feed.atom", &[]).await;
+    let etag = response.headers()["ETag"].to_str().unwrap().to_string();
+
+    // Act
+    // Taking an issue out of the archive doesn't change when the feed was last published to,
+    // so only the ETag can tell
+    app.put_archive_visibility(
+        &newsletter_issue_id,
+        &serde_json::json!({ "exclude_from_archive": true }),
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+    let response = app.get_feed("feed.atom", &[("If-None-Match", &etag)]).await;
+
+    // Assert
+    assert_successful_response(&response);
+    assert_ne!(response.headers()["ETag"], etag.as_str());
+    assert!(!response.text().await.unwrap().contains("<entry>"));
+}
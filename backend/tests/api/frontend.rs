@@ -13,3 +13,41 @@ async fn index_html_file_served_by_app() {
     // Assert
     assert!(response_html.contains("<body"));
 }
+
+#[tokio::test]
+async fn frontend_pages_are_served_the_index_html_file() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/preferences?unsubscribe_token=abc",
+            &app.address
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("<body"));
+}
+
+#[tokio::test]
+async fn missing_files_are_not_found() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/assets/missing.js", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
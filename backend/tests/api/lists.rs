@@ -0,0 +1,366 @@
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{any, method, path},
+};
+
+use crate::helpers::{
+    TestApp, assert_error_response, assert_successful_response, create_confirmed_subscriber,
+    spawn_app,
+};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_lists() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let list_response = app.get_lists().await;
+    let create_response = app
+        .post_list(&serde_json::json!({ "name": "Weekly digest" }))
+        .await;
+
+    // Assert
+    assert_error_response(list_response, 401, "not_logged_in").await;
+    assert_error_response(create_response, 401, "not_logged_in").await;
+}
+
+#[tokio::test]
+async fn created_lists_are_listed_next_to_the_default_list() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    create_confirmed_subscriber(&app).await;
+
+    // Act
+    let response = app
+        .post_list(&serde_json::json!({
+            "name": "Weekly digest",
+            "description": "The best of the week, every Friday",
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 201);
+    let list: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(list["name"], "Weekly digest");
+    assert_eq!(list["is_default"], false);
+
+    let lists: serde_json::Value = app.get_lists().await.json().await.unwrap();
+    let lists = lists.as_array().unwrap();
+    assert_eq!(lists.len(), 2);
+    let default_list = lists.iter().find(|l| l["is_default"] == true).unwrap();
+    assert_eq!(default_list["confirmed_members"], 1);
+    let digest = lists.iter().find(|l| l["name"] == "Weekly digest").unwrap();
+    assert_eq!(digest["confirmed_members"], 0);
+
+    let public_lists: serde_json::Value = app.get_public_lists().await.json().await.unwrap();
+    assert_eq!(public_lists.as_array().unwrap().len(), 2);
+    assert_eq!(public_lists[0]["is_default"], true);
+}
+
+#[tokio::test]
+async fn lists_need_a_unique_name() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let response = app
+        .post_list(&serde_json::json!({ "name": "Weekly digest" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    // Act
+    let duplicate_response = app
+        .post_list(&serde_json::json!({ "name": " Weekly digest " }))
+        .await;
+    let empty_response = app.post_list(&serde_json::json!({ "name": "  " })).await;
+
+    // Assert
+    assert_error_response(duplicate_response, 409, "conflict").await;
+    assert_error_response(empty_response, 400, "invalid_data").await;
+}
+
+#[tokio::test]
+async fn subscribers_confirm_each_list_they_join() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let digest_id = create_list(&app, "Weekly digest").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    let body = serde_json::json!({
+        "name": "le guin",
+        "email": "ursula_le_guin@gmail.com",
+    });
+    app.post_subscriptions(body).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    reqwest::get(app.get_confirmation_links(email_request).html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let body = serde_json::json!({
+        "name": "le guin",
+        "email": "ursula_le_guin@gmail.com",
+        "list_id": digest_id,
+    });
+    let response = app.post_subscriptions(body).await;
+
+    // Assert
+    assert_successful_response(&response);
+    assert_eq!(
+        memberships(&app).await,
+        [
+            ("Newsletter".to_string(), "confirmed".to_string()),
+            (
+                "Weekly digest".to_string(),
+                "pending_confirmation".to_string()
+            ),
+        ]
+    );
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[1];
+    reqwest::get(app.get_confirmation_links(email_request).html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(
+        memberships(&app).await,
+        [
+            ("Newsletter".to_string(), "confirmed".to_string()),
+            ("Weekly digest".to_string(), "confirmed".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn subscribing_to_an_unknown_list_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "name": "le guin",
+        "email": "ursula_le_guin@gmail.com",
+        "list_id": Uuid::new_v4(),
+    });
+
+    // Act
+    let response = app.post_subscriptions(body).await;
+
+    // Assert
+    assert_error_response(response, 400, "invalid_data").await;
+}
+
+#[tokio::test]
+async fn issues_are_delivered_to_the_confirmed_members_of_their_list() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let digest_id = create_list(&app, "Weekly digest").await;
+    // Only on the default list
+    create_confirmed_subscriber(&app).await;
+    subscribe(&app, "member@example.com", &digest_id, true).await;
+    subscribe(&app, "pending@example.com", &digest_id, false).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "content_text": "Newsletter body as plain text",
+            "content_html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "list_id": digest_id,
+        }))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    app.dispatch_all_pending_emails().await;
+
+    let deliveries = sqlx::query!("SELECT subscriber_email FROM issue_deliveries")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].subscriber_email, "member@example.com");
+}
+
+#[tokio::test]
+async fn issues_sent_to_several_lists_reach_each_member_once() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let digest_id = create_list(&app, "Weekly digest").await;
+    let announcements_id = create_list(&app, "Announcements").await;
+    // Only on the default list
+    create_confirmed_subscriber(&app).await;
+    subscribe(&app, "both@example.com", &digest_id, true).await;
+    subscribe(&app, "both@example.com", &announcements_id, true).await;
+    subscribe(&app, "announcements@example.com", &announcements_id, true).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "content_text": "Newsletter body as plain text",
+            "content_html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "list_ids": [digest_id, announcements_id],
+        }))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    app.dispatch_all_pending_emails().await;
+
+    let deliveries =
+        sqlx::query!("SELECT subscriber_email FROM issue_deliveries ORDER BY subscriber_email")
+            .fetch_all(&app.db_pool)
+            .await
+            .unwrap();
+    let recipients: Vec<_> = deliveries.into_iter().map(|d| d.subscriber_email).collect();
+    assert_eq!(
+        recipients,
+        ["announcements@example.com", "both@example.com"]
+    );
+}
+
+#[tokio::test]
+async fn members_who_leave_the_list_after_publishing_are_not_sent_the_issue() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let digest_id = create_list(&app, "Weekly digest").await;
+    subscribe(&app, "member@example.com", &digest_id, true).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "content_text": "Newsletter body as plain text",
+            "content_html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "list_id": digest_id,
+        }))
+        .await;
+    assert_successful_response(&response);
+
+    // Act
+    sqlx::query!("UPDATE list_memberships SET status = 'unsubscribed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let queued = sqlx::query!(r#"SELECT count(*) AS "count!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.count, 0);
+}
+
+#[tokio::test]
+async fn publishing_to_an_unknown_list_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "content_text": "Newsletter body as plain text",
+            "content_html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "list_id": Uuid::new_v4(),
+        }))
+        .await;
+
+    // Assert
+    assert_error_response(response, 400, "invalid_data").await;
+}
+
+async fn create_list(app: &TestApp, name: &str) -> String {
+    let response = app.post_list(&serde_json::json!({ "name": name })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    let list: serde_json::Value = response.json().await.unwrap();
+    list["list_id"].as_str().unwrap().to_string()
+}
+
+/// Sign `email` up for the list, and follow the confirmation link if `confirm` is set.
+async fn subscribe(app: &TestApp, email: &str, list_id: &str, confirm: bool) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions(serde_json::json!({
+        "name": "Subscriber",
+        "email": email,
+        "list_id": list_id,
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    if confirm {
+        let email_request = app
+            .email_server
+            .received_requests()
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
+        reqwest::get(app.get_confirmation_links(&email_request).html)
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+}
+
+/// The lists of the only subscriber, with their status, ordered by name.
+async fn memberships(app: &TestApp) -> Vec<(String, String)> {
+    sqlx::query!(
+        r#"
+        SELECT lists.name, list_memberships.status
+        FROM list_memberships
+        JOIN lists ON lists.list_id = list_memberships.list_id
+        ORDER BY lists.name
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|row| (row.name, row.status))
+    .collect()
+}
@@ -1,9 +1,28 @@
 mod admin_dashboard;
+mod archive;
 mod change_password;
+mod data_requests;
+mod delivery_retries;
+mod delivery_workers;
+mod drafts;
+mod feeds;
 mod frontend;
 mod health_check;
 mod helpers;
+mod lists;
 mod login;
 mod newsletter;
+mod newsletter_state;
+mod newsletter_status;
+mod pending_subscriptions;
+mod scheduled_newsletters;
+mod segments;
+mod subscriber_export;
+mod subscriber_import;
+mod subscribers;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_preferences;
+mod subscriptions_unsubscribe;
+mod test_newsletter;
+mod webhooks;
@@ -1,17 +1,11 @@
 use std::time::Duration;
 
-use fake::{
-    Fake,
-    faker::{internet::en::SafeEmail, name::en::Name},
-};
 use uuid::Uuid;
-use wiremock::{
-    Mock, ResponseTemplate,
-    matchers::{any, method, path},
-};
+use wiremock::{Mock, ResponseTemplate, matchers::any};
 
 use crate::helpers::{
-    ConfirmationLinks, TestApp, assert_error_response, assert_successful_response, spawn_app,
+    assert_error_response, assert_successful_response, create_confirmed_subscriber,
+    create_unconfirmed_subscriber, spawn_app,
 };
 
 #[tokio::test]
@@ -228,44 +222,101 @@ async fn concurrent_form_submission_is_handled_gracefully() {
     // Mock verifies on Drop that we have sent the newsletter email only once.
 }
 
-async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
-    // Since we might have multiple subscribers in one test, randomize their metadata to avoid conflicts.
-    let name: String = Name().fake();
-    let email: String = SafeEmail().fake();
-    let body = serde_json::json!({
-        "name": name,
-        "email": email,
-    });
+#[tokio::test]
+async fn newsletters_are_personalized_for_each_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT name, email, unsubscribe_token
+        FROM subscriptions
+        JOIN unsubscribe_tokens ON unsubscribe_tokens.subscriber_id = subscriptions.id
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
 
-    let _mock_guard = Mock::given(path("/email"))
-        .and(method("POST"))
+    Mock::given(any())
         .respond_with(ResponseTemplate::new(200))
-        .named("Create unconfirmed subscriber")
         .expect(1)
-        .mount_as_scoped(&app.email_server)
+        .mount(&app.email_server)
         .await;
 
-    app.post_subscriptions(body)
-        .await
-        .error_for_status()
-        .unwrap();
+    app.login().await;
 
-    let email_request = &app
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content_text": "Hi {{ name }}, this issue was sent to {{ email }}.",
+        "content_html": "<p>{% if name %}Hi {{ name }}{% else %}Hi{% endif %}</p><a href=\"{{ unsubscribe_url }}\">Leave</a>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_successful_response(&response);
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = app
         .email_server
         .received_requests()
         .await
         .unwrap()
         .pop()
         .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let unsubscribe_link = format!(
+        "{}/api/subscriptions/unsubscribe?unsubscribe_token={}",
+        app.base_url, subscriber.unsubscribe_token
+    );
 
-    app.get_confirmation_links(email_request)
+    assert!(body["TextBody"].as_str().unwrap().starts_with(&format!(
+        "Hi {}, this issue was sent to {}.",
+        subscriber.name, subscriber.email
+    )));
+    let html_body = body["HtmlBody"].as_str().unwrap();
+    assert!(html_body.starts_with("<p>Hi ") && !html_body.contains("{{"));
+    assert!(html_body.contains(&format!("<a href=\"{unsubscribe_link}\">Leave</a>")));
 }
 
-async fn create_confirmed_subscriber(app: &TestApp) {
-    let confirmation_links = create_unconfirmed_subscriber(app).await;
-    reqwest::get(confirmation_links.html)
-        .await
-        .unwrap()
-        .error_for_status()
-        .unwrap();
+#[tokio::test]
+async fn newsletters_with_invalid_templates_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    app.login().await;
+
+    let test_cases = [
+        ("Hi {{ name", "<p>Hi</p>", "unclosed placeholder"),
+        ("Hi", "{% if name %}<p>Hi</p>", "unclosed conditional"),
+        ("Hi {{ nmae }}", "<p>Hi</p>", "unknown placeholder"),
+    ];
+
+    for (content_text, content_html, description) in test_cases {
+        // Act
+        let newsletter_request_body = serde_json::json!({
+            "title": "Newsletter title",
+            "content_text": content_text,
+            "content_html": content_html,
+            "idempotency_key": Uuid::new_v4().to_string(),
+        });
+        let response = app.post_publish_newsletter(&newsletter_request_body).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 400, "{description}");
+        let error: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(error["error_id"], "invalid_template", "{description}");
+        assert!(error["message"].is_string(), "{description}");
+    }
+
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that we haven't sent any newsletter email
 }
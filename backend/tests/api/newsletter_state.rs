@@ -0,0 +1,174 @@
+use uuid::Uuid;
+use wiremock::{Mock, ResponseTemplate, matchers::any};
+
+use crate::helpers::{
+    assert_error_response, assert_successful_response, create_confirmed_subscriber, spawn_app,
+};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_change_the_state_of_a_newsletter() {
+    // Arrange
+    let app = spawn_app().await;
+    let issue_id = Uuid::new_v4().to_string();
+
+    // Act
+    let responses = [
+        app.post_pause_newsletter(&issue_id).await,
+        app.post_resume_newsletter(&issue_id).await,
+        app.post_cancel_newsletter(&issue_id).await,
+    ];
+
+    // Assert
+    for response in responses {
+        assert_error_response(response, 401, "not_logged_in").await;
+    }
+}
+
+#[tokio::test]
+async fn changing_the_state_of_an_unknown_newsletter_is_404() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let issue_id = Uuid::new_v4().to_string();
+
+    // Act
+    let responses = [
+        app.post_pause_newsletter(&issue_id).await,
+        app.post_resume_newsletter(&issue_id).await,
+        app.post_cancel_newsletter(&issue_id).await,
+    ];
+
+    // Assert
+    for response in responses {
+        assert_error_response(response, 404, "not_found").await;
+    }
+}
+
+#[tokio::test]
+async fn paused_newsletters_are_not_delivered_until_they_are_resumed() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    let issue_id = app.publish_newsletter().await;
+
+    // Act - Part 1 - Pause
+    let response = app.post_pause_newsletter(&issue_id).await;
+    assert_successful_response(&response);
+
+    // Assert
+    {
+        let _paused_mock = Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .named("Paused newsletter")
+            .mount_as_scoped(&app.email_server)
+            .await;
+        app.dispatch_all_pending_emails().await;
+    }
+    let status = app.newsletter_status(&issue_id).await;
+    assert_eq!(status["status"], "paused");
+    assert_eq!(status["queued"], 1);
+
+    // Act - Part 2 - Resume
+    let response = app.post_resume_newsletter(&issue_id).await;
+    assert_successful_response(&response);
+
+    // Assert
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+    let status = app.newsletter_status(&issue_id).await;
+    assert_eq!(status["status"], "completed");
+    assert_eq!(status["sent"], 1);
+}
+
+#[tokio::test]
+async fn cancelling_a_newsletter_purges_its_pending_deliveries() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.publish_newsletter().await;
+
+    // Act
+    let response = app.post_cancel_newsletter(&issue_id).await;
+
+    // Assert
+    assert_successful_response(&response);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["purged"], 2);
+
+    app.dispatch_all_pending_emails().await;
+    let status = app.newsletter_status(&issue_id).await;
+    assert_eq!(status["status"], "cancelled");
+    assert_eq!(status["queued"], 0);
+}
+
+#[tokio::test]
+async fn cancelling_keeps_the_log_of_deliveries_that_were_already_attempted() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(503))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.publish_newsletter().await;
+    app.dispatch_all_pending_emails().await;
+
+    // Act
+    let response = app.post_cancel_newsletter(&issue_id).await;
+
+    // Assert
+    assert_successful_response(&response);
+    let delivery = sqlx::query!("SELECT n_attempts, outcome FROM issue_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(delivery.n_attempts, 1);
+    assert_eq!(delivery.outcome, "cancelled");
+}
+
+#[tokio::test]
+async fn newsletters_in_the_wrong_state_cannot_be_changed() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.publish_newsletter().await;
+
+    // Act - Part 1 - Only paused newsletters can be resumed
+    let response = app.post_resume_newsletter(&issue_id).await;
+    assert_error_response(response, 409, "conflict").await;
+
+    // Act - Part 2 - Completed newsletters can't be paused or cancelled
+    app.dispatch_all_pending_emails().await;
+    let response = app.post_pause_newsletter(&issue_id).await;
+    assert_error_response(response, 409, "conflict").await;
+    let response = app.post_cancel_newsletter(&issue_id).await;
+    assert_error_response(response, 409, "conflict").await;
+}
@@ -0,0 +1,174 @@
+use uuid::Uuid;
+use wiremock::{Mock, ResponseTemplate, matchers::any};
+
+use crate::helpers::{assert_error_response, create_confirmed_subscriber, spawn_app};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_newsletter_status() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_newsletter_status(&Uuid::new_v4().to_string()).await;
+
+    // Assert
+    assert_error_response(response, 401, "not_logged_in").await;
+}
+
+#[tokio::test]
+async fn status_of_an_unknown_newsletter_is_404() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    let response = app.get_newsletter_status(&Uuid::new_v4().to_string()).await;
+
+    // Assert
+    assert_error_response(response, 404, "not_found").await;
+}
+
+#[tokio::test]
+async fn status_counts_queued_and_sent_deliveries() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817",
+            "ErrorCode": 0,
+            "Message": "OK"
+        })))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.publish_newsletter().await;
+
+    // Act - Part 1 - Nothing has been sent yet
+    let status = app.newsletter_status(&issue_id).await;
+
+    // Assert
+    assert_eq!(status["status"], "sending");
+    assert_eq!(status["queued"], 2);
+    assert_eq!(status["sent"], 0);
+    assert_eq!(status["failed"], 0);
+
+    // Act - Part 2 - Everything has been sent
+    app.dispatch_all_pending_emails().await;
+    let status = app.newsletter_status(&issue_id).await;
+
+    // Assert
+    assert_eq!(status["status"], "completed");
+    assert_eq!(status["queued"], 0);
+    assert_eq!(status["sent"], 2);
+    assert_eq!(status["failed"], 0);
+}
+
+#[tokio::test]
+async fn deliveries_are_logged_with_the_provider_message_id() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817",
+            "ErrorCode": 0,
+            "Message": "OK"
+        })))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.publish_newsletter().await;
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let delivery = sqlx::query!(
+        r#"
+        SELECT n_attempts, outcome, provider_message_id, sent_at
+        FROM issue_deliveries
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(delivery.n_attempts, 1);
+    assert_eq!(delivery.outcome, "sent");
+    assert_eq!(
+        delivery.provider_message_id.as_deref(),
+        Some("b7bc2f4a-e38e-4336-af7d-e6c392c2f817")
+    );
+    assert!(delivery.sent_at.is_some());
+}
+
+#[tokio::test]
+async fn status_counts_failed_deliveries() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(422))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.publish_newsletter().await;
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let status = app.newsletter_status(&issue_id).await;
+    assert_eq!(status["queued"], 0);
+    assert_eq!(status["sent"], 0);
+    assert_eq!(status["failed"], 1);
+
+    let delivery = sqlx::query!("SELECT outcome, last_error FROM issue_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(delivery.outcome, "failed");
+    assert!(delivery.last_error.unwrap().contains("422"));
+}
+
+#[tokio::test]
+async fn deliveries_being_retried_count_as_queued() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(503))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = app.publish_newsletter().await;
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let status = app.newsletter_status(&issue_id).await;
+    assert_eq!(status["queued"], 1);
+    assert_eq!(status["sent"], 0);
+    assert_eq!(status["failed"], 0);
+
+    let delivery = sqlx::query!("SELECT n_attempts, outcome FROM issue_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(delivery.n_attempts, 1);
+    assert_eq!(delivery.outcome, "retrying");
+}
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use zero2prod::pending_subscriptions::delete_stale_pending_subscriptions;
+
+use crate::helpers::{create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app};
+
+const RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+#[tokio::test]
+async fn pending_subscriptions_whose_links_expired_long_ago_are_deleted() {
+    // Arrange
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscriptions SET subscribed_at = now() - interval '40 days'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    sqlx::query!("UPDATE subscription_tokens SET expires_at = now() - interval '38 days'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let deleted = delete_stale_pending_subscriptions(&app.db_pool, RETENTION)
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(deleted, 1);
+    let remaining = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT count(*) FROM subscriptions) AS "subscriptions!",
+            (SELECT count(*) FROM subscription_tokens) AS "subscription_tokens!",
+            (SELECT count(*) FROM unsubscribe_tokens) AS "unsubscribe_tokens!"
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(remaining.subscriptions, 0);
+    assert_eq!(remaining.subscription_tokens, 0);
+    assert_eq!(remaining.unsubscribe_tokens, 0);
+}
+
+#[tokio::test]
+async fn recent_and_confirmed_subscriptions_are_kept() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_unconfirmed_subscriber(&app).await;
+    // The pending subscriber signed up long ago, but was sent a fresh link recently
+    sqlx::query!("UPDATE subscriptions SET subscribed_at = now() - interval '40 days'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    sqlx::query!("UPDATE subscription_tokens SET expires_at = now() - interval '1 day'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let deleted = delete_stale_pending_subscriptions(&app.db_pool, RETENTION)
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(deleted, 0);
+    let remaining = sqlx::query!(r#"SELECT count(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining.count, 2);
+}
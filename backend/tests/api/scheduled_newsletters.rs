@@ -0,0 +1,253 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use uuid::Uuid;
+use wiremock::{Mock, ResponseTemplate, matchers::any};
+
+use crate::helpers::{
+    TestApp, assert_error_response, assert_successful_response, create_confirmed_subscriber,
+    spawn_app,
+};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_list_scheduled_newsletters() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_scheduled_newsletters().await;
+
+    // Assert
+    assert_error_response(response, 401, "not_logged_in").await;
+}
+
+#[tokio::test]
+async fn scheduled_newsletters_are_not_delivered_before_send_at() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(&newsletter_request_body(Some(in_one_day())))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["newsletter_issue_id"].is_string());
+
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that we haven't sent the newsletter email
+}
+
+#[tokio::test]
+async fn scheduled_newsletters_are_delivered_once_send_at_has_passed() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = schedule_newsletter(&app).await;
+
+    // Act
+    make_scheduled_issues_due(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let issue = sqlx::query!(
+        "SELECT status, published_at FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id.parse::<Uuid>().unwrap()
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(issue.status, "completed");
+    assert!(issue.published_at.is_some());
+    // Mock verifies on Drop that we have sent the newsletter email
+}
+
+#[tokio::test]
+async fn publishing_with_send_at_in_the_past_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(&newsletter_request_body(Some(
+            Utc::now() - TimeDelta::hours(1),
+        )))
+        .await;
+
+    // Assert
+    assert_error_response(response, 400, "invalid_data").await;
+}
+
+#[tokio::test]
+async fn scheduled_newsletters_are_listed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let issue_id = schedule_newsletter(&app).await;
+
+    // Act
+    let response = app.get_scheduled_newsletters().await;
+
+    // Assert
+    assert_successful_response(&response);
+    let scheduled: Vec<serde_json::Value> = response.json().await.unwrap();
+    assert_eq!(scheduled.len(), 1);
+    assert_eq!(scheduled[0]["newsletter_issue_id"], issue_id);
+    assert_eq!(scheduled[0]["title"], "Newsletter title");
+}
+
+#[tokio::test]
+async fn scheduled_newsletters_can_be_rescheduled() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let issue_id = schedule_newsletter(&app).await;
+    let new_send_at = Utc::now() + TimeDelta::days(3);
+
+    // Act
+    let response = app
+        .post_reschedule_newsletter(&issue_id, &serde_json::json!({ "send_at": new_send_at }))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    let scheduled: Vec<serde_json::Value> =
+        app.get_scheduled_newsletters().await.json().await.unwrap();
+    let send_at: DateTime<Utc> = scheduled[0]["send_at"].as_str().unwrap().parse().unwrap();
+    assert_eq!(send_at.timestamp(), new_send_at.timestamp());
+}
+
+#[tokio::test]
+async fn rescheduling_into_the_past_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let issue_id = schedule_newsletter(&app).await;
+
+    // Act
+    let response = app
+        .post_reschedule_newsletter(
+            &issue_id,
+            &serde_json::json!({ "send_at": Utc::now() - TimeDelta::minutes(5) }),
+        )
+        .await;
+
+    // Assert
+    assert_error_response(response, 400, "invalid_data").await;
+}
+
+#[tokio::test]
+async fn cancelled_newsletters_are_never_delivered() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = schedule_newsletter(&app).await;
+
+    // Act
+    let response = app.post_cancel_newsletter(&issue_id).await;
+
+    // Assert
+    assert_successful_response(&response);
+
+    make_scheduled_issues_due(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let scheduled: Vec<serde_json::Value> =
+        app.get_scheduled_newsletters().await.json().await.unwrap();
+    assert!(scheduled.is_empty());
+    // Mock verifies on Drop that we haven't sent the newsletter email
+}
+
+#[tokio::test]
+async fn newsletters_that_are_no_longer_scheduled_cannot_be_changed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let issue_id = schedule_newsletter(&app).await;
+    make_scheduled_issues_due(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Act
+    let cancel_response = app.post_cancel_newsletter(&issue_id).await;
+    let reschedule_response = app
+        .post_reschedule_newsletter(&issue_id, &serde_json::json!({ "send_at": in_one_day() }))
+        .await;
+
+    // Assert
+    assert_error_response(cancel_response, 409, "conflict").await;
+    assert_error_response(reschedule_response, 409, "conflict").await;
+}
+
+#[tokio::test]
+async fn cancelling_an_unknown_newsletter_returns_404() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    let response = app
+        .post_cancel_newsletter(&Uuid::new_v4().to_string())
+        .await;
+
+    // Assert
+    assert_error_response(response, 404, "not_found").await;
+}
+
+fn in_one_day() -> DateTime<Utc> {
+    Utc::now() + TimeDelta::days(1)
+}
+
+fn newsletter_request_body(send_at: Option<DateTime<Utc>>) -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content_text": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+        "send_at": send_at,
+    })
+}
+
+/// Schedule a newsletter for tomorrow and return its id.
+async fn schedule_newsletter(app: &TestApp) -> String {
+    let response = app
+        .post_publish_newsletter(&newsletter_request_body(Some(in_one_day())))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let body: serde_json::Value = response.json().await.unwrap();
+    body["newsletter_issue_id"].as_str().unwrap().to_string()
+}
+
+/// Pretend that the time the scheduled issues were waiting for has come.
+async fn make_scheduled_issues_due(app: &TestApp) {
+    sqlx::query!(
+        "UPDATE newsletter_issues SET send_at = now() - interval '1 second' WHERE status = 'scheduled'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
@@ -0,0 +1,374 @@
+use chrono::{TimeDelta, Utc};
+use uuid::Uuid;
+use wiremock::{Mock, ResponseTemplate, matchers::any};
+
+use crate::helpers::{
+    TestApp, assert_error_response, assert_successful_response, create_confirmed_subscriber,
+    create_unconfirmed_subscriber, spawn_app,
+};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_segments_tags_and_attributes() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = Uuid::new_v4().to_string();
+
+    // Act
+    let list_response = app.get_segments().await;
+    let create_response = app.post_segment(&beta_testers()).await;
+    let tags_response = app
+        .put_subscriber_tags(&subscriber_id, &serde_json::json!({ "tags": ["beta"] }))
+        .await;
+    let attributes_response = app
+        .put_subscriber_attributes(&subscriber_id, &serde_json::json!({ "attributes": {} }))
+        .await;
+
+    // Assert
+    assert_error_response(list_response, 401, "not_logged_in").await;
+    assert_error_response(create_response, 401, "not_logged_in").await;
+    assert_error_response(tags_response, 401, "not_logged_in").await;
+    assert_error_response(attributes_response, 401, "not_logged_in").await;
+}
+
+#[tokio::test]
+async fn tags_and_attributes_are_stored_and_can_be_filtered_on() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = newest_subscriber_id(&app).await;
+    create_confirmed_subscriber(&app).await;
+
+    // Act
+    let tags_response = app
+        .put_subscriber_tags(
+            &subscriber_id,
+            &serde_json::json!({ "tags": [" beta ", "vip", "beta"] }),
+        )
+        .await;
+    let attributes_response = app
+        .put_subscriber_attributes(
+            &subscriber_id,
+            &serde_json::json!({ "attributes": { "plan": "pro", "seats": 3 } }),
+        )
+        .await;
+
+    // Assert
+    assert_successful_response(&tags_response);
+    let subscriber: serde_json::Value = tags_response.json().await.unwrap();
+    assert_eq!(subscriber["tags"], serde_json::json!(["beta", "vip"]));
+    assert_successful_response(&attributes_response);
+    let subscriber: serde_json::Value = attributes_response.json().await.unwrap();
+    assert_eq!(subscriber["attributes"]["plan"], "pro");
+
+    let page: serde_json::Value = app.get_subscribers("tag=beta").await.json().await.unwrap();
+    assert_eq!(page["total"], 1);
+    assert_eq!(page["subscribers"][0]["id"], subscriber_id.as_str());
+    assert_eq!(page["subscribers"][0]["attributes"]["seats"], 3);
+}
+
+#[tokio::test]
+async fn invalid_tags_and_attributes_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = newest_subscriber_id(&app).await;
+
+    // Act
+    let empty_tag_response = app
+        .put_subscriber_tags(&subscriber_id, &serde_json::json!({ "tags": ["  "] }))
+        .await;
+    let attributes_response = app
+        .put_subscriber_attributes(&subscriber_id, &serde_json::json!({ "attributes": [1] }))
+        .await;
+    let unknown_subscriber_response = app
+        .put_subscriber_tags(
+            &Uuid::new_v4().to_string(),
+            &serde_json::json!({ "tags": ["beta"] }),
+        )
+        .await;
+
+    // Assert
+    assert_error_response(empty_tag_response, 400, "invalid_data").await;
+    assert_error_response(attributes_response, 400, "invalid_data").await;
+    assert_error_response(unknown_subscriber_response, 404, "not_found").await;
+}
+
+#[tokio::test]
+async fn segments_count_the_subscribers_they_match() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    create_confirmed_subscriber(&app).await;
+    tag_newest_subscriber(&app, &["beta"]).await;
+    create_confirmed_subscriber(&app).await;
+
+    // Act
+    let response = app.post_segment(&beta_testers()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 201);
+    let segment: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(segment["name"], "Beta testers");
+    let segment_id = segment["segment_id"].as_str().unwrap();
+
+    let segments: serde_json::Value = app.get_segments().await.json().await.unwrap();
+    assert_eq!(segments.as_array().unwrap().len(), 1);
+
+    let details: serde_json::Value = app.get_segment(segment_id).await.json().await.unwrap();
+    assert_eq!(details["matching_subscribers"], 1);
+
+    let response = app
+        .put_segment(
+            segment_id,
+            &serde_json::json!({
+                "name": "Everyone but beta testers",
+                "filter": { "not": { "has_tag": "beta" } },
+            }),
+        )
+        .await;
+    assert_successful_response(&response);
+    let details: serde_json::Value = app.get_segment(segment_id).await.json().await.unwrap();
+    assert_eq!(details["name"], "Everyone but beta testers");
+    assert_eq!(details["matching_subscribers"], 1);
+}
+
+#[tokio::test]
+async fn every_condition_is_counted_like_it_reads() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = newest_subscriber_id(&app).await;
+    app.put_subscriber_attributes(
+        &subscriber_id,
+        &serde_json::json!({ "attributes": { "plan": "pro", "seats": 3 } }),
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+    create_confirmed_subscriber(&app).await;
+    let segment_id = create_segment(&app).await;
+
+    let cases = [
+        (
+            serde_json::json!({ "attribute_equals": { "key": "seats", "value": 3 } }),
+            1,
+        ),
+        (
+            serde_json::json!({ "attribute_equals": { "key": "seats", "value": "3" } }),
+            0,
+        ),
+        // The subscriber without the attribute doesn't have it equal to anything
+        (
+            serde_json::json!({ "not": { "attribute_equals": { "key": "seats", "value": 3 } } }),
+            1,
+        ),
+        (serde_json::json!({ "has_attribute": "plan" }), 1),
+        (
+            serde_json::json!({ "any": [{ "has_tag": "beta" }, { "has_attribute": "plan" }] }),
+            1,
+        ),
+        (
+            serde_json::json!({
+                "all": [{ "subscribed_within_days": 1 }, { "status": "confirmed" }],
+            }),
+            2,
+        ),
+        (
+            serde_json::json!({ "subscribed_before": "2000-01-01T00:00:00Z" }),
+            0,
+        ),
+        (serde_json::json!({ "any": [] }), 0),
+    ];
+    for (filter, expected) in cases {
+        // Act
+        app.put_segment(
+            &segment_id,
+            &serde_json::json!({ "name": "Beta testers", "filter": filter }),
+        )
+        .await
+        .error_for_status()
+        .unwrap();
+        let details: serde_json::Value = app.get_segment(&segment_id).await.json().await.unwrap();
+
+        // Assert
+        assert_eq!(details["matching_subscribers"], expected, "{filter}");
+    }
+}
+
+#[tokio::test]
+async fn segments_need_a_unique_name_and_a_valid_filter() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let response = app.post_segment(&beta_testers()).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    // Act
+    let duplicate_response = app.post_segment(&beta_testers()).await;
+    let unknown_condition_response = app
+        .post_segment(&serde_json::json!({
+            "name": "Old subscribers",
+            "filter": { "older_than": 30 },
+        }))
+        .await;
+    let unknown_status_response = app
+        .post_segment(&serde_json::json!({
+            "name": "Lost subscribers",
+            "filter": { "status": "lost" },
+        }))
+        .await;
+
+    // Assert
+    assert_error_response(duplicate_response, 409, "conflict").await;
+    assert_error_response(unknown_condition_response, 400, "invalid_data").await;
+    assert_error_response(unknown_status_response, 400, "invalid_data").await;
+}
+
+#[tokio::test]
+async fn issues_sent_to_a_segment_only_reach_its_confirmed_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    create_confirmed_subscriber(&app).await;
+    let beta_tester = tag_newest_subscriber(&app, &["beta"]).await;
+    create_confirmed_subscriber(&app).await;
+    create_unconfirmed_subscriber(&app).await;
+    tag_newest_subscriber(&app, &["beta"]).await;
+    let segment_id = create_segment(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(&newsletter_request_body(
+            serde_json::json!(segment_id),
+            None,
+        ))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    app.dispatch_all_pending_emails().await;
+
+    let deliveries = sqlx::query!(
+        r#"
+        SELECT subscriptions.id
+        FROM issue_deliveries
+        JOIN subscriptions ON subscriptions.email = issue_deliveries.subscriber_email
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].id.to_string(), beta_tester);
+}
+
+#[tokio::test]
+async fn publishing_to_an_unknown_segment_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    create_confirmed_subscriber(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(&newsletter_request_body(
+            serde_json::json!(Uuid::new_v4()),
+            None,
+        ))
+        .await;
+
+    // Assert
+    assert_error_response(response, 400, "invalid_data").await;
+}
+
+#[tokio::test]
+async fn segments_with_scheduled_issues_cannot_be_deleted() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let segment_id = create_segment(&app).await;
+    let send_at = (Utc::now() + TimeDelta::days(1)).to_rfc3339();
+    let response = app
+        .post_publish_newsletter(&newsletter_request_body(
+            serde_json::json!(segment_id),
+            Some(send_at),
+        ))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let newsletter_issue_id = body["newsletter_issue_id"].as_str().unwrap();
+
+    // Act
+    let scheduled_response = app.delete_segment(&segment_id).await;
+    app.post_cancel_newsletter(newsletter_issue_id)
+        .await
+        .error_for_status()
+        .unwrap();
+    let cancelled_response = app.delete_segment(&segment_id).await;
+
+    // Assert
+    assert_error_response(scheduled_response, 409, "conflict").await;
+    assert_successful_response(&cancelled_response);
+    assert_error_response(app.get_segment(&segment_id).await, 404, "not_found").await;
+}
+
+fn beta_testers() -> serde_json::Value {
+    serde_json::json!({
+        "name": "Beta testers",
+        "filter": { "has_tag": "beta" },
+    })
+}
+
+fn newsletter_request_body(
+    segment_id: serde_json::Value,
+    send_at: Option<String>,
+) -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content_text": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+        "segment_id": segment_id,
+        "send_at": send_at,
+    })
+}
+
+async fn create_segment(app: &TestApp) -> String {
+    let response = app.post_segment(&beta_testers()).await;
+    assert_eq!(response.status().as_u16(), 201);
+    let segment: serde_json::Value = response.json().await.unwrap();
+    segment["segment_id"].as_str().unwrap().to_string()
+}
+
+async fn newest_subscriber_id(app: &TestApp) -> String {
+    sqlx::query!("SELECT id FROM subscriptions ORDER BY subscribed_at DESC LIMIT 1")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id
+        .to_string()
+}
+
+/// Give the most recent subscriber these tags, and return their id.
+async fn tag_newest_subscriber(app: &TestApp, tags: &[&str]) -> String {
+    let subscriber_id = newest_subscriber_id(app).await;
+    app.put_subscriber_tags(&subscriber_id, &serde_json::json!({ "tags": tags }))
+        .await
+        .error_for_status()
+        .unwrap();
+    subscriber_id
+}
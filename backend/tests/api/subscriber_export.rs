@@ -0,0 +1,163 @@
+use zero2prod::csv::CsvReader;
+
+use crate::helpers::{
+    TestApp, assert_error_response, assert_successful_response, create_confirmed_subscriber,
+    create_unconfirmed_subscriber, spawn_app,
+};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_export_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_subscriber_export("").await;
+
+    // Assert
+    assert_error_response(response, 401, "not_logged_in").await;
+}
+
+#[tokio::test]
+async fn subscribers_are_exported_as_csv() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let confirmed_email = subscriber_emails(&app).await[0].clone();
+    app.login().await;
+    let response = app
+        .post_subscriber_import(
+            "confirmed",
+            "email,name\nursula@example.com,\"Le Guin, Ursula K.\"\n",
+        )
+        .await;
+    assert_successful_response(&response);
+
+    // Act
+    let response = app.get_subscriber_export("format=csv").await;
+
+    // Assert
+    assert_successful_response(&response);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/csv; charset=utf-8"
+    );
+    assert_eq!(
+        response.headers()["Content-Disposition"],
+        "attachment; filename=\"subscribers.csv\""
+    );
+    let records = read_csv(&response.bytes().await.unwrap());
+    assert_eq!(
+        records[0],
+        [
+            "id",
+            "email",
+            "name",
+            "status",
+            "subscribed_at",
+            "confirmed_at",
+            "source"
+        ]
+    );
+    assert_eq!(records.len(), 3);
+
+    // Oldest first
+    let signed_up = &records[1];
+    assert_eq!(signed_up[1], confirmed_email);
+    assert_eq!(signed_up[3], "confirmed");
+    assert!(!signed_up[5].is_empty(), "The confirmation time is missing");
+    assert_eq!(signed_up[6], "signup_form");
+
+    let imported = &records[2];
+    assert_eq!(imported[1], "ursula@example.com");
+    assert_eq!(imported[2], "Le Guin, Ursula K.");
+    assert_eq!(imported[3], "confirmed");
+    assert_eq!(imported[6], "import");
+}
+
+#[tokio::test]
+async fn subscribers_are_exported_as_ndjson_with_the_list_filters() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_unconfirmed_subscriber(&app).await;
+    app.login().await;
+
+    // Act
+    let response = app
+        .get_subscriber_export("format=ndjson&status=pending_confirmation")
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    assert_eq!(response.headers()["Content-Type"], "application/x-ndjson");
+    let body = response.text().await.unwrap();
+    let subscribers: Vec<serde_json::Value> = body
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(subscribers[0]["status"], "pending_confirmation");
+    assert_eq!(subscribers[0]["confirmed_at"], serde_json::Value::Null);
+    assert_eq!(subscribers[0]["source"], "signup_form");
+    for field in ["id", "email", "name", "subscribed_at"] {
+        assert!(subscribers[0][field].is_string(), "{field} is missing");
+    }
+}
+
+#[tokio::test]
+async fn large_lists_are_exported_in_full() {
+    // Arrange
+    let app = spawn_app().await;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        SELECT gen_random_uuid(), 'subscriber' || i || '@example.com', 'Subscriber ' || i, now(), 'confirmed'
+        FROM generate_series(1, 3000) AS i
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.login().await;
+
+    // Act
+    let response = app.get_subscriber_export("").await;
+
+    // Assert
+    assert_successful_response(&response);
+    let records = read_csv(&response.bytes().await.unwrap());
+    assert_eq!(records.len(), 3001);
+}
+
+#[tokio::test]
+async fn exporting_returns_400_for_unknown_formats() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    let response = app.get_subscriber_export("format=xlsx").await;
+
+    // Assert
+    assert_error_response(response, 400, "invalid_data").await;
+}
+
+fn read_csv(body: &[u8]) -> Vec<Vec<String>> {
+    let mut reader = CsvReader::default();
+    let mut records = reader.feed(body);
+    records.extend(reader.finish());
+    records
+        .into_iter()
+        .map(|record| record.fields.unwrap())
+        .collect()
+}
+
+async fn subscriber_emails(app: &TestApp) -> Vec<String> {
+    sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| row.email)
+        .collect()
+}
@@ -0,0 +1,282 @@
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{any, method, path},
+};
+
+use crate::helpers::{
+    TestApp, assert_error_response, assert_successful_response, create_confirmed_subscriber,
+    spawn_app,
+};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_import_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_subscriber_import("confirmed", "email,name\nursula@example.com,Ursula\n")
+        .await;
+
+    // Assert
+    assert_error_response(response, 401, "not_logged_in").await;
+    assert!(subscriptions(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn imported_subscribers_can_be_confirmed_right_away() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriber_import(
+            "confirmed",
+            "id,email,name\r\n1,ursula@example.com,Ursula\r\n2,\"octavia@example.com\",\"Butler, Octavia\"\r\n",
+        )
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["accepted"], 2);
+    assert_eq!(report["rejected"], 0);
+    assert_eq!(
+        report["rows"],
+        serde_json::json!([
+            { "line": 2, "email": "ursula@example.com", "status": "accepted" },
+            { "line": 3, "email": "octavia@example.com", "status": "accepted" },
+        ])
+    );
+    assert_eq!(
+        subscriptions(&app).await,
+        [
+            (
+                "octavia@example.com".to_string(),
+                "Butler, Octavia".to_string(),
+                "confirmed".to_string()
+            ),
+            (
+                "ursula@example.com".to_string(),
+                "Ursula".to_string(),
+                "confirmed".to_string()
+            ),
+        ]
+    );
+    // Mock verifies on Drop that no confirmation email was sent
+}
+
+#[tokio::test]
+async fn imported_subscribers_can_be_asked_to_confirm() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriber_import(
+            "send_confirmation",
+            "email,name\nursula@example.com,Ursula\n",
+        )
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["accepted"], 1);
+    assert_eq!(subscriptions(&app).await[0].2, "pending_confirmation");
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(subscriptions(&app).await[0].2, "confirmed");
+}
+
+#[tokio::test]
+async fn invalid_and_duplicate_rows_are_rejected_with_a_reason() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    // Taken regardless of case
+    let existing_email = subscriptions(&app).await[0].0.to_uppercase();
+    sqlx::query!(
+        "INSERT INTO suppressions (email, reason, suppressed_at) VALUES ('bounced@example.com', 'hard_bounce', now())"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.login().await;
+
+    let csv = format!(
+        "name,email\n\
+        Ursula,ursula@example.com\n\
+        Not an email,definitely-not-an-email\n\
+        Bad {{name}},bad-name@example.com\n\
+        Ursula again,URSULA@Example.com\n\
+        Existing,{existing_email}\n\
+        Bounced,Bounced@Example.com\n\
+        Only a name\n\
+        \n\
+        Octavia,octavia@example.com"
+    );
+
+    // Act
+    let response = app.post_subscriber_import("confirmed", &csv).await;
+
+    // Assert
+    assert_successful_response(&response);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["accepted"], 2);
+    assert_eq!(report["rejected"], 6);
+
+    let rows = report["rows"].as_array().unwrap();
+    let outcomes: Vec<_> = rows
+        .iter()
+        .map(|row| {
+            (
+                row["line"].as_u64().unwrap(),
+                row["status"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        outcomes,
+        [
+            (2, "accepted"),
+            (3, "rejected"),
+            (4, "rejected"),
+            (5, "rejected"),
+            (6, "rejected"),
+            (7, "rejected"),
+            (8, "rejected"),
+            (10, "accepted"),
+        ]
+    );
+    let reasons: Vec<_> = rows
+        .iter()
+        .filter_map(|row| row["reason"].as_str())
+        .collect();
+    assert!(reasons[0].contains("is not a valid subscriber email"));
+    assert!(reasons[1].contains("is not a valid subscriber name"));
+    assert_eq!(reasons[2], "The address is already on line 2");
+    assert_eq!(reasons[3], "The address is already subscribed");
+    assert_eq!(reasons[4], "The address is suppressed");
+    assert_eq!(
+        reasons[5],
+        "The line is missing the 'email' or 'name' column"
+    );
+
+    assert_eq!(subscriptions(&app).await.len(), 3);
+}
+
+#[tokio::test]
+async fn large_files_are_imported_in_full() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let mut csv = "email,name\n".to_string();
+    for i in 0..1234 {
+        csv.push_str(&format!("subscriber{i}@example.com,Subscriber {i}\n"));
+    }
+
+    // Act
+    let response = app.post_subscriber_import("confirmed", &csv).await;
+
+    // Assert
+    assert_successful_response(&response);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["accepted"], 1234);
+    assert_eq!(report["rows"][1233]["line"], 1235);
+    assert_eq!(subscriptions(&app).await.len(), 1234);
+}
+
+#[tokio::test]
+async fn subscribers_whose_confirmation_email_failed_are_not_imported() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriber_import(
+            "send_confirmation",
+            "email,name\nursula@example.com,Ursula\n",
+        )
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["rejected"], 1);
+    assert_eq!(
+        report["rows"][0]["reason"],
+        "Failed to send the confirmation email"
+    );
+    assert!(subscriptions(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn importing_returns_400_for_unusable_files_and_modes() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let test_cases = vec![
+        ("confirmed", "", "an empty file"),
+        (
+            "confirmed",
+            "email,first_name\nursula@example.com,Ursula\n",
+            "no name column",
+        ),
+        ("confirmed", "ursula@example.com,Ursula\n", "no header"),
+        (
+            "subscribed",
+            "email,name\nursula@example.com,Ursula\n",
+            "an unknown mode",
+        ),
+    ];
+
+    for (mode, csv, description) in test_cases {
+        // Act
+        let response = app.post_subscriber_import(mode, csv).await;
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The import did not fail with 400 Bad Request for {description}"
+        );
+        assert_error_response(response, 400, "invalid_data").await;
+    }
+    assert!(subscriptions(&app).await.is_empty());
+}
+
+/// Email, name and status of every subscriber, ordered by email.
+async fn subscriptions(app: &TestApp) -> Vec<(String, String, String)> {
+    sqlx::query!("SELECT email, name, status FROM subscriptions ORDER BY email")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| (row.email, row.name, row.status))
+        .collect()
+}
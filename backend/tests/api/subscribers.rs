@@ -0,0 +1,357 @@
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+use wiremock::{Mock, ResponseTemplate, matchers::any};
+
+use crate::helpers::{
+    TestApp, assert_error_response, assert_successful_response, create_unconfirmed_subscriber,
+    spawn_app,
+};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = Uuid::new_v4().to_string();
+
+    // Act
+    let responses = vec![
+        app.get_subscribers("").await,
+        app.get_subscriber(&subscriber_id).await,
+        app.patch_subscriber(&subscriber_id, &serde_json::json!({ "name": "Ursula" }))
+            .await,
+        app.post_confirm_subscriber(&subscriber_id).await,
+        app.delete_subscriber(&subscriber_id).await,
+    ];
+
+    // Assert
+    for response in responses {
+        assert_error_response(response, 401, "not_logged_in").await;
+    }
+}
+
+#[tokio::test]
+async fn subscribers_are_listed_newest_first_one_page_at_a_time() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let now = Utc::now();
+    for i in 0..5 {
+        insert_subscriber(
+            &app,
+            &format!("subscriber{i}@example.com"),
+            "confirmed",
+            now - Duration::days(i),
+        )
+        .await;
+    }
+
+    // Act
+    let response = app.get_subscribers("page=2&per_page=2").await;
+
+    // Assert
+    assert_successful_response(&response);
+    let page: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(page["total"], 5);
+    assert_eq!(page["page"], 2);
+    assert_eq!(page["per_page"], 2);
+    assert_eq!(
+        emails(&page),
+        ["subscriber2@example.com", "subscriber3@example.com"]
+    );
+}
+
+#[tokio::test]
+async fn subscribers_can_be_searched_by_email_or_name() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let now = Utc::now();
+    insert_subscriber(&app, "ursula@example.com", "confirmed", now).await;
+    insert_subscriber(&app, "octavia@example.com", "confirmed", now).await;
+    insert_subscriber(&app, "100%_real@example.com", "confirmed", now).await;
+
+    let test_cases = vec![
+        ("search=URSULA", vec!["ursula@example.com"]),
+        ("search=Name%20of%20octavia", vec!["octavia@example.com"]),
+        // Wildcards are matched literally
+        ("search=%25_", vec!["100%_real@example.com"]),
+        ("search=nobody", vec![]),
+    ];
+
+    for (query, expected) in test_cases {
+        // Act
+        let response = app.get_subscribers(query).await;
+
+        // Assert
+        assert_successful_response(&response);
+        let page: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(emails(&page), expected, "Unexpected results for {query}");
+    }
+}
+
+#[tokio::test]
+async fn subscribers_can_be_filtered_by_status_and_signup_date() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let now = Utc::now();
+    insert_subscriber(
+        &app,
+        "old@example.com",
+        "confirmed",
+        now - Duration::days(30),
+    )
+    .await;
+    insert_subscriber(&app, "new@example.com", "confirmed", now).await;
+    insert_subscriber(&app, "pending@example.com", "pending_confirmation", now).await;
+    let a_week_ago = urlencoding::encode(&(now - Duration::days(7)).to_rfc3339()).into_owned();
+
+    let test_cases = vec![
+        (
+            "status=pending_confirmation".to_string(),
+            vec!["pending@example.com"],
+        ),
+        (
+            format!("status=confirmed&subscribed_since={a_week_ago}"),
+            vec!["new@example.com"],
+        ),
+        (
+            format!("subscribed_before={a_week_ago}"),
+            vec!["old@example.com"],
+        ),
+    ];
+
+    for (query, expected) in test_cases {
+        // Act
+        let response = app.get_subscribers(&query).await;
+
+        // Assert
+        assert_successful_response(&response);
+        let page: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(emails(&page), expected, "Unexpected results for {query}");
+    }
+}
+
+#[tokio::test]
+async fn listing_subscribers_returns_400_for_invalid_pages() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    for query in ["page=0", "per_page=0", "per_page=1000"] {
+        // Act
+        let response = app.get_subscribers(query).await;
+
+        // Assert
+        assert_error_response(response, 400, "invalid_data").await;
+    }
+}
+
+#[tokio::test]
+async fn a_subscriber_can_be_viewed_with_their_tokens_and_deliveries() {
+    // Arrange
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+    app.login().await;
+    let subscriber_id = only_subscriber_id(&app).await;
+    app.post_confirm_subscriber(&subscriber_id).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "content_text": "Newsletter body as plain text",
+            "content_html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_successful_response(&response);
+    app.dispatch_all_pending_emails().await;
+
+    // Act
+    let response = app.get_subscriber(&subscriber_id).await;
+
+    // Assert
+    assert_successful_response(&response);
+    let subscriber: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(subscriber["id"], subscriber_id);
+    assert_eq!(subscriber["status"], "confirmed");
+    assert_eq!(
+        subscriber["subscription_tokens"].as_array().unwrap().len(),
+        1
+    );
+    assert!(subscriber["unsubscribe_token"].is_string());
+    let deliveries = subscriber["deliveries"].as_array().unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0]["title"], "Newsletter title");
+    assert_eq!(deliveries[0]["outcome"], "sent");
+}
+
+#[tokio::test]
+async fn a_subscriber_can_be_renamed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let subscriber_id =
+        insert_subscriber(&app, "ursula@example.com", "confirmed", Utc::now()).await;
+
+    // Act
+    let response = app
+        .patch_subscriber(
+            &subscriber_id,
+            &serde_json::json!({ "name": "Ursula K. Le Guin" }),
+        )
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    let subscriber: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(subscriber["name"], "Ursula K. Le Guin");
+}
+
+#[tokio::test]
+async fn renaming_a_subscriber_returns_400_for_invalid_names() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let subscriber_id =
+        insert_subscriber(&app, "ursula@example.com", "confirmed", Utc::now()).await;
+
+    for name in ["", "   ", "Ursula<script>"] {
+        // Act
+        let response = app
+            .patch_subscriber(&subscriber_id, &serde_json::json!({ "name": name }))
+            .await;
+
+        // Assert
+        assert_error_response(response, 400, "invalid_data").await;
+    }
+}
+
+#[tokio::test]
+async fn pending_subscribers_can_be_confirmed_manually() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let subscriber_id = insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "pending_confirmation",
+        Utc::now(),
+    )
+    .await;
+
+    // Act
+    let response = app.post_confirm_subscriber(&subscriber_id).await;
+
+    // Assert
+    assert_successful_response(&response);
+    let subscriber: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(subscriber["status"], "confirmed");
+
+    // Confirming again is a no-op
+    let response = app.post_confirm_subscriber(&subscriber_id).await;
+    assert_successful_response(&response);
+}
+
+#[tokio::test]
+async fn subscribers_who_left_cannot_be_confirmed_manually() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let subscriber_id =
+        insert_subscriber(&app, "ursula@example.com", "unsubscribed", Utc::now()).await;
+
+    // Act
+    let response = app.post_confirm_subscriber(&subscriber_id).await;
+
+    // Assert
+    assert_error_response(response, 409, "conflict").await;
+}
+
+#[tokio::test]
+async fn a_subscriber_can_be_deleted() {
+    // Arrange
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app).await;
+    app.login().await;
+    let subscriber_id = only_subscriber_id(&app).await;
+
+    // Act
+    let response = app.delete_subscriber(&subscriber_id).await;
+
+    // Assert
+    assert_successful_response(&response);
+    let response = app.get_subscriber(&subscriber_id).await;
+    assert_error_response(response, 404, "not_found").await;
+    let response = app.delete_subscriber(&subscriber_id).await;
+    assert_error_response(response, 404, "not_found").await;
+}
+
+#[tokio::test]
+async fn unknown_subscribers_return_404() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let subscriber_id = Uuid::new_v4().to_string();
+
+    // Act
+    let responses = vec![
+        app.get_subscriber(&subscriber_id).await,
+        app.patch_subscriber(&subscriber_id, &serde_json::json!({ "name": "Ursula" }))
+            .await,
+        app.post_confirm_subscriber(&subscriber_id).await,
+        app.delete_subscriber(&subscriber_id).await,
+    ];
+
+    // Assert
+    for response in responses {
+        assert_error_response(response, 404, "not_found").await;
+    }
+}
+
+/// Store a subscriber named after their email, without going through the subscription flow.
+async fn insert_subscriber(
+    app: &TestApp,
+    email: &str,
+    status: &str,
+    subscribed_at: DateTime<Utc>,
+) -> String {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        id,
+        email,
+        format!("Name of {}", email.split('@').next().unwrap()),
+        subscribed_at,
+        status
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    id.to_string()
+}
+
+async fn only_subscriber_id(app: &TestApp) -> String {
+    sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id
+        .to_string()
+}
+
+fn emails(page: &serde_json::Value) -> Vec<&str> {
+    page["subscribers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["email"].as_str().unwrap())
+        .collect()
+}
@@ -3,7 +3,9 @@ use wiremock::{
     matchers::{method, path},
 };
 
-use crate::helpers::spawn_app;
+use crate::helpers::{
+    assert_error_response, assert_successful_response, create_unconfirmed_subscriber, spawn_app,
+};
 
 #[tokio::test]
 async fn confirmations_without_token_are_rejected_with_a_400() {
@@ -57,7 +59,37 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
 }
 
 #[tokio::test]
-async fn confirm_returns_500_if_token_is_invalid() {
+async fn old_confirmation_links_do_not_bring_back_subscribers_who_left() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_links = create_unconfirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // Assert
+    assert_error_response(response, 400, "invalid_data").await;
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "unsubscribed");
+    let confirmed_memberships = sqlx::query!(
+        "SELECT count(*) AS \"count!\" FROM list_memberships WHERE status = 'confirmed'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(confirmed_memberships.count, 0);
+}
+
+#[tokio::test]
+async fn unknown_confirmation_tokens_are_rejected_with_a_400() {
     // Arrange
     let app = spawn_app().await;
     let body = serde_json::json!({
@@ -85,5 +117,72 @@ async fn confirm_returns_500_if_token_is_invalid() {
     let response = reqwest::get(confirmation_links.html).await.unwrap();
 
     // Assert
-    assert_eq!(response.status().as_u16(), 500);
+    assert_error_response(response, 400, "invalid_data").await;
+}
+
+#[tokio::test]
+async fn expired_confirmation_links_are_rejected_with_a_410() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmation_links = create_unconfirmed_subscriber(&app).await;
+    sqlx::query!("UPDATE subscription_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // Assert
+    assert_error_response(response, 410, "expired_token").await;
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn the_link_resent_after_expiry_confirms_the_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "name": "le guin",
+        "email": "ursula_le_guin@gmail.com",
+    });
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.clone()).await;
+    sqlx::query!(
+        r#"
+        UPDATE subscription_tokens
+        SET
+            created_at = now() - interval '3 days',
+            expires_at = now() - interval '1 day'
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.post_subscriptions(body).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[1];
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    // Act
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // Assert
+    assert_successful_response(&response);
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
 }
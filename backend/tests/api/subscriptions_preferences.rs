@@ -0,0 +1,387 @@
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{method, path},
+};
+
+use crate::helpers::{
+    TestApp, assert_error_response, assert_successful_response, create_confirmed_subscriber,
+    spawn_app,
+};
+
+#[tokio::test]
+async fn preferences_need_a_valid_unsubscribe_token() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    // Act
+    let get_response = app.get_preferences("not-a-token").await;
+    let patch_response = app
+        .patch_preferences("not-a-token", &serde_json::json!({ "name": "Ursula" }))
+        .await;
+
+    // Assert
+    assert_error_response(get_response, 400, "invalid_data").await;
+    assert_error_response(patch_response, 400, "invalid_data").await;
+}
+
+#[tokio::test]
+async fn preferences_show_the_subscriber_and_every_list() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let digest_id = create_list(&app, "Weekly digest").await;
+    let token = unsubscribe_token(&app).await;
+
+    // Act
+    let response = app.get_preferences(&token).await;
+
+    // Assert
+    assert_successful_response(&response);
+    let preferences: serde_json::Value = response.json().await.unwrap();
+    let subscriber = sqlx::query!("SELECT name, email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(preferences["name"], subscriber.name);
+    assert_eq!(preferences["email"], subscriber.email);
+    assert_eq!(preferences["status"], "confirmed");
+    assert!(preferences["pending_email"].is_null());
+    assert_eq!(preferences["lists"][0]["name"], "Newsletter");
+    assert_eq!(preferences["lists"][0]["status"], "confirmed");
+    assert_eq!(preferences["lists"][1]["list_id"], digest_id.as_str());
+    assert!(preferences["lists"][1]["status"].is_null());
+}
+
+#[tokio::test]
+async fn subscribers_can_change_their_name() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = unsubscribe_token(&app).await;
+
+    // Act
+    let response = app
+        .patch_preferences(&token, &serde_json::json!({ "name": "Ursula K. Le Guin" }))
+        .await;
+    let invalid_response = app
+        .patch_preferences(&token, &serde_json::json!({ "name": "   " }))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    let preferences: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(preferences["name"], "Ursula K. Le Guin");
+    assert_error_response(invalid_response, 400, "invalid_data").await;
+
+    let saved = sqlx::query!("SELECT name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.name, "Ursula K. Le Guin");
+}
+
+#[tokio::test]
+async fn subscribers_can_switch_lists_without_confirming_again() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let digest_id = create_list(&app, "Weekly digest").await;
+    let token = unsubscribe_token(&app).await;
+
+    // Act
+    let response = app
+        .patch_preferences(&token, &serde_json::json!({ "lists": [digest_id] }))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    let preferences: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(preferences["lists"][0]["name"], "Newsletter");
+    assert_eq!(preferences["lists"][0]["status"], "unsubscribed");
+    assert_eq!(preferences["lists"][1]["name"], "Weekly digest");
+    assert_eq!(preferences["lists"][1]["status"], "confirmed");
+}
+
+#[tokio::test]
+async fn joining_an_unknown_list_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = unsubscribe_token(&app).await;
+
+    // Act
+    let response = app
+        .patch_preferences(
+            &token,
+            &serde_json::json!({ "lists": [uuid::Uuid::new_v4()] }),
+        )
+        .await;
+
+    // Assert
+    assert_error_response(response, 400, "invalid_data").await;
+    let memberships = sqlx::query!("SELECT status FROM list_memberships")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(memberships.len(), 1);
+    assert_eq!(memberships[0].status, "confirmed");
+}
+
+#[tokio::test]
+async fn unsubscribed_subscribers_cannot_join_lists() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let digest_id = create_list(&app, "Weekly digest").await;
+    let token = unsubscribe_token(&app).await;
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = app
+        .patch_preferences(&token, &serde_json::json!({ "lists": [digest_id] }))
+        .await;
+
+    // Assert
+    assert_error_response(response, 409, "conflict").await;
+}
+
+#[tokio::test]
+async fn a_new_address_is_used_once_it_is_confirmed() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = unsubscribe_token(&app).await;
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .patch_preferences(
+            &token,
+            &serde_json::json!({ "email": "ursula@example.com" }),
+        )
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    let preferences: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(preferences["pending_email"], "ursula@example.com");
+    assert_ne!(preferences["email"], "ursula@example.com");
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "ursula@example.com");
+    let link = app.get_confirmation_links(&email_request).html;
+    reqwest::get(link.clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let preferences: serde_json::Value = app.get_preferences(&token).await.json().await.unwrap();
+    assert_eq!(preferences["email"], "ursula@example.com");
+    assert!(preferences["pending_email"].is_null());
+
+    // The link only works once
+    let response = reqwest::get(link).await.unwrap();
+    assert_error_response(response, 400, "invalid_data").await;
+}
+
+#[tokio::test]
+async fn invalid_addresses_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = unsubscribe_token(&app).await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .patch_preferences(
+            &token,
+            &serde_json::json!({ "email": "definitely-not-an-email" }),
+        )
+        .await;
+
+    // Assert
+    assert_error_response(response, 400, "invalid_data").await;
+}
+
+#[tokio::test]
+async fn moving_to_a_suppressed_address_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = unsubscribe_token(&app).await;
+    sqlx::query!(
+        "INSERT INTO suppressions (email, reason, suppressed_at) VALUES ($1, 'hard_bounce', now())",
+        "Ursula@Example.com"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .patch_preferences(
+            &token,
+            &serde_json::json!({ "email": "ursula@example.com" }),
+        )
+        .await;
+
+    // Assert
+    assert_error_response(response, 400, "invalid_data").await;
+    let pending = sqlx::query!(r#"SELECT count(*) AS "count!" FROM email_change_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(pending.count, 0);
+}
+
+#[tokio::test]
+async fn moving_to_an_address_that_is_already_subscribed_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = unsubscribe_token(&app).await;
+    let link = request_email_change(&app, &token, "ursula@example.com").await;
+    create_confirmed_subscriber_with_email(&app, "ursula@example.com").await;
+
+    // Act
+    let response = reqwest::get(link).await.unwrap();
+
+    // Assert
+    assert_error_response(response, 409, "conflict").await;
+}
+
+#[tokio::test]
+async fn addresses_suppressed_before_the_change_is_confirmed_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = unsubscribe_token(&app).await;
+    let link = request_email_change(&app, &token, "ursula@example.com").await;
+    sqlx::query!(
+        "INSERT INTO suppressions (email, reason, suppressed_at) VALUES ($1, 'hard_bounce', now())",
+        "ursula@example.com"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    let response = reqwest::get(link).await.unwrap();
+
+    // Assert
+    assert_error_response(response, 400, "invalid_data").await;
+    let preferences: serde_json::Value = app.get_preferences(&token).await.json().await.unwrap();
+    assert_ne!(preferences["email"], "ursula@example.com");
+}
+
+#[tokio::test]
+async fn expired_email_change_links_are_rejected_with_a_410() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let token = unsubscribe_token(&app).await;
+    let link = request_email_change(&app, &token, "ursula@example.com").await;
+    sqlx::query!("UPDATE email_change_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = reqwest::get(link).await.unwrap();
+
+    // Assert
+    assert_error_response(response, 410, "expired_token").await;
+    let preferences: serde_json::Value = app.get_preferences(&token).await.json().await.unwrap();
+    assert_ne!(preferences["email"], "ursula@example.com");
+}
+
+/// The unsubscribe token of the only subscriber.
+async fn unsubscribe_token(app: &TestApp) -> String {
+    sqlx::query!("SELECT unsubscribe_token FROM unsubscribe_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .unsubscribe_token
+}
+
+async fn create_list(app: &TestApp, name: &str) -> String {
+    app.login().await;
+    let response = app.post_list(&serde_json::json!({ "name": name })).await;
+    assert_eq!(response.status().as_u16(), 201);
+    let list: serde_json::Value = response.json().await.unwrap();
+    list["list_id"].as_str().unwrap().to_string()
+}
+
+/// Ask to move the subscription to `email`, and return the link that confirms it.
+async fn request_email_change(app: &TestApp, token: &str, email: &str) -> reqwest::Url {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.patch_preferences(token, &serde_json::json!({ "email": email }))
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    app.get_confirmation_links(&email_request).html
+}
+
+async fn create_confirmed_subscriber_with_email(app: &TestApp, email: &str) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions(serde_json::json!({ "name": "Ursula", "email": email }))
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    reqwest::get(app.get_confirmation_links(&email_request).html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
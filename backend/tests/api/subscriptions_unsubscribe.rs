@@ -0,0 +1,292 @@
+use uuid::Uuid;
+use wiremock::{Mock, ResponseTemplate, matchers::any};
+
+use crate::helpers::{
+    TestApp, assert_error_response, assert_successful_response, create_confirmed_subscriber,
+    spawn_app,
+};
+
+#[tokio::test]
+async fn unsubscribe_without_token_is_rejected_with_a_400() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(&format!("{}/api/subscriptions/unsubscribe", app.address))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn unsubscribe_with_an_unknown_token_is_rejected_with_a_400() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(&format!(
+        "{}/api/subscriptions/unsubscribe?unsubscribe_token=not-a-real-token",
+        app.address
+    ))
+    .await
+    .unwrap();
+
+    // Assert
+    assert_error_response(response, 400, "invalid_data").await;
+}
+
+#[tokio::test]
+async fn newsletter_issues_include_an_unsubscribe_link() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    // Act
+    let unsubscribe_link = publish_and_get_unsubscribe_link(&app).await;
+
+    // Assert
+    assert_eq!(unsubscribe_link.path(), "/api/subscriptions/unsubscribe");
+}
+
+#[tokio::test]
+async fn newsletter_issues_carry_one_click_list_unsubscribe_headers() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+
+    // Act
+    let email_request = publish_and_get_issue_request(&app).await;
+
+    // Assert
+    let unsubscribe_link = app.get_unsubscribe_link(&email_request);
+    let list_unsubscribe = get_email_header(&email_request, "List-Unsubscribe");
+    assert!(list_unsubscribe.starts_with("<mailto:"));
+
+    // The header carries the link as sent, i.e. without the test server's port
+    let mut https_link = unsubscribe_link.clone();
+    https_link.set_port(None).unwrap();
+    assert!(list_unsubscribe.ends_with(&format!(", <{https_link}>")));
+
+    assert_eq!(
+        get_email_header(&email_request, "List-Unsubscribe-Post"),
+        "List-Unsubscribe=One-Click"
+    );
+}
+
+#[tokio::test]
+async fn one_click_unsubscribe_from_a_mail_client_unsubscribes_a_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let unsubscribe_link = publish_and_get_unsubscribe_link(&app).await;
+
+    // Act - Mimic what a mail client sends per RFC 8058: no cookies, a fixed form body
+    let response = reqwest::Client::new()
+        .post(unsubscribe_link)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("List-Unsubscribe=One-Click")
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_successful_response(&response);
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+
+    assert_eq!(saved.status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn clicking_on_the_unsubscribe_link_unsubscribes_a_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let unsubscribe_link = publish_and_get_unsubscribe_link(&app).await;
+
+    // Act
+    let response = reqwest::get(unsubscribe_link).await.unwrap();
+
+    // Assert
+    assert_successful_response(&response);
+
+    let saved = sqlx::query!("SELECT status, unsubscribed_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+
+    assert_eq!(saved.status, "unsubscribed");
+    assert!(saved.unsubscribed_at.is_some());
+}
+
+#[tokio::test]
+async fn posting_to_the_unsubscribe_link_unsubscribes_a_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let unsubscribe_link = publish_and_get_unsubscribe_link(&app).await;
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(unsubscribe_link)
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_successful_response(&response);
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+
+    assert_eq!(saved.status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn unsubscribing_twice_succeeds_and_keeps_the_original_timestamp() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let unsubscribe_link = publish_and_get_unsubscribe_link(&app).await;
+
+    reqwest::get(unsubscribe_link.clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let first = sqlx::query!("SELECT unsubscribed_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = reqwest::get(unsubscribe_link).await.unwrap();
+
+    // Assert
+    assert_successful_response(&response);
+
+    let second = sqlx::query!("SELECT unsubscribed_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(first.unsubscribed_at, second.unsubscribed_at);
+}
+
+#[tokio::test]
+async fn newsletters_are_not_delivered_to_unsubscribed_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let unsubscribe_link = publish_and_get_unsubscribe_link(&app).await;
+    reqwest::get(unsubscribe_link)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        // We assert that no request sent to the email server
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(&newsletter_request_body())
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+
+    app.dispatch_all_pending_emails().await;
+
+    // Mock verifies on Drop that we haven't sent the newsletter email
+}
+
+#[tokio::test]
+async fn unsubscribing_removes_pending_deliveries() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let unsubscribe_link = publish_and_get_unsubscribe_link(&app).await;
+
+    // Queue a second issue, but unsubscribe before the worker gets to it
+    app.post_publish_newsletter(&newsletter_request_body())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    reqwest::get(unsubscribe_link)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let queued = sqlx::query!("SELECT subscriber_email FROM issue_delivery_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(queued.is_empty());
+}
+
+fn newsletter_request_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content_text": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    })
+}
+
+/// Publish an issue to the (single) confirmed subscriber and return the request sent to the email API.
+async fn publish_and_get_issue_request(app: &TestApp) -> wiremock::Request {
+    let _mock_guard = Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.login().await;
+    app.post_publish_newsletter(&newsletter_request_body())
+        .await
+        .error_for_status()
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    app.email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap()
+}
+
+/// Publish an issue to the (single) confirmed subscriber and return the unsubscribe link it carried.
+async fn publish_and_get_unsubscribe_link(app: &TestApp) -> reqwest::Url {
+    let email_request = publish_and_get_issue_request(app).await;
+    app.get_unsubscribe_link(&email_request)
+}
+
+/// Extract the value of a custom header from a request sent to the email API.
+fn get_email_header(email_request: &wiremock::Request, name: &str) -> String {
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    body["Headers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|h| h["Name"] == name)
+        .unwrap_or_else(|| panic!("No '{name}' header was sent"))["Value"]
+        .as_str()
+        .unwrap()
+        .to_owned()
+}
@@ -0,0 +1,155 @@
+use uuid::Uuid;
+use wiremock::{
+    Mock, ResponseTemplate,
+    matchers::{any, method, path},
+};
+
+use crate::helpers::{
+    assert_error_response, assert_successful_response, create_confirmed_subscriber, spawn_app,
+};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_send_a_test_newsletter() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_test_newsletter(&test_request_body(&["reviewer@example.com"]))
+        .await;
+
+    // Assert
+    assert_error_response(response, 401, "not_logged_in").await;
+}
+
+#[tokio::test]
+async fn test_newsletters_are_sent_to_every_recipient_with_a_test_subject() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_test_newsletter(&test_request_body(&[
+            "reviewer@example.com",
+            "editor@example.com",
+        ]))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+
+    let received_requests = app.email_server.received_requests().await.unwrap();
+    let recipients: Vec<String> = received_requests
+        .iter()
+        .map(|r| {
+            let body: serde_json::Value = serde_json::from_slice(&r.body).unwrap();
+            assert_eq!(body["Subject"], "[TEST] Newsletter title");
+            body["To"].as_str().unwrap().to_string()
+        })
+        .collect();
+    assert_eq!(recipients, ["reviewer@example.com", "editor@example.com"]);
+}
+
+#[tokio::test]
+async fn test_newsletters_are_not_stored_or_delivered_to_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_test_newsletter(&test_request_body(&["reviewer@example.com"]))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+
+    let issues = sqlx::query!("SELECT count(*) AS \"count!\" FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issues.count, 0);
+
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that only the test email was sent
+}
+
+#[tokio::test]
+async fn test_newsletters_with_invalid_recipients_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let test_cases = [
+        (
+            vec!["reviewer@example.com", "not-an-email"],
+            "invalid recipient",
+        ),
+        (vec![], "no recipients"),
+    ];
+
+    for (recipients, description) in test_cases {
+        // Act
+        let response = app
+            .post_test_newsletter(&test_request_body(&recipients))
+            .await;
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 Bad Request when the payload was {description}."
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_newsletter_fails_if_the_email_cannot_be_sent() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_test_newsletter(&test_request_body(&["reviewer@example.com"]))
+        .await;
+
+    // Assert
+    assert_error_response(response, 500, "internal_error").await;
+}
+
+fn test_request_body(recipients: &[&str]) -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content_text": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+        "recipients": recipients,
+    })
+}
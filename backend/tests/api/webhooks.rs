@@ -0,0 +1,295 @@
+use uuid::Uuid;
+use wiremock::{Mock, ResponseTemplate, matchers::any};
+
+use crate::helpers::{
+    TestApp, assert_error_response, assert_successful_response, create_confirmed_subscriber,
+    spawn_app,
+};
+
+#[tokio::test]
+async fn hard_bounces_suppress_the_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let email = subscriber_email(&app).await;
+
+    // Act
+    let response = app
+        .post_postmark_webhook(&serde_json::json!({
+            "RecordType": "Bounce",
+            "Type": "HardBounce",
+            "TypeCode": 1,
+            "Email": email,
+            "Description": "The server was unable to deliver your message (ex: unknown user, mailbox not found).",
+        }))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+
+    let suppression = sqlx::query!("SELECT email, reason, details FROM suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("The address should be suppressed");
+    assert_eq!(suppression.email, email);
+    assert_eq!(suppression.reason, "hard_bounce");
+    assert!(suppression.details.unwrap().contains("mailbox not found"));
+    assert_eq!(subscriber_status(&app).await, "bounced");
+}
+
+#[tokio::test]
+async fn spam_complaints_suppress_the_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let email = subscriber_email(&app).await;
+
+    // Act
+    let response = app
+        .post_postmark_webhook(&serde_json::json!({
+            "RecordType": "SpamComplaint",
+            "Type": "SpamComplaint",
+            "TypeCode": 512,
+            "Email": email,
+        }))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+
+    let suppression = sqlx::query!("SELECT reason FROM suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("The address should be suppressed");
+    assert_eq!(suppression.reason, "spam_complaint");
+    assert_eq!(subscriber_status(&app).await, "complained");
+}
+
+#[tokio::test]
+async fn soft_bounces_and_other_records_are_ignored() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let email = subscriber_email(&app).await;
+    let test_cases = vec![
+        serde_json::json!({
+            "RecordType": "Bounce",
+            "Type": "SoftBounce",
+            "Email": email,
+        }),
+        serde_json::json!({
+            "RecordType": "Delivery",
+            "Recipient": email,
+        }),
+    ];
+
+    for body in test_cases {
+        // Act
+        let response = app.post_postmark_webhook(&body).await;
+
+        // Assert
+        assert_successful_response(&response);
+    }
+    assert_eq!(suppression_count(&app).await, 0);
+    assert_eq!(subscriber_status(&app).await, "confirmed");
+}
+
+#[tokio::test]
+async fn webhook_requests_without_valid_credentials_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "RecordType": "SpamComplaint",
+        "Email": "ursula@example.com",
+    });
+    let url = format!("{}/api/webhooks/postmark", &app.address);
+    let test_cases = vec![
+        (app.api_client.post(&url), "no credentials"),
+        (
+            app.api_client
+                .post(&url)
+                .basic_auth(&app.webhook_settings.username, Some("wrong-password")),
+            "a wrong password",
+        ),
+        (
+            app.api_client
+                .post(&url)
+                .basic_auth(Uuid::new_v4().to_string(), Some("wrong-password")),
+            "a wrong username",
+        ),
+    ];
+
+    for (request, description) in test_cases {
+        // Act
+        let response = request.json(&body).send().await.unwrap();
+
+        // Assert
+        assert_eq!(
+            response.headers()["WWW-Authenticate"],
+            r#"Basic realm="webhooks""#,
+            "The webhook did not ask for credentials when the request had {description}",
+        );
+        assert_error_response(response, 401, "invalid_credentials").await;
+    }
+    assert_eq!(suppression_count(&app).await, 0);
+}
+
+#[tokio::test]
+async fn suppressed_addresses_do_not_receive_newsletters() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let email = subscriber_email(&app).await;
+    // E.g. Postmark deactivated the address by hand, without a change to the subscription
+    sqlx::query!(
+        "INSERT INTO suppressions (email, reason, suppressed_at) VALUES ($1, 'hard_bounce', now())",
+        email
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "content_text": "Newsletter body as plain text",
+            "content_html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_successful_response(&response);
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    // Mock verifies on Drop that we haven't sent the newsletter email
+}
+
+#[tokio::test]
+async fn suppressed_addresses_do_not_get_a_confirmation_email() {
+    // Arrange
+    let app = spawn_app().await;
+    let response = app
+        .post_postmark_webhook(&serde_json::json!({
+            "RecordType": "SpamComplaint",
+            "Email": "ursula_le_guin@gmail.com",
+        }))
+        .await;
+    assert_successful_response(&response);
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions(serde_json::json!({
+            "name": "le guin",
+            "email": "ursula_le_guin@gmail.com",
+        }))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    let subscriptions = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(subscriptions.is_empty());
+    // Mock verifies on Drop that we haven't sent the confirmation email
+}
+
+#[tokio::test]
+async fn bounces_are_matched_regardless_of_case() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let email = subscriber_email(&app).await;
+
+    // Act
+    for reported_email in [email.to_uppercase(), email.clone()] {
+        let response = app
+            .post_postmark_webhook(&serde_json::json!({
+                "RecordType": "Bounce",
+                "Type": "HardBounce",
+                "Email": reported_email,
+            }))
+            .await;
+        assert_successful_response(&response);
+    }
+
+    // Assert
+    assert_eq!(subscriber_status(&app).await, "bounced");
+    assert_eq!(suppression_count(&app).await, 1);
+}
+
+#[tokio::test]
+async fn failed_deliveries_to_suppressed_addresses_cannot_be_requeued() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let email = subscriber_email(&app).await;
+    app.login().await;
+    let mock_guard = Mock::given(any())
+        .respond_with(ResponseTemplate::new(422))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "content_text": "Newsletter body as plain text",
+            "content_html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_successful_response(&response);
+    app.dispatch_all_pending_emails().await;
+    drop(mock_guard);
+
+    // Act
+    let response = app
+        .post_postmark_webhook(&serde_json::json!({
+            "RecordType": "SpamComplaint",
+            "Email": email,
+        }))
+        .await;
+
+    // Assert
+    assert_successful_response(&response);
+    let failures: Vec<serde_json::Value> = app.get_delivery_failures().await.json().await.unwrap();
+    assert!(failures.is_empty());
+}
+
+async fn subscriber_email(app: &TestApp) -> String {
+    sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email
+}
+
+async fn subscriber_status(app: &TestApp) -> String {
+    sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status
+}
+
+async fn suppression_count(app: &TestApp) -> i64 {
+    sqlx::query!("SELECT count(*) AS \"count!\" FROM suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count
+}
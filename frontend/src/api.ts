@@ -69,7 +69,68 @@ export function changePassword(
   )
 }
 
-async function fetchWithPost(
+export interface ListPreference {
+  list_id: string
+  name: string
+  description: string
+  status: string | null
+}
+
+export interface Preferences {
+  name: string
+  email: string
+  status: string
+  pending_email: string | null
+  lists: ListPreference[]
+}
+
+export async function getPreferences(
+  unsubscribeToken: string,
+  messages: Messages,
+): Promise<Preferences | null> {
+  const url = `/api/subscriptions/preferences?unsubscribe_token=${encodeURIComponent(unsubscribeToken)}`
+  try {
+    const response = await fetch(url, { method: 'GET' })
+    if (response.ok) {
+      return await response.json()
+    }
+    const responseContent = await response.json()
+    messages.error.value = errorMessage(responseContent.error_id)
+  } catch (error: unknown) {
+    messages.error.value =
+      'An internal front-end error has occured. Apologies for the inconvenience.'
+    console.error(`Error while fetching GET ${url}: ${error}`)
+  }
+  return null
+}
+
+export function updatePreferences(
+  unsubscribeToken: string,
+  name: string,
+  email: string,
+  lists: string[],
+  messages: Messages,
+) {
+  return fetchWithBody(
+    'PATCH',
+    `/api/subscriptions/preferences?unsubscribe_token=${encodeURIComponent(unsubscribeToken)}`,
+    { name, email, lists },
+    messages,
+    'Your preferences have been saved. If you changed your address, follow the link we sent there to confirm it.',
+  )
+}
+
+function fetchWithPost(
+  url: string,
+  body: object,
+  messages: Messages,
+  successMessage: string | null,
+): Promise<boolean> {
+  return fetchWithBody('POST', url, body, messages, successMessage)
+}
+
+async function fetchWithBody(
+  method: string,
   url: string,
   body: object,
   messages: Messages,
@@ -78,19 +139,19 @@ async function fetchWithPost(
   messages.error.value = null
   messages.info.value = null
 
-  console.log(`Fetching POST ${url}`)
+  console.log(`Fetching ${method} ${url}`)
   console.log(body)
 
   try {
     const response = await fetch(url, {
-      method: 'POST',
+      method,
       body: JSON.stringify(body),
       headers: {
         'Content-Type': 'application/json',
       },
     })
 
-    console.log(`Response received from POST ${url}`)
+    console.log(`Response received from ${method} ${url}`)
 
     if (response.ok) {
       messages.info.value = successMessage
@@ -104,7 +165,7 @@ async function fetchWithPost(
       messages.error.value =
         'An internal front-end error has occured. Apologies for the inconvenience.'
     }
-    console.error(`Error while fetching POST ${url}: ${error}`)
+    console.error(`Error while fetching ${method} ${url}: ${error}`)
   }
 
   return false
@@ -119,6 +180,9 @@ function errorMessage(errorId: string): string {
     case 'invalid_data': {
       return 'There was a problem with the form data you entered. Please try again.'
     }
+    case 'conflict': {
+      return 'That change is not possible. If you unsubscribed, please subscribe again first.'
+    }
     case 'send_confirmation_email': {
       return 'We were unable to send a confirmation email to that email address.'
     }
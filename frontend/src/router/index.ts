@@ -4,12 +4,14 @@ import SubscribeForm from '../SubscribeForm.vue'
 import LoginForm from '../LoginForm.vue'
 import SendNewsletterForm from '../SendNewsletterForm.vue'
 import ChangePasswordForm from '../ChangePasswordForm.vue'
+import PreferencesForm from '../PreferencesForm.vue'
 
 const router = createRouter({
   history: createWebHistory(import.meta.env.BASE_URL),
   routes: [
     { path: '/', component: SubscribeForm },
     { path: '/login', component: LoginForm, meta: { breadcrumb: 'Log in' } },
+    { path: '/preferences', component: PreferencesForm, meta: { breadcrumb: 'Preferences' } },
     {
       path: '/admin/newsletters',
       component: SendNewsletterForm,